@@ -0,0 +1,204 @@
+use std::collections::{ HashMap };
+use std::rc::Rc;
+
+use sc2::data::{ Point2, Tag };
+use tantrum::{ ResourceCluster };
+
+/// base locations that appeared or vanished in the last update
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BaseLocationChanges {
+    /// locations that were not there before
+    pub added:              Vec<Point2>,
+    /// locations that are gone
+    pub removed:            Vec<Point2>,
+}
+
+impl BaseLocationChanges {
+    /// true if no locations were added or removed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// remembers the base location of every resource cluster
+///
+/// clusters are identified by the tags of their resources, so a location is
+/// only solved again when a resource is mined out or a new one is revealed.
+pub struct BaseLocationCache {
+    solved:                 HashMap<Vec<Tag>, Option<Point2>>,
+    locations:              Rc<Vec<Point2>>,
+    changes:                BaseLocationChanges,
+}
+
+impl BaseLocationCache {
+    /// an empty cache
+    pub fn new() -> Self {
+        Self {
+            solved: HashMap::new(),
+            locations: Rc::from(vec![ ]),
+            changes: BaseLocationChanges::default(),
+        }
+    }
+
+    /// the locations of the clusters from the last update
+    ///
+    /// the same `Rc` is handed out until the locations change.
+    pub fn locations(&self) -> Rc<Vec<Point2>> {
+        Rc::clone(&self.locations)
+    }
+
+    /// what changed in the last update
+    pub fn changes(&self) -> &BaseLocationChanges {
+        &self.changes
+    }
+
+    /// solve the clusters that are not in the cache and forget the ones that
+    /// are gone
+    pub fn update<F>(&mut self, clusters: &[ResourceCluster], solve: F)
+        where F: FnMut(&ResourceCluster) -> Option<Point2>
+    {
+        self.update_with(clusters, cluster_key, solve)
+    }
+
+    /// update with the clusters identified by the given key
+    fn update_with<C, K, F>(&mut self, clusters: &[C], key: K, mut solve: F)
+        where K: Fn(&C) -> Vec<Tag>, F: FnMut(&C) -> Option<Point2>
+    {
+        let mut solved = HashMap::new();
+        let mut locations = vec![ ];
+
+        for cluster in clusters {
+            let key = key(cluster);
+
+            let location = match self.solved.get(&key) {
+                Some(&location) => location,
+                None => solve(cluster)
+            };
+
+            if let Some(location) = location {
+                locations.push(location);
+            }
+
+            solved.insert(key, location);
+        }
+
+        self.solved = solved;
+
+        self.changes = BaseLocationChanges {
+            added: locations.iter()
+                .filter(|l| !self.locations.contains(l))
+                .cloned()
+                .collect(),
+            removed: self.locations.iter()
+                .filter(|l| !locations.contains(l))
+                .cloned()
+                .collect(),
+        };
+
+        if *self.locations != locations {
+            self.locations = Rc::from(locations);
+        }
+    }
+}
+
+/// the sorted tags of the resources in a cluster
+pub fn cluster_key(cluster: &ResourceCluster) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = cluster.resources.iter().map(|r| r.tag).collect();
+
+    tags.sort();
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// clusters are the tags of their resources, located at the sum of
+    /// their tags
+    fn location(cluster: &[Tag]) -> Option<Point2> {
+        Some(Point2::new(cluster.iter().sum::<Tag>() as f32, 0.0))
+    }
+
+    /// update the cache, returning the clusters that had to be solved
+    fn update(cache: &mut BaseLocationCache, clusters: &[Vec<Tag>])
+        -> Vec<Vec<Tag>>
+    {
+        let mut solved = vec![ ];
+
+        cache.update_with(
+            clusters,
+            |cluster| cluster.clone(),
+            |cluster| {
+                solved.push(cluster.clone());
+                location(cluster)
+            }
+        );
+
+        solved
+    }
+
+    #[test]
+    fn clusters_are_only_solved_once() {
+        let mut cache = BaseLocationCache::new();
+        let clusters = vec![ vec![ 10, 20 ], vec![ 3, 4 ] ];
+
+        assert_eq!(update(&mut cache, &clusters), clusters);
+
+        let locations = cache.locations();
+
+        assert!(update(&mut cache, &clusters).is_empty());
+        assert!(cache.changes().is_empty());
+        assert!(Rc::ptr_eq(&locations, &cache.locations()));
+    }
+
+    #[test]
+    fn clusters_are_solved_again_when_their_tags_change() {
+        let mut cache = BaseLocationCache::new();
+
+        update(&mut cache, &[ vec![ 10, 20 ], vec![ 3, 4 ] ]);
+
+        // a mineral field of the second cluster was mined out
+        assert_eq!(
+            update(&mut cache, &[ vec![ 10, 20 ], vec![ 3 ] ]),
+            vec![ vec![ 3 ] ]
+        );
+        assert_eq!(
+            cache.changes(),
+            &BaseLocationChanges {
+                added: vec![ Point2::new(3.0, 0.0) ],
+                removed: vec![ Point2::new(7.0, 0.0) ],
+            }
+        );
+        assert_eq!(
+            *cache.locations(),
+            vec![ Point2::new(30.0, 0.0), Point2::new(3.0, 0.0) ]
+        );
+
+        // the forgotten tag set is solved from scratch when it comes back
+        assert_eq!(
+            update(&mut cache, &[ vec![ 10, 20 ], vec![ 3, 4 ] ]),
+            vec![ vec![ 3, 4 ] ]
+        );
+    }
+
+    #[test]
+    fn unsolvable_clusters_are_cached_too() {
+        let mut cache = BaseLocationCache::new();
+        let mut calls = 0;
+
+        for _ in 0..2 {
+            cache.update_with(
+                &[ vec![ 1 ] ],
+                |cluster| cluster.clone(),
+                |_| {
+                    calls += 1;
+                    None
+                }
+            );
+        }
+
+        assert_eq!(calls, 1);
+        assert!(cache.locations().is_empty());
+    }
+}
@@ -0,0 +1,593 @@
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::fmt;
+use std::rc::Rc;
+
+use cortical;
+use sc2;
+use sc2::data::{
+    Alliance, DisplayType, ImageData, Point2, Tag, Unit, UnitType
+};
+use tantrum::{ ResourceCluster };
+
+use super::{
+    BaseLocationChanges,
+    GroundDistances,
+    KeliConstraint,
+    KeliData,
+
+    cluster_key,
+    is_geyser,
+};
+
+/// how close our first hatchery has to be to a site to make it the main
+const MAIN_RADIUS: f32 = 8.0;
+
+/// the role of a base site relative to our starting base
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BaseKind {
+    /// the site we started the game on
+    Main,
+    /// the site closest to the main over the ground
+    Natural,
+    /// any other site
+    Other,
+}
+
+/// a potential base location along with what makes it worth taking
+#[derive(Clone)]
+pub struct BaseSite {
+    /// where the town hall goes
+    pub location:           Point2,
+    /// the minerals left in the cluster, or `None` while part of it is only
+    /// known from a snapshot, since snapshots report no contents
+    pub minerals:           Option<u32>,
+    /// the vespene left in the cluster, or `None` while part of it is only
+    /// known from a snapshot
+    pub vespene:            Option<u32>,
+    /// the number of vespene geysers in the cluster
+    pub geysers:            u32,
+    /// whether this is our main, our natural or neither
+    pub kind:               BaseKind,
+    /// the terrain height at the location, as stored in the height map
+    pub height:             u8,
+    /// the ground distance from our main in tiles, if it can be reached
+    pub ground_distance:    Option<u32>,
+
+    clusters:               Rc<Vec<ResourceCluster>>,
+    cluster:                usize,
+}
+
+impl BaseSite {
+    /// the resource cluster the location was found for
+    pub fn cluster(&self) -> &ResourceCluster {
+        &self.clusters[self.cluster]
+    }
+}
+
+impl fmt::Debug for BaseSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BaseSite")
+            .field("location", &self.location)
+            .field("minerals", &self.minerals)
+            .field("vespene", &self.vespene)
+            .field("geysers", &self.geysers)
+            .field("kind", &self.kind)
+            .field("height", &self.height)
+            .field("ground_distance", &self.ground_distance)
+            .field("cluster", &self.cluster)
+            .finish()
+    }
+}
+
+/// the center of the resources in a cluster
+fn cluster_center(cluster: &ResourceCluster) -> Point2 {
+    let n = cluster.resources.len() as f32;
+
+    Point2::new(
+        cluster.resources.iter().map(|r| r.pos.x).sum::<f32>() / n,
+        cluster.resources.iter().map(|r| r.pos.y).sum::<f32>() / n
+    )
+}
+
+/// the key of the cluster whose center is closest to the location
+fn nearest_cluster(location: Point2, clusters: &[(Vec<Tag>, Point2)])
+    -> Option<&Vec<Tag>>
+{
+    let squared = |c: Point2| {
+        (location.x - c.x) * (location.x - c.x)
+            + (location.y - c.y) * (location.y - c.y)
+    };
+
+    clusters.iter().fold(
+        None,
+        |best: Option<&(Vec<Tag>, Point2)>, cluster| match best {
+            Some(best) if squared(best.1) <= squared(cluster.1) => Some(best),
+            _ => Some(cluster)
+        }
+    ).map(
+        |&(ref key, _)| key
+    )
+}
+
+/// match added locations with the nearest cluster and forget removed ones
+///
+/// clusters are given by their key and center. a cluster that lost or gained
+/// a resource has a new key, so its location is matched again even if it
+/// stayed put.
+fn match_locations(
+    matches: &mut Vec<(Point2, Vec<Tag>)>,
+    clusters: &[(Vec<Tag>, Point2)],
+    changes: &BaseLocationChanges
+) {
+    matches.retain(|&(l, _)| !changes.removed.contains(&l));
+
+    for &location in &changes.added {
+        matches.push((location, vec![ ]));
+    }
+
+    for m in matches.iter_mut() {
+        if clusters.iter().any(|&(ref key, _)| *key == m.1) {
+            continue
+        }
+
+        if let Some(key) = nearest_cluster(m.0, clusters) {
+            m.1 = key.clone();
+        }
+    }
+}
+
+/// what is left in the resources of a cluster
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Contents {
+    minerals:               Option<u32>,
+    vespene:                Option<u32>,
+    geysers:                u32,
+}
+
+/// add up the resources of a cluster
+///
+/// snapshots of fogged resources report no contents, so the totals are
+/// unknown while any of them is fogged. geysers are told apart by type, so
+/// they are counted either way.
+fn count_contents<R: Borrow<Unit>>(resources: &[R]) -> Contents {
+    let snapshot = resources.iter().any(
+        |r| r.borrow().display_type == DisplayType::Snapshot
+    );
+
+    Contents {
+        minerals: if snapshot {
+            None
+        }
+        else {
+            Some(resources.iter().map(|r| r.borrow().mineral_contents).sum())
+        },
+        vespene: if snapshot {
+            None
+        }
+        else {
+            Some(resources.iter().map(|r| r.borrow().vespene_contents).sum())
+        },
+        geysers: resources.iter()
+            .filter(|r| is_geyser(r.borrow().unit_type))
+            .count() as u32,
+    }
+}
+
+/// the raw value of the height map at a location
+fn height_at(heights: &ImageData, location: Point2) -> u8 {
+    let x = location.x as i32;
+    let y = location.y as i32;
+
+    if x < 0 || y < 0 || x >= heights.width || y >= heights.height {
+        return 0
+    }
+
+    let i = (x + y * heights.width) as usize;
+
+    match heights.bits_per_pixel {
+        8 => heights.data.get(i).cloned().unwrap_or(0),
+        _ => 0
+    }
+}
+
+/// describes every potential base location in terms of its resources and
+/// its position relative to our main
+///
+/// each location is matched with the cluster whose center is closest to it,
+/// so any base locator can feed this lobe. locations are only matched when
+/// they are added, or when the resources of their cluster change.
+pub struct BaseSiteLobe {
+    main:                   Option<Point2>,
+    distances:              Option<GroundDistances>,
+
+    /// every location with the key of the cluster it was matched with
+    matches:                Vec<(Point2, Vec<Tag>)>,
+
+    sites:                  Rc<Vec<BaseSite>>,
+}
+
+impl BaseSiteLobe {
+    /// describe the sites of the incoming base locations
+    pub fn new() -> Self {
+        Self {
+            main: None,
+            distances: None,
+
+            matches: vec![ ],
+
+            sites: Rc::from(vec![ ]),
+        }
+    }
+
+    /// match added locations with a cluster and forget removed ones
+    ///
+    /// returns the index of every current cluster by its key.
+    fn rematch(
+        &mut self,
+        clusters: &[ResourceCluster],
+        changes: &BaseLocationChanges
+    )
+        -> HashMap<Vec<Tag>, usize>
+    {
+        let centers: Vec<(Vec<Tag>, Point2)> = clusters.iter().map(
+            |cluster| (cluster_key(cluster), cluster_center(cluster))
+        ).collect();
+
+        match_locations(&mut self.matches, &centers, changes);
+
+        centers.into_iter()
+            .enumerate()
+            .map(|(i, (key, _))| (key, i))
+            .collect()
+    }
+
+    /// remember where the first hatchery stood and how far everything is
+    fn find_main(&mut self, frame: &sc2::FrameData) {
+        if self.main.is_some() {
+            return
+        }
+
+        let hatcheries = frame.state.filter_units(
+            |u| u.unit_type == UnitType::ZergHatchery
+                && u.alliance == Alliance::Domestic
+        );
+
+        if let Some(main) = hatcheries.first() {
+            let main = Point2::new(main.pos.x, main.pos.y);
+
+            self.main = Some(main);
+            self.distances = Some(
+                GroundDistances::search(
+                    &frame.data.terrain_info.pathing_grid, main
+                )
+            );
+        }
+    }
+
+    fn describe(
+        &self,
+        frame: &sc2::FrameData,
+        clusters: &Rc<Vec<ResourceCluster>>,
+        locations: &[Point2],
+        keys: &HashMap<Vec<Tag>, usize>
+    )
+        -> Vec<BaseSite>
+    {
+        if clusters.is_empty() {
+            return vec![ ]
+        }
+
+        let mut sites: Vec<BaseSite> = locations.iter().filter_map(
+            |&location| self.matches.iter().find(|&&(l, _)| l == location)
+        ).map(
+            |&(location, ref key)| {
+                let cluster = keys[key];
+                let contents = count_contents(&clusters[cluster].resources);
+
+                BaseSite {
+                    location: location,
+                    minerals: contents.minerals,
+                    vespene: contents.vespene,
+                    geysers: contents.geysers,
+                    kind: BaseKind::Other,
+                    height: height_at(
+                        &frame.data.terrain_info.terrain_height, location
+                    ),
+                    ground_distance: match self.distances {
+                        Some(ref distances) => distances.distance_to(location),
+                        None => None
+                    },
+
+                    clusters: Rc::clone(clusters),
+                    cluster: cluster,
+                }
+            }
+        ).collect();
+
+        let main = match self.main {
+            Some(main) => main,
+            None => return sites
+        };
+
+        let squared = |a: Point2, b: Point2| {
+            (a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)
+        };
+
+        let main_site = (0..sites.len())
+            .filter(
+                |&i| squared(sites[i].location, main)
+                    < MAIN_RADIUS * MAIN_RADIUS
+            )
+            .min_by(
+                |&a, &b| squared(sites[a].location, main).partial_cmp(
+                    &squared(sites[b].location, main)
+                ).unwrap()
+            )
+        ;
+
+        if let Some(i) = main_site {
+            sites[i].kind = BaseKind::Main;
+        }
+
+        let natural = (0..sites.len())
+            .filter(|&i| Some(i) != main_site)
+            .filter_map(|i| sites[i].ground_distance.map(|d| (i, d)))
+            .min_by_key(|&(_, d)| d)
+            .map(|(i, _)| i)
+        ;
+
+        if let Some(i) = natural {
+            sites[i].kind = BaseKind::Natural;
+        }
+
+        sites
+    }
+}
+
+create_lobe_data! {
+    module: base_site,
+
+    req frame: Rc<sc2::FrameData>,
+    req clusters: Rc<Vec<ResourceCluster>>,
+    req locations: Rc<Vec<Point2>>,
+    req changes: BaseLocationChanges,
+
+    out sites: Rc<Vec<BaseSite>>,
+}
+
+pub use self::base_site::{
+    Input as BaseSiteInput,
+    Output as BaseSiteOutput,
+    FeedbackInput as BaseSiteFeedbackInput,
+    FeedbackOutput as BaseSiteFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: BaseSiteLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: BaseSiteInput,
+    output: BaseSiteOutput,
+    feedback_input: BaseSiteFeedbackInput,
+    feedback_output: BaseSiteFeedbackOutput,
+
+    req frame: FrameData,
+    req clusters: Resources,
+    req locations: PotentialBaseLocations,
+    req changes: BaseLocationChanges,
+
+    out sites: BaseSites,
+}
+
+impl cortical::Lobe for BaseSiteLobe {
+    type Input = BaseSiteInput;
+    type Output = BaseSiteOutput;
+    type FeedbackInput = BaseSiteFeedbackInput;
+    type FeedbackOutput = BaseSiteFeedbackOutput;
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.find_main(&input.frame);
+
+        let keys = self.rematch(&input.clusters, &input.changes);
+
+        self.sites = Rc::from(
+            self.describe(
+                &input.frame, &input.clusters, &input.locations, &keys
+            )
+        );
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(BaseSiteOutput { sites: Rc::clone(&self.sites) })
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(BaseSiteFeedbackOutput { })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::{
+        RecordedAlliance,
+        RecordedDisplayType,
+        RecordedFrame,
+        RecordedImage,
+        RecordedState,
+        RecordedTerrain,
+        RecordedUnit,
+        Recording,
+    };
+
+    fn image() -> RecordedImage {
+        RecordedImage { width: 0, height: 0, bits_per_pixel: 8, data: vec![ ] }
+    }
+
+    fn resource(tag: Tag, unit_type: UnitType, contents: u32, fogged: bool)
+        -> RecordedUnit
+    {
+        let geyser = is_geyser(unit_type);
+
+        RecordedUnit {
+            tag: tag,
+            unit_type: unit_type as u32,
+            display_type: if fogged {
+                RecordedDisplayType::Snapshot
+            }
+            else {
+                RecordedDisplayType::Visible
+            },
+            alliance: RecordedAlliance::Neutral,
+            owner: 0,
+            pos: (tag as f32, 0.0, 0.0),
+            facing: 0.0,
+            radius: 1.0,
+            build_progress: 1.0,
+            health: (0.0, 0.0),
+            shield: (0.0, 0.0),
+            energy: (0.0, 0.0),
+            is_flying: false,
+            is_burrowed: false,
+            orders: vec![ ],
+            mineral_contents: if fogged || geyser { 0 } else { contents },
+            vespene_contents: if fogged || !geyser { 0 } else { contents },
+        }
+    }
+
+    /// the resources as the game reports them
+    fn units(resources: Vec<RecordedUnit>) -> Vec<Rc<Unit>> {
+        let recording = Recording {
+            terrain: RecordedTerrain {
+                width: 0,
+                height: 0,
+                pathing_grid: image(),
+                placement_grid: image(),
+                terrain_height: image(),
+                playable_min: (0.0, 0.0),
+                playable_max: (0.0, 0.0),
+                enemy_start_locations: vec![ ],
+            },
+            unit_types: vec![ ],
+            frames: vec![
+                RecordedFrame {
+                    state: RecordedState {
+                        current_step: 0,
+                        minerals: 0,
+                        vespene: 0,
+                        food_used: 0,
+                        food_cap: 0,
+                        larva_count: 0,
+                    },
+                    units: resources,
+                    creep: image(),
+                    visibility: image(),
+                },
+            ],
+        };
+
+        recording.frame_data().unwrap()[0].state.units.clone()
+    }
+
+    #[test]
+    fn visible_clusters_add_up_their_contents() {
+        let contents = count_contents(
+            &units(
+                vec![
+                    resource(1, UnitType::NeutralMineralField, 1800, false),
+                    resource(2, UnitType::NeutralMineralField750, 750, false),
+                    resource(3, UnitType::NeutralVespeneGeyser, 2250, false),
+                ]
+            )
+        );
+
+        assert_eq!(
+            contents,
+            Contents { minerals: Some(2550), vespene: Some(2250), geysers: 1 }
+        );
+    }
+
+    #[test]
+    fn fogged_clusters_still_count_their_geysers() {
+        let contents = count_contents(
+            &units(
+                vec![
+                    resource(1, UnitType::NeutralMineralField, 1800, false),
+                    resource(2, UnitType::NeutralRichVespeneGeyser, 0, true),
+                    resource(3, UnitType::NeutralVespeneGeyser, 0, true),
+                ]
+            )
+        );
+
+        assert_eq!(
+            contents, Contents { minerals: None, vespene: None, geysers: 2 }
+        );
+    }
+
+    fn changes(added: &[Point2], removed: &[Point2]) -> BaseLocationChanges {
+        BaseLocationChanges {
+            added: added.to_vec(),
+            removed: removed.to_vec(),
+        }
+    }
+
+    #[test]
+    fn added_locations_are_matched_with_the_nearest_cluster() {
+        let clusters = vec![
+            (vec![ 1, 2 ], Point2::new(10.0, 10.0)),
+            (vec![ 3, 4 ], Point2::new(50.0, 50.0)),
+        ];
+
+        let near = Point2::new(45.0, 45.0);
+        let far = Point2::new(12.0, 15.0);
+
+        let mut matches = vec![ ];
+        match_locations(
+            &mut matches, &clusters, &changes(&[ near, far ], &[ ])
+        );
+
+        assert_eq!(matches, vec![ (near, vec![ 3, 4 ]), (far, vec![ 1, 2 ]) ]);
+
+        // removed locations are forgotten
+        match_locations(&mut matches, &clusters, &changes(&[ ], &[ near ]));
+
+        assert_eq!(matches, vec![ (far, vec![ 1, 2 ]) ]);
+    }
+
+    #[test]
+    fn locations_are_matched_again_when_their_cluster_changes() {
+        let location = Point2::new(12.0, 15.0);
+        let mut matches = vec![ (location, vec![ 1, 2 ]) ];
+
+        // a mineral field was mined out, so the cluster has a new key
+        match_locations(
+            &mut matches,
+            &[
+                (vec![ 1 ], Point2::new(10.0, 10.0)),
+                (vec![ 3, 4 ], Point2::new(50.0, 50.0)),
+            ],
+            &changes(&[ ], &[ ])
+        );
+
+        assert_eq!(matches, vec![ (location, vec![ 1 ]) ]);
+    }
+
+    #[test]
+    fn locations_keep_their_match_without_clusters() {
+        let location = Point2::new(12.0, 15.0);
+        let mut matches = vec![ (location, vec![ 1, 2 ]) ];
+
+        match_locations(&mut matches, &[ ], &changes(&[ ], &[ ]));
+
+        assert_eq!(matches, vec![ (location, vec![ 1, 2 ]) ]);
+    }
+}
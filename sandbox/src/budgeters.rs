@@ -1,12 +1,22 @@
 
-use std::collections::{ HashMap };
+use std::cmp;
+use std::collections::{ HashMap, VecDeque };
 use std::ops;
 use std::rc::Rc;
 
 use cortical;
-use rand::random;
+use rand::{ Rng };
 use sc2;
-use super::{ KeliConstraint, KeliData };
+use sc2::data::{ Ability, Tag, Unit, UnitType, UnitTypeData };
+use super::{
+    KeliConstraint,
+    KeliData,
+    LobeNames,
+    LobeRng,
+
+    seeded_rng,
+    unseeded_rng,
+};
 
 /// a resource budget for a lobe
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -44,6 +54,184 @@ impl LobeBudget {
     }
 }
 
+/// whether a spender still has a use for its budget
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SpenderStatus {
+    /// the spender wants its share of the budget
+    Active,
+    /// the spender has nothing to spend on for now, but may again later
+    Idle,
+    /// the spender has finished everything it was meant to do
+    Done,
+}
+
+impl SpenderStatus {
+    /// true if the spender should be given a share of the budget
+    pub fn is_active(&self) -> bool {
+        *self == SpenderStatus::Active
+    }
+}
+
+impl Default for SpenderStatus {
+    fn default() -> Self {
+        SpenderStatus::Active
+    }
+}
+
+/// what a spender wants to buy next, sent back to its ledger as feedback
+#[derive(Debug, Copy, Clone)]
+pub struct LobeBudgetRequest {
+    /// the lobe making the request
+    pub lobe:               cortical::NodeHdl,
+    /// the cost of the next purchase, or `None` if the lobe can make use of
+    /// any budget it is given
+    pub cost:               Option<Budget>,
+    /// how badly the purchase is needed, higher is more urgent
+    pub urgency:            u32,
+    /// whether the lobe wants any budget at all. ledgers only split their
+    /// budget between active outputs
+    pub status:             SpenderStatus,
+}
+
+impl LobeBudgetRequest {
+    /// combine the requests of several outputs into one request
+    ///
+    /// costs of active outputs are summed and the highest urgency wins. if any
+    /// output can use any budget, so can the combined request. the combined
+    /// request is active if any output is active, idle if any output is idle
+    /// and done once every output is done.
+    pub fn combine<'a, I>(lobe: cortical::NodeHdl, requests: I) -> Self
+        where I: IntoIterator<Item=&'a LobeBudgetRequest>
+    {
+        let mut combined = Self {
+            lobe: lobe,
+            cost: Some(Budget::default()),
+            urgency: 0,
+            status: SpenderStatus::Done,
+        };
+
+        let mut is_empty = true;
+
+        for request in requests {
+            is_empty = false;
+
+            match request.status {
+                SpenderStatus::Active => {
+                    combined.status = SpenderStatus::Active
+                },
+                SpenderStatus::Idle => {
+                    if combined.status == SpenderStatus::Done {
+                        combined.status = SpenderStatus::Idle;
+                    }
+
+                    continue
+                },
+                SpenderStatus::Done => continue,
+            }
+
+            combined.cost = match (combined.cost, request.cost) {
+                (Some(total), Some(cost)) => Some(total + cost),
+                _ => None
+            };
+
+            if request.urgency > combined.urgency {
+                combined.urgency = request.urgency;
+            }
+        }
+
+        // nothing has been heard from the outputs yet
+        if is_empty {
+            combined.status = SpenderStatus::Active;
+        }
+
+        combined
+    }
+}
+
+/// make sure the outputs are never allotted more than the ledger was given
+///
+/// outputs that spent less than their share in the past are owed more than
+/// what is on hand, so every resource that falls short is scaled down in
+/// proportion to what each output is owed. without this, a nested ledger
+/// could pass on more than its parent allotted to it.
+fn limit_allotted(
+    allotted: &mut HashMap<cortical::NodeHdl, Budget>,
+    outputs: &Vec<cortical::NodeHdl>,
+    available: Budget
+) {
+    // sum in 64 bits so the totals can't saturate
+    let total = |resource: fn(&Budget) -> u32| outputs.iter()
+        .map(|o| resource(&allotted[o]) as u64)
+        .sum::<u64>()
+    ;
+
+    let minerals = total(|b| b.minerals);
+    let vespene = total(|b| b.vespene);
+    let food = total(|b| b.food);
+    let larva = total(|b| b.larva);
+
+    for o in outputs {
+        let budget = allotted.get_mut(o).unwrap();
+
+        budget.minerals = scale_share(
+            budget.minerals, minerals, available.minerals
+        );
+        budget.vespene = scale_share(
+            budget.vespene, vespene, available.vespene
+        );
+        budget.food = scale_share(budget.food, food, available.food);
+        budget.larva = scale_share(budget.larva, larva, available.larva);
+    }
+}
+
+/// scale a share of a total down so that the total fits what is available
+///
+/// rounding down keeps the sum of the shares within what is available.
+fn scale_share(share: u32, total: u64, available: u32) -> u32 {
+    if total <= available as u64 {
+        share
+    }
+    else {
+        (share as u64 * available as u64 / total) as u32
+    }
+}
+
+/// the outputs that should be given a share of the budget
+///
+/// outputs that have not sent a request yet are assumed to be active.
+fn active_outputs(
+    outputs: &Vec<cortical::NodeHdl>,
+    requests: &HashMap<cortical::NodeHdl, LobeBudgetRequest>
+)
+    -> Vec<cortical::NodeHdl>
+{
+    outputs.iter()
+        .filter(
+            |o| requests.get(o).map_or(true, |r| r.status.is_active())
+        )
+        .cloned()
+        .collect()
+}
+
+/// take the cost of released reservations back out of what their spenders
+/// have spent
+///
+/// the game never charged for orders that were dropped or timed out, so
+/// counting them would shrink the spender's share for good. only spenders
+/// directly below the ledger are tracked.
+fn refund_released(
+    spenders: &mut HashMap<cortical::NodeHdl, Budget>,
+    released: &Option<Vec<Reservation>>
+) {
+    if let Some(ref released) = *released {
+        for r in released {
+            if let Some(spent) = spenders.get_mut(&r.lobe) {
+                *spent = spent.saturating_sub(r.cost);
+            }
+        }
+    }
+}
+
 impl ops::AddAssign for Budget {
     fn add_assign(&mut self, rhs: Budget) {
         *self = *self + rhs;
@@ -65,28 +253,21 @@ impl ops::DivAssign<u32> for Budget {
     }
 }
 
+// budgets can't go negative or overflow, so the operators saturate. use
+// `checked_add` and `checked_sub` where running out needs to be noticed
+
 impl ops::Add for Budget {
     type Output = Budget;
 
     fn add(self, rhs: Budget) -> Budget {
-        Budget {
-            minerals: self.minerals + rhs.minerals,
-            vespene: self.vespene + rhs.vespene,
-            food: self.food + rhs.food,
-            larva: self.larva + rhs.larva
-        }
+        self.saturating_add(rhs)
     }
 }
 impl ops::Sub for Budget {
     type Output = Budget;
 
     fn sub(self, rhs: Budget) -> Budget {
-        Budget {
-            minerals: self.minerals - rhs.minerals,
-            vespene: self.vespene - rhs.vespene,
-            food: self.food - rhs.food,
-            larva: self.larva - rhs.larva,
-        }
+        self.saturating_sub(rhs)
     }
 }
 impl ops::Mul<u32> for Budget {
@@ -94,10 +275,10 @@ impl ops::Mul<u32> for Budget {
 
     fn mul(self, rhs: u32) -> Budget {
         Budget {
-            minerals: self.minerals * rhs,
-            vespene: self.vespene * rhs,
-            food: self.food * rhs,
-            larva: self.larva * rhs,
+            minerals: self.minerals.saturating_mul(rhs),
+            vespene: self.vespene.saturating_mul(rhs),
+            food: self.food.saturating_mul(rhs),
+            larva: self.larva.saturating_mul(rhs),
         }
     }
 }
@@ -114,6 +295,345 @@ impl ops::Div<u32> for Budget {
     }
 }
 
+impl Budget {
+    /// add two budgets, returning `None` if any resource overflows
+    pub fn checked_add(self, rhs: Budget) -> Option<Budget> {
+        match (
+            self.minerals.checked_add(rhs.minerals),
+            self.vespene.checked_add(rhs.vespene),
+            self.food.checked_add(rhs.food),
+            self.larva.checked_add(rhs.larva)
+        ) {
+            (Some(minerals), Some(vespene), Some(food), Some(larva)) => Some(
+                Budget {
+                    minerals: minerals,
+                    vespene: vespene,
+                    food: food,
+                    larva: larva,
+                }
+            ),
+            _ => None
+        }
+    }
+
+    /// subtract two budgets, returning `None` if any resource would go
+    /// negative
+    pub fn checked_sub(self, rhs: Budget) -> Option<Budget> {
+        match (
+            self.minerals.checked_sub(rhs.minerals),
+            self.vespene.checked_sub(rhs.vespene),
+            self.food.checked_sub(rhs.food),
+            self.larva.checked_sub(rhs.larva)
+        ) {
+            (Some(minerals), Some(vespene), Some(food), Some(larva)) => Some(
+                Budget {
+                    minerals: minerals,
+                    vespene: vespene,
+                    food: food,
+                    larva: larva,
+                }
+            ),
+            _ => None
+        }
+    }
+
+    /// add two budgets, clamping each resource at `u32::MAX`
+    pub fn saturating_add(self, rhs: Budget) -> Budget {
+        Budget {
+            minerals: self.minerals.saturating_add(rhs.minerals),
+            vespene: self.vespene.saturating_add(rhs.vespene),
+            food: self.food.saturating_add(rhs.food),
+            larva: self.larva.saturating_add(rhs.larva),
+        }
+    }
+
+    /// subtract two budgets, clamping each resource at zero
+    pub fn saturating_sub(self, rhs: Budget) -> Budget {
+        Budget {
+            minerals: self.minerals.saturating_sub(rhs.minerals),
+            vespene: self.vespene.saturating_sub(rhs.vespene),
+            food: self.food.saturating_sub(rhs.food),
+            larva: self.larva.saturating_sub(rhs.larva),
+        }
+    }
+
+    /// the smaller of each resource
+    pub fn min(self, rhs: Budget) -> Budget {
+        Budget {
+            minerals: cmp::min(self.minerals, rhs.minerals),
+            vespene: cmp::min(self.vespene, rhs.vespene),
+            food: cmp::min(self.food, rhs.food),
+            larva: cmp::min(self.larva, rhs.larva),
+        }
+    }
+
+    /// the larger of each resource
+    pub fn max(self, rhs: Budget) -> Budget {
+        Budget {
+            minerals: cmp::max(self.minerals, rhs.minerals),
+            vespene: cmp::max(self.vespene, rhs.vespene),
+            food: cmp::max(self.food, rhs.food),
+            larva: cmp::max(self.larva, rhs.larva),
+        }
+    }
+
+    /// true if every resource is covered by the other budget
+    pub fn fits_within(&self, other: &Budget) -> bool {
+        self.minerals <= other.minerals
+            && self.vespene <= other.vespene
+            && self.food <= other.food
+            && self.larva <= other.larva
+    }
+
+    /// the signed difference between two budgets
+    pub fn delta(self, rhs: Budget) -> BudgetDelta {
+        BudgetDelta::from(self) - BudgetDelta::from(rhs)
+    }
+
+    /// the cost of a single morph into the given unit type
+    ///
+    /// larva morphs cost one larva and may produce more than one unit (a pair
+    /// of zerglings costs twice the listed minerals and one supply slot).
+    /// fractional supply is rounded up per morph. structures consume the
+    /// drone that morphs into them, which frees its supply slot, so they
+    /// need neither supply nor larva.
+    pub fn cost_of(data: &UnitTypeData) -> Budget {
+        match MorphSource::of(data.unit_type) {
+            MorphSource::Larva { per_morph } => Budget {
+                minerals: data.mineral_cost * per_morph,
+                vespene: data.vespene_cost * per_morph,
+                food: (data.food_required * per_morph as f32).ceil() as u32,
+                larva: 1,
+            },
+            MorphSource::Drone => Budget {
+                minerals: data.mineral_cost,
+                vespene: data.vespene_cost,
+
+                ..Budget::default()
+            },
+            MorphSource::Other => Budget {
+                minerals: data.mineral_cost,
+                vespene: data.vespene_cost,
+                food: data.food_required.ceil() as u32,
+
+                ..Budget::default()
+            },
+        }
+    }
+
+    /// the cost of using an ability that produces a unit
+    ///
+    /// returns `None` if no unit type is produced by the ability.
+    pub fn cost_of_ability(ability: Ability, data: &sc2::GameData)
+        -> Option<Budget>
+    {
+        data.unit_type_data.values()
+            .find(|d| d.ability == ability)
+            .map(|d| Budget::cost_of(d))
+    }
+}
+
+/// a signed difference between two budgets
+///
+/// negative resources are a debt, ie. more was spent than was allotted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BudgetDelta {
+    /// the change in minerals
+    pub minerals:           i64,
+    /// the change in vespene
+    pub vespene:            i64,
+    /// the change in supply slots
+    pub food:               i64,
+    /// the change in larva
+    pub larva:              i64,
+}
+
+impl BudgetDelta {
+    /// true if any resource is negative
+    pub fn is_debt(&self) -> bool {
+        self.minerals < 0 || self.vespene < 0 || self.food < 0 || self.larva < 0
+    }
+
+    /// the negative part of each resource as a budget
+    pub fn debt(&self) -> Budget {
+        Budget {
+            minerals: clamp_u32(-self.minerals),
+            vespene: clamp_u32(-self.vespene),
+            food: clamp_u32(-self.food),
+            larva: clamp_u32(-self.larva),
+        }
+    }
+
+    /// the positive part of each resource as a budget
+    pub fn surplus(&self) -> Budget {
+        Budget {
+            minerals: clamp_u32(self.minerals),
+            vespene: clamp_u32(self.vespene),
+            food: clamp_u32(self.food),
+            larva: clamp_u32(self.larva),
+        }
+    }
+}
+
+fn clamp_u32(value: i64) -> u32 {
+    cmp::max(0, cmp::min(value, u32::max_value() as i64)) as u32
+}
+
+impl Default for BudgetDelta {
+    fn default() -> Self {
+        Self {
+            minerals: 0,
+            vespene: 0,
+            food: 0,
+            larva: 0
+        }
+    }
+}
+
+impl From<Budget> for BudgetDelta {
+    fn from(budget: Budget) -> Self {
+        Self {
+            minerals: budget.minerals as i64,
+            vespene: budget.vespene as i64,
+            food: budget.food as i64,
+            larva: budget.larva as i64,
+        }
+    }
+}
+
+impl ops::Add for BudgetDelta {
+    type Output = BudgetDelta;
+
+    fn add(self, rhs: BudgetDelta) -> BudgetDelta {
+        BudgetDelta {
+            minerals: self.minerals + rhs.minerals,
+            vespene: self.vespene + rhs.vespene,
+            food: self.food + rhs.food,
+            larva: self.larva + rhs.larva,
+        }
+    }
+}
+impl ops::Sub for BudgetDelta {
+    type Output = BudgetDelta;
+
+    fn sub(self, rhs: BudgetDelta) -> BudgetDelta {
+        BudgetDelta {
+            minerals: self.minerals - rhs.minerals,
+            vespene: self.vespene - rhs.vespene,
+            food: self.food - rhs.food,
+            larva: self.larva - rhs.larva,
+        }
+    }
+}
+
+/// what a zerg unit is morphed from
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MorphSource {
+    /// morphed from a larva, producing `per_morph` units at once
+    Larva {
+        /// the number of units produced by a single morph
+        per_morph: u32
+    },
+    /// morphed from a drone, which is consumed
+    Drone,
+    /// produced some other way (queens, morphs from other units)
+    Other,
+}
+
+impl MorphSource {
+    /// what the given unit type is morphed from
+    pub fn of(unit_type: UnitType) -> Self {
+        match unit_type {
+            UnitType::ZergZergling => MorphSource::Larva { per_morph: 2 },
+
+            UnitType::ZergDrone
+            | UnitType::ZergOverlord
+            | UnitType::ZergRoach
+            | UnitType::ZergHydralisk
+            | UnitType::ZergMutalisk
+            | UnitType::ZergCorruptor
+            | UnitType::ZergInfestor
+            | UnitType::ZergUltralisk
+            | UnitType::ZergViper => MorphSource::Larva { per_morph: 1 },
+
+            UnitType::ZergHatchery
+            | UnitType::ZergExtractor
+            | UnitType::ZergSpawningPool
+            | UnitType::ZergEvolutionChamber
+            | UnitType::ZergRoachWarren
+            | UnitType::ZergBanelingNest
+            | UnitType::ZergHydraliskDen
+            | UnitType::ZergSpire
+            | UnitType::ZergInfestationPit
+            | UnitType::ZergUltraliskCavern
+            | UnitType::ZergSpineCrawler
+            | UnitType::ZergSporeCrawler => MorphSource::Drone,
+
+            _ => MorphSource::Other
+        }
+    }
+}
+
+/// caps every output at the cost it requested, then hands the surplus to
+/// requests that are still short (most urgent first) and finally splits
+/// whatever is left between outputs without a specific request
+fn apply_requests(
+    allotted: &mut HashMap<cortical::NodeHdl, Budget>,
+    outputs: &Vec<cortical::NodeHdl>,
+    requests: &HashMap<cortical::NodeHdl, LobeBudgetRequest>
+) {
+    let cost_of = |o: &cortical::NodeHdl| requests.get(o).and_then(
+        |r| r.cost
+    );
+
+    let mut surplus = Budget::default();
+
+    for o in outputs {
+        if let Some(cost) = cost_of(o) {
+            let budget = allotted.get_mut(o).unwrap();
+            let capped = budget.min(cost);
+
+            surplus += *budget - capped;
+            *budget = capped;
+        }
+    }
+
+    let mut short: Vec<cortical::NodeHdl> = outputs.iter()
+        .filter(|o| cost_of(o).is_some())
+        .cloned()
+        .collect()
+    ;
+    short.sort_by_key(|o| cmp::Reverse(requests[o].urgency));
+
+    for o in short {
+        let budget = allotted.get_mut(&o).unwrap();
+        let top_up = cost_of(&o).unwrap().saturating_sub(*budget).min(
+            surplus
+        );
+
+        *budget += top_up;
+        surplus -= top_up;
+    }
+
+    let open: Vec<cortical::NodeHdl> = outputs.iter()
+        .filter(|o| cost_of(o).is_none())
+        .cloned()
+        .collect()
+    ;
+
+    if !open.is_empty() {
+        let share = surplus / open.len() as u32;
+
+        for o in &open {
+            *allotted.get_mut(o).unwrap() += share;
+        }
+
+        *allotted.get_mut(&open[0]).unwrap() +=
+            surplus - share * open.len() as u32
+        ;
+    }
+}
+
 /// sets all resources as the budget
 pub struct WholeBudgetLobe {
     budget:             Budget
@@ -160,7 +680,10 @@ impl cortical::Lobe for WholeBudgetLobe {
     type FeedbackOutput = WholeBudgetFeedbackOutput;
 
     fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
-        let food = input.frame.state.food_cap - input.frame.state.food_used;
+        // supply used can exceed the cap when overlords die
+        let food = input.frame.state.food_cap.saturating_sub(
+            input.frame.state.food_used
+        );
 
         self.budget = Budget {
             minerals: input.frame.state.minerals,
@@ -189,67 +712,160 @@ impl cortical::Lobe for WholeBudgetLobe {
     }
 }
 
-/// sets all resources as the budget
-pub struct EvenSplitBudgetLobe {
-    num_outputs:            usize,
+/// game steps per second of game time on the faster game speed
+pub const STEPS_PER_SECOND: f32 = 22.4;
+
+/// measured resource income
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Income {
+    /// minerals gathered per game second
+    pub minerals:           f32,
+    /// vespene gathered per game second
+    pub vespene:            f32,
+}
+
+impl Default for Income {
+    fn default() -> Self {
+        Self { minerals: 0.0, vespene: 0.0 }
+    }
+}
+
+/// budgets resources that will be available in the near future
+///
+/// income is measured over a sliding window of steps from the increases in
+/// minerals and vespene, then projected forward to give planners a budget they
+/// will be able to afford soon.
+pub struct IncomeForecastLobe {
+    window:                 u32,
+    horizon:                f32,
+
+    samples:                VecDeque<(u32, u32, u32)>,
+
+    income:                 Income,
     budget:                 Budget,
 }
 
-impl EvenSplitBudgetLobe {
-    pub fn new() -> Self {
-        Self { num_outputs: 0, budget: Budget::default() }
+impl IncomeForecastLobe {
+    /// measure income over `window` steps and budget `horizon` game seconds
+    /// ahead
+    pub fn new(window: u32, horizon: f32) -> Self {
+        Self {
+            window: window,
+            horizon: horizon,
+
+            samples: VecDeque::new(),
+
+            income: Income::default(),
+            budget: Budget::default(),
+        }
+    }
+
+    fn measure(&mut self, step: u32, minerals: u32, vespene: u32) {
+        self.samples.push_back((step, minerals, vespene));
+
+        while let Some(&(oldest, _, _)) = self.samples.front() {
+            if oldest.saturating_add(self.window) < step {
+                self.samples.pop_front();
+            }
+            else {
+                break
+            }
+        }
+
+        let elapsed = match (self.samples.front(), self.samples.back()) {
+            (Some(&(first, _, _)), Some(&(last, _, _))) => {
+                last.checked_sub(first)
+            },
+            _ => Some(0)
+        };
+
+        let elapsed = match elapsed {
+            Some(elapsed) => elapsed,
+            None => {
+                // the step went backwards, so a new game started. measure
+                // from scratch starting at this step
+                self.samples.clear();
+                self.samples.push_back((step, minerals, vespene));
+
+                0
+            }
+        };
+
+        if elapsed == 0 {
+            self.income = Income::default();
+            return
+        }
+
+        // only count increases, spending shows up as a decrease
+        let mut gathered = (0, 0);
+
+        for (a, b) in self.samples.iter().zip(self.samples.iter().skip(1)) {
+            gathered.0 += b.1.saturating_sub(a.1);
+            gathered.1 += b.2.saturating_sub(a.2);
+        }
+
+        let seconds = elapsed as f32 / STEPS_PER_SECOND;
+
+        self.income = Income {
+            minerals: gathered.0 as f32 / seconds,
+            vespene: gathered.1 as f32 / seconds,
+        };
     }
 }
 
 create_lobe_data! {
-    module: even_split_budget,
+    module: income_forecast,
 
-    req budget: LobeBudget,
-    out split_budget: LobeBudget,
+    req frame: Rc<sc2::FrameData>,
+
+    out budget: LobeBudget,
+    out income: Income,
 }
 
-pub use self::even_split_budget::{
-    Input as EvenSplitBudgetInput,
-    Output as EvenSplitBudgetOutput,
-    FeedbackInput as EvenSplitBudgetFeedbackInput,
-    FeedbackOutput as EvenSplitBudgetFeedbackOutput,
+pub use self::income_forecast::{
+    Input as IncomeForecastInput,
+    Output as IncomeForecastOutput,
+    FeedbackInput as IncomeForecastFeedbackInput,
+    FeedbackOutput as IncomeForecastFeedbackOutput,
 };
 
 constrain_lobe! {
-    lobe: EvenSplitBudgetLobe,
+    lobe: IncomeForecastLobe,
     constraint: KeliConstraint,
     data: KeliData,
 
-    input: EvenSplitBudgetInput,
-    output: EvenSplitBudgetOutput,
-    feedback_input: EvenSplitBudgetFeedbackInput,
-    feedback_output: EvenSplitBudgetFeedbackOutput,
+    input: IncomeForecastInput,
+    output: IncomeForecastOutput,
+    feedback_input: IncomeForecastFeedbackInput,
+    feedback_output: IncomeForecastFeedbackOutput,
 
-    req budget: Budget,
-    out split_budget: Budget,
+    req frame: FrameData,
+
+    out budget: Budget,
+    out income: IncomeRate,
 }
 
-impl cortical::Lobe for EvenSplitBudgetLobe {
-    type Input = EvenSplitBudgetInput;
-    type Output = EvenSplitBudgetOutput;
-    type FeedbackInput = EvenSplitBudgetFeedbackInput;
-    type FeedbackOutput = EvenSplitBudgetFeedbackOutput;
+impl cortical::Lobe for IncomeForecastLobe {
+    type Input = IncomeForecastInput;
+    type Output = IncomeForecastOutput;
+    type FeedbackInput = IncomeForecastFeedbackInput;
+    type FeedbackOutput = IncomeForecastFeedbackOutput;
 
-    fn start(
-        &mut self,
-        _: cortical::NodeHdl,
-        _: Vec<cortical::NodeHdl>,
-        outputs: Vec<cortical::NodeHdl>
-    )
-        -> cortical::Result<()>
-    {
-        self.num_outputs = outputs.len();
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        let state = &input.frame.state;
 
-        Ok(())
-    }
+        self.measure(state.current_step, state.minerals, state.vespene);
 
-    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
-        self.budget = input.budget.budget;
+        self.budget = Budget {
+            minerals: state.minerals.saturating_add(
+                (self.income.minerals * self.horizon) as u32
+            ),
+            vespene: state.vespene.saturating_add(
+                (self.income.vespene * self.horizon) as u32
+            ),
+            food: state.food_cap.saturating_sub(state.food_used),
+            larva: state.larva_count
+        };
 
         Ok(())
     }
@@ -257,19 +873,10 @@ impl cortical::Lobe for EvenSplitBudgetLobe {
     fn tailor_output(&mut self, output: cortical::NodeHdl)
         -> cortical::Result<Self::Output>
     {
-        let factor = self.num_outputs as u32;
-
         Ok(
-            EvenSplitBudgetOutput {
-                split_budget: LobeBudget {
-                    lobe: output,
-                    budget: Budget {
-                        minerals: self.budget.minerals / factor,
-                        vespene: self.budget.vespene / factor,
-                        food: self.budget.food / factor,
-                        larva: self.budget.larva / factor,
-                    }
-                }
+            IncomeForecastOutput {
+                budget: LobeBudget { lobe: output, budget: self.budget },
+                income: self.income,
             }
         )
     }
@@ -277,73 +884,1240 @@ impl cortical::Lobe for EvenSplitBudgetLobe {
     fn tailor_feedback(&mut self, _: cortical::NodeHdl)
         -> cortical::Result<Self::FeedbackOutput>
     {
-        Ok(EvenSplitBudgetFeedbackOutput { })
+        Ok(IncomeForecastFeedbackOutput { })
     }
 }
 
+/// resources committed to an order the game has not paid for yet
+///
+/// the game only deducts the cost of a structure once the drone reaches the
+/// build site, so spenders report their orders as reservations until then.
+#[derive(Debug, Copy, Clone)]
+pub struct Reservation {
+    /// the spender that gave the order
+    pub lobe:               cortical::NodeHdl,
+    /// the unit carrying out the order
+    pub tag:                Tag,
+    /// the ability that was ordered
+    pub ability:            Ability,
+    /// the cost of the order
+    pub cost:               Budget,
+}
+
+struct OutstandingReservation {
+    reservation:            Reservation,
+    issued:                 u32,
+    seen_order:             bool,
+}
+
+/// subtracts reserved resources from the budget until the game confirms them
+///
+/// a reservation is released once its unit is gone (a drone morphing into a
+/// structure), once the unit drops the order after having been seen with it,
+/// or once it times out. the game never charged for dropped and timed out
+/// orders, so they are sent on to the ledgers below to refund their spenders.
+pub struct ReservationLedgerLobe {
+    hdl:                    Option<cortical::NodeHdl>,
+
+    timeout:                u32,
+    outstanding:            Vec<OutstandingReservation>,
+    refunded:               Vec<Reservation>,
+
+    step:                   u32,
+    budget:                 Budget,
+}
+
+impl ReservationLedgerLobe {
+    /// release reservations that have not been confirmed after `timeout`
+    /// steps
+    pub fn new(timeout: u32) -> Self {
+        Self {
+            hdl: None,
+
+            timeout: timeout,
+            outstanding: vec![ ],
+            refunded: vec![ ],
+
+            step: 0,
+            budget: Budget::default(),
+        }
+    }
+
+    /// the total cost of all orders the game has not paid for yet
+    pub fn reserved(&self) -> Budget {
+        self.outstanding.iter().fold(
+            Budget::default(), |acc, r| acc + r.reservation.cost
+        )
+    }
+
+    fn confirm(&mut self, frame: &sc2::FrameData) {
+        let step = frame.state.current_step;
+        let timeout = self.timeout;
+
+        let units: HashMap<Tag, &Unit> = frame.state.units.iter().map(
+            |u| (u.tag, &**u)
+        ).collect();
+
+        let mut refunded = vec![ ];
+
+        self.outstanding.retain(
+            |r| {
+                let keep = match units.get(&r.reservation.tag) {
+                    Some(unit) => {
+                        let has_order = unit.orders.iter().any(
+                            |o| o.ability == r.reservation.ability
+                        );
+
+                        // keep it while the order is pending or in progress
+                        has_order || !r.seen_order
+                    },
+                    // the drone turned into the structure
+                    None => return false
+                };
+
+                if !keep || r.issued.saturating_add(timeout) < step {
+                    refunded.push(r.reservation);
+
+                    return false
+                }
+
+                true
+            }
+        );
+
+        self.refunded = refunded;
+
+        for r in &mut self.outstanding {
+            if let Some(unit) = units.get(&r.reservation.tag) {
+                if unit.orders.iter().any(
+                    |o| o.ability == r.reservation.ability
+                ) {
+                    r.seen_order = true;
+                }
+            }
+        }
+    }
+}
+
+create_lobe_data! {
+    module: reservation_ledger,
+
+    req frame: Rc<sc2::FrameData>,
+    req budget: LobeBudget,
+
+    out budget: LobeBudget,
+    out refunded: Vec<Reservation>,
+
+    fbk var each_reservations: Vec<Reservation>,
+}
+
+pub use self::reservation_ledger::{
+    Input as ReservationLedgerInput,
+    Output as ReservationLedgerOutput,
+    FeedbackInput as ReservationLedgerFeedbackInput,
+    FeedbackOutput as ReservationLedgerFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: ReservationLedgerLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: ReservationLedgerInput,
+    output: ReservationLedgerOutput,
+    feedback_input: ReservationLedgerFeedbackInput,
+    feedback_output: ReservationLedgerFeedbackOutput,
+
+    req frame: FrameData,
+    req budget: Budget,
+
+    out budget: Budget,
+    out refunded: Reservations,
+
+    fbk var each_reservations: Reservations,
+}
+
+impl cortical::Lobe for ReservationLedgerLobe {
+    type Input = ReservationLedgerInput;
+    type Output = ReservationLedgerOutput;
+    type FeedbackInput = ReservationLedgerFeedbackInput;
+    type FeedbackOutput = ReservationLedgerFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.hdl = Some(hdl);
+        self.outstanding.clear();
+        self.refunded.clear();
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.step = input.frame.state.current_step;
+
+        self.confirm(&input.frame);
+
+        self.budget = input.budget.budget.saturating_sub(self.reserved());
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            ReservationLedgerOutput {
+                budget: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.budget,
+                },
+                refunded: self.refunded.clone(),
+            }
+        )
+    }
+
+    fn feedback(&mut self, input: Self::FeedbackInput) -> cortical::Result<()>
+    {
+        for reservations in input.each_reservations {
+            for reservation in reservations {
+                self.outstanding.push(
+                    OutstandingReservation {
+                        reservation: reservation,
+                        issued: self.step,
+                        seen_order: false,
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(ReservationLedgerFeedbackOutput { })
+    }
+}
+
+/// sets all resources as the budget
+pub struct EvenSplitBudgetLobe {
+    num_outputs:            usize,
+    budget:                 Budget,
+}
+
+impl EvenSplitBudgetLobe {
+    pub fn new() -> Self {
+        Self { num_outputs: 0, budget: Budget::default() }
+    }
+}
+
+create_lobe_data! {
+    module: even_split_budget,
+
+    req budget: LobeBudget,
+    out split_budget: LobeBudget,
+}
+
+pub use self::even_split_budget::{
+    Input as EvenSplitBudgetInput,
+    Output as EvenSplitBudgetOutput,
+    FeedbackInput as EvenSplitBudgetFeedbackInput,
+    FeedbackOutput as EvenSplitBudgetFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: EvenSplitBudgetLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: EvenSplitBudgetInput,
+    output: EvenSplitBudgetOutput,
+    feedback_input: EvenSplitBudgetFeedbackInput,
+    feedback_output: EvenSplitBudgetFeedbackOutput,
+
+    req budget: Budget,
+    out split_budget: Budget,
+}
+
+impl cortical::Lobe for EvenSplitBudgetLobe {
+    type Input = EvenSplitBudgetInput;
+    type Output = EvenSplitBudgetOutput;
+    type FeedbackInput = EvenSplitBudgetFeedbackInput;
+    type FeedbackOutput = EvenSplitBudgetFeedbackOutput;
+
+    fn start(
+        &mut self,
+        _: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        outputs: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.num_outputs = outputs.len();
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.budget = input.budget.budget;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, output: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        let factor = self.num_outputs as u32;
+
+        Ok(
+            EvenSplitBudgetOutput {
+                split_budget: LobeBudget {
+                    lobe: output,
+                    budget: Budget {
+                        minerals: self.budget.minerals / factor,
+                        vespene: self.budget.vespene / factor,
+                        food: self.budget.food / factor,
+                        larva: self.budget.larva / factor,
+                    }
+                }
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(EvenSplitBudgetFeedbackOutput { })
+    }
+}
+
+/// the spending history of a single output of a ledger
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LedgerEntry {
+    /// the output lobe
+    pub lobe:               cortical::NodeHdl,
+    /// the budget allotted during the last step
+    pub allotted:           Budget,
+    /// the budget spent during the last step
+    pub spent:              Budget,
+    /// the sum of the budgets allotted every step
+    pub total_allotted:     Budget,
+    /// everything the output has spent
+    pub total_spent:        Budget,
+    /// the sum of the budgets left unspent every step
+    pub idle:               Budget,
+    /// the number of steps the output could not afford its request
+    pub starved_steps:      u32,
+    /// what the output overspent and has not paid back yet
+    pub debt:               Budget,
+}
+
+impl LedgerEntry {
+    fn new(lobe: cortical::NodeHdl) -> Self {
+        Self {
+            lobe: lobe,
+            allotted: Budget::default(),
+            spent: Budget::default(),
+            total_allotted: Budget::default(),
+            total_spent: Budget::default(),
+            idle: Budget::default(),
+            starved_steps: 0,
+            debt: Budget::default(),
+        }
+    }
+}
+
+/// what a ledger has handed out and what its outputs did with it
+#[derive(Debug, Clone)]
+pub struct LedgerReport {
+    /// the ledger making the report
+    pub ledger:             cortical::NodeHdl,
+    /// the number of steps recorded
+    pub steps:              u32,
+    /// one entry for every output that spends budget
+    pub entries:            Vec<LedgerEntry>,
+}
+
+impl LedgerReport {
+    /// an empty report for the given ledger
+    pub fn new(ledger: cortical::NodeHdl) -> Self {
+        Self { ledger: ledger, steps: 0, entries: vec![ ] }
+    }
+
+    /// the entry for the given output, if it has been recorded
+    pub fn entry(&self, lobe: cortical::NodeHdl) -> Option<&LedgerEntry> {
+        self.entries.iter().find(|e| e.lobe == lobe)
+    }
+
+    /// record what an output was allotted and spent during a step, and what
+    /// it still owes
+    ///
+    /// the output is counted as starved if it had a request that the budget
+    /// could not cover.
+    pub fn record(
+        &mut self,
+        lobe: cortical::NodeHdl,
+        allotted: Budget,
+        spent: Budget,
+        debt: Budget,
+        request: Option<&LobeBudgetRequest>
+    ) {
+        let index = match self.entries.iter().position(|e| e.lobe == lobe) {
+            Some(index) => index,
+            None => {
+                self.entries.push(LedgerEntry::new(lobe));
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+
+        entry.allotted = allotted;
+        entry.spent = spent;
+        entry.total_allotted = entry.total_allotted.saturating_add(allotted);
+        entry.total_spent = entry.total_spent.saturating_add(spent);
+        entry.idle = entry.idle.saturating_add(allotted.saturating_sub(spent));
+        entry.debt = debt;
+
+        if let Some(cost) = request.and_then(|r| r.cost) {
+            if !cost.fits_within(&allotted) {
+                entry.starved_steps += 1;
+            }
+        }
+    }
+}
+
+/// the books every ledger keeps on its outputs
+///
+/// tracks what each output was allotted and spent during the last step and
+/// what it asks for next, and keeps a report of every step. ledgers that
+/// already charge outputs for everything they ever spent would count an
+/// overspend twice, so only ledgers that split each step's budget on its own
+/// track what outputs owe.
+///
+/// observers are outputs that only watch the ledger (like a ledger report),
+/// so they are left out of the books and never given any budget.
+struct LedgerBook {
+    hdl:                    Option<cortical::NodeHdl>,
+    tracks_debts:           bool,
+    observers:              Vec<String>,
+
+    allotted:               HashMap<cortical::NodeHdl, Budget>,
+    spent:                  HashMap<cortical::NodeHdl, Budget>,
+    debts:                  HashMap<cortical::NodeHdl, Budget>,
+    requests:               HashMap<cortical::NodeHdl, LobeBudgetRequest>,
+
+    report:                 Option<LedgerReport>,
+}
+
+impl LedgerBook {
+    fn new() -> Self {
+        Self {
+            hdl: None,
+            tracks_debts: false,
+            observers: vec![ ],
+
+            allotted: HashMap::new(),
+            spent: HashMap::new(),
+            debts: HashMap::new(),
+            requests: HashMap::new(),
+
+            report: None,
+        }
+    }
+
+    /// keep track of overspending and withhold it from later shares
+    fn with_debts(mut self) -> Self {
+        self.tracks_debts = true;
+        self
+    }
+
+    /// open a fresh book for the given outputs and return the ones that
+    /// spend budget
+    ///
+    /// fails if an observer is not connected to the ledger.
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        outputs: &[cortical::NodeHdl],
+        names: &LobeNames
+    )
+        -> cortical::Result<Vec<cortical::NodeHdl>>
+    {
+        let names = names.borrow();
+
+        for observer in &self.observers {
+            if !outputs.iter().any(|o| names.get(o) == Some(observer)) {
+                bail!("observer {} is not connected to the ledger", observer)
+            }
+        }
+
+        let spenders: Vec<cortical::NodeHdl> = outputs.iter()
+            .filter(
+                |o| names.get(o).map_or(true, |n| !self.observers.contains(n))
+            )
+            .cloned()
+            .collect()
+        ;
+
+        self.hdl = Some(hdl);
+
+        self.allotted.clear();
+        self.spent.clear();
+        self.debts.clear();
+        self.requests.clear();
+
+        self.report = Some(LedgerReport::new(hdl));
+
+        for &o in &spenders {
+            self.allotted.insert(o, Budget::default());
+            self.spent.insert(o, Budget::default());
+        }
+
+        Ok(spenders)
+    }
+
+    /// take back everything allotted during the last step
+    fn clear_allotted(&mut self) {
+        for budget in self.allotted.values_mut() {
+            *budget = Budget::default();
+        }
+    }
+
+    /// the budget sent to the given output
+    fn split_budget(&self, output: cortical::NodeHdl) -> LobeBudget {
+        LobeBudget {
+            lobe: self.hdl.unwrap(),
+            budget: self.allotted.get(&output).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// record what every output spent and what it wants next in the report
+    ///
+    /// the game has already taken the resources, so overspending is not an
+    /// error. if debts are tracked, the debt is paid back out of the output's
+    /// next shares instead.
+    fn record(
+        &mut self,
+        each_spent: &[LobeBudget],
+        each_request: Vec<LobeBudgetRequest>
+    )
+        -> cortical::Result<()>
+    {
+        if each_spent.len() != self.allotted.len() {
+            bail!("did not receive feedback from all outputs")
+        }
+
+        for spent in each_spent {
+            let allotted = match self.allotted.get(&spent.lobe) {
+                Some(&budget) => budget,
+                None => bail!(
+                    "received spending from unknown lobe {:?}", spent.lobe
+                )
+            };
+
+            let delta = allotted.delta(spent.budget);
+
+            if self.tracks_debts && delta.is_debt() {
+                let debt = self.debts.entry(spent.lobe).or_insert_with(
+                    Budget::default
+                );
+
+                *debt = debt.saturating_add(delta.debt());
+            }
+
+            self.spent.insert(spent.lobe, spent.budget);
+
+            self.report.as_mut().unwrap().record(
+                spent.lobe,
+                allotted,
+                spent.budget,
+                self.debts.get(&spent.lobe).cloned().unwrap_or_default(),
+                self.requests.get(&spent.lobe)
+            );
+        }
+
+        self.report.as_mut().unwrap().steps += 1;
+
+        for request in each_request {
+            self.requests.insert(request.lobe, request);
+        }
+
+        Ok(())
+    }
+
+    /// the report of every step recorded so far
+    fn report(&self) -> LedgerReport {
+        self.report.clone().unwrap()
+    }
+
+    /// withhold what outputs owe from their shares until the debt is paid
+    fn repay_debts(&mut self) {
+        for (o, debt) in self.debts.iter_mut() {
+            if let Some(budget) = self.allotted.get_mut(o) {
+                let repaid = budget.min(*debt);
+
+                *budget -= repaid;
+                *debt -= repaid;
+            }
+        }
+    }
+
+    /// everything the outputs spent during the last step
+    fn total_spent(&self) -> LobeBudget {
+        LobeBudget {
+            lobe: self.hdl.unwrap(),
+            budget: self.spent.values().fold(
+                Budget::default(), |acc, spent| acc + *spent
+            ),
+        }
+    }
+
+    /// the combined request of every output
+    fn combined_request(&self) -> LobeBudgetRequest {
+        LobeBudgetRequest::combine(self.hdl.unwrap(), self.requests.values())
+    }
+}
+
+/// splits the budget evenly between its outputs
+///
+/// the split is made over everything the outputs have ever spent plus the
+/// budget on hand, so an output that saves up keeps its share until it can
+/// afford something expensive. outputs can be other ledgers: a nested ledger
+/// reports the spending of everything below it and never passes on more
+/// than it was given. reservations refunded by a reservation ledger above are
+/// taken back out of what their spender has spent.
 pub struct EvenSplitLedgerLobe {
-    hdl: Option<cortical::NodeHdl>,
+    outputs: Vec<cortical::NodeHdl>,
+    spenders: HashMap<cortical::NodeHdl, Budget>,
+
+    names: LobeNames,
+
+    book: LedgerBook,
+
+    rng: LobeRng,
+}
+
+create_lobe_data! {
+    module: even_split_ledger,
+
+    req allotted: LobeBudget,
+    opt refunded: Vec<Reservation>,
+    out split_budget: LobeBudget,
+    out report: LedgerReport,
+
+    fbk var each_spent: LobeBudget,
+    fbk out spent: LobeBudget,
+
+    fbk var each_request: LobeBudgetRequest,
+    fbk out request: LobeBudgetRequest,
+}
+
+pub use self::even_split_ledger::{
+    Input as EvenSplitLedgerInput,
+    Output as EvenSplitLedgerOutput,
+    FeedbackInput as EvenSplitLedgerFeedbackInput,
+    FeedbackOutput as EvenSplitLedgerFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: EvenSplitLedgerLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: EvenSplitLedgerInput,
+    output: EvenSplitLedgerOutput,
+    feedback_input: EvenSplitLedgerFeedbackInput,
+    feedback_output: EvenSplitLedgerFeedbackOutput,
+
+    req allotted: Budget,
+    opt refunded: Reservations,
+    out split_budget: Budget,
+    out report: LedgerReport,
+
+    fbk var each_spent: Budget,
+    fbk out spent: Budget,
+
+    fbk var each_request: BudgetRequest,
+    fbk out request: BudgetRequest,
+}
+
+impl EvenSplitLedgerLobe {
+    pub fn new() -> Self {
+        Self {
+            outputs: vec![ ],
+            spenders: HashMap::new(),
+
+            names: LobeNames::default(),
+
+            book: LedgerBook::new(),
+
+            rng: unseeded_rng(),
+        }
+    }
+
+    /// share the names of the lobes in the cortex
+    pub fn with_names(mut self, names: LobeNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// use a seeded generator so that the same budgets are split the same way
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seeded_rng(seed);
+        self
+    }
+
+    /// send the report to the output with the given name instead of a share
+    /// of the budget
+    ///
+    /// names are resolved when the cortex starts, so the lobe names must be
+    /// shared with `with_names`. starting fails if no output has the name.
+    pub fn with_observer(mut self, output: &str) -> Self {
+        self.book.observers.push(output.to_string());
+        self
+    }
+}
+
+/// split the budget on hand so that every output ends up with an even share
+/// of everything spent so far
+///
+/// outputs that spent more than their share get that much less, down to
+/// nothing. returns each output's share and whatever is left over from
+/// rounding.
+fn even_shares(available: Budget, spent: &[Budget]) -> (Vec<Budget>, Budget) {
+    let total = spent.iter().fold(available, |acc, &s| acc + s);
+    let split = total / spent.len() as u32;
+
+    let shares = spent.iter().map(|&s| split - s).collect();
+
+    (shares, total - split * spent.len() as u32)
+}
+
+impl cortical::Lobe for EvenSplitLedgerLobe {
+    type Input = EvenSplitLedgerInput;
+    type Output = EvenSplitLedgerOutput;
+    type FeedbackInput = EvenSplitLedgerFeedbackInput;
+    type FeedbackOutput = EvenSplitLedgerFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        outputs: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        let outputs = self.book.start(hdl, &outputs, &self.names)?;
+
+        self.spenders.clear();
+
+        for &o in &outputs {
+            self.spenders.insert(o, Budget::default());
+        }
+
+        self.outputs = outputs;
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        // zero all allotted budgets
+        self.book.clear_allotted();
+
+        refund_released(&mut self.spenders, &input.refunded);
+
+        if input.allotted.is_zero() {
+            return Ok(())
+        }
+
+        // idle and finished outputs give up their share right away
+        let active = active_outputs(&self.outputs, &self.book.requests);
+
+        if active.is_empty() {
+            return Ok(())
+        }
+
+        let spent: Vec<Budget> = active.iter().map(
+            |o| self.spenders[o]
+        ).collect();
+        let (shares, remaining) = even_shares(input.allotted.budget, &spent);
+
+        for (o, share) in active.iter().zip(shares) {
+            *self.book.allotted.get_mut(o).unwrap() = share;
+        }
+
+        // just distribute the remaining budget to a random output. pick it
+        // from the ordered outputs since map iteration order is not stable
+        let n = self.rng.gen_range(0, active.len());
+        *self.book.allotted.get_mut(&active[n]).unwrap() += remaining;
+
+        limit_allotted(&mut self.book.allotted, &active, input.allotted.budget);
+        apply_requests(&mut self.book.allotted, &active, &self.book.requests);
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, output: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            EvenSplitLedgerOutput {
+                split_budget: self.book.split_budget(output),
+                report: self.book.report(),
+            }
+        )
+    }
+
+    fn feedback(&mut self, input: Self::FeedbackInput) -> cortical::Result<()>
+    {
+        self.book.record(&input.each_spent, input.each_request)?;
+
+        for spent in &input.each_spent {
+            *self.spenders.get_mut(&spent.lobe).unwrap() += spent.budget;
+        }
+
+        Ok(())
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            EvenSplitLedgerFeedbackOutput {
+                spent: self.book.total_spent(),
+                request: self.book.combined_request(),
+            }
+        )
+    }
+}
+
+/// splits the budget between outputs in proportion to their weights
+///
+/// weights can be given per node handle or per lobe name. outputs without a
+/// weight get a weight of 1. spending is tracked the same way as the even
+/// split ledger.
+pub struct WeightedLedgerLobe {
+    outputs: Vec<cortical::NodeHdl>,
+
+    names: LobeNames,
+    weights_by_hdl: HashMap<cortical::NodeHdl, u32>,
+    weights_by_name: HashMap<String, u32>,
+    weights: HashMap<cortical::NodeHdl, u32>,
+
+    spenders: HashMap<cortical::NodeHdl, Budget>,
+
+    book: LedgerBook,
+}
+
+create_lobe_data! {
+    module: weighted_ledger,
+
+    req allotted: LobeBudget,
+    opt refunded: Vec<Reservation>,
+    out split_budget: LobeBudget,
+    out report: LedgerReport,
+
+    fbk var each_spent: LobeBudget,
+    fbk out spent: LobeBudget,
+
+    fbk var each_request: LobeBudgetRequest,
+    fbk out request: LobeBudgetRequest,
+}
+
+pub use self::weighted_ledger::{
+    Input as WeightedLedgerInput,
+    Output as WeightedLedgerOutput,
+    FeedbackInput as WeightedLedgerFeedbackInput,
+    FeedbackOutput as WeightedLedgerFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: WeightedLedgerLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: WeightedLedgerInput,
+    output: WeightedLedgerOutput,
+    feedback_input: WeightedLedgerFeedbackInput,
+    feedback_output: WeightedLedgerFeedbackOutput,
+
+    req allotted: Budget,
+    opt refunded: Reservations,
+    out split_budget: Budget,
+    out report: LedgerReport,
+
+    fbk var each_spent: Budget,
+    fbk out spent: Budget,
+
+    fbk var each_request: BudgetRequest,
+    fbk out request: BudgetRequest,
+}
+
+impl WeightedLedgerLobe {
+    pub fn new() -> Self {
+        Self {
+            outputs: vec![ ],
+
+            names: LobeNames::default(),
+            weights_by_hdl: HashMap::new(),
+            weights_by_name: HashMap::new(),
+            weights: HashMap::new(),
+
+            spenders: HashMap::new(),
+
+            book: LedgerBook::new(),
+        }
+    }
+
+    /// set the weight of the output with the given handle
+    pub fn with_weight(mut self, output: cortical::NodeHdl, weight: u32)
+        -> Self
+    {
+        self.weights_by_hdl.insert(output, weight);
+        self
+    }
+
+    /// set the weight of the output with the given name
+    ///
+    /// names are resolved when the cortex starts, so the lobe names must be
+    /// shared with `with_names`. starting fails if no output has the name.
+    pub fn with_named_weight(mut self, output: &str, weight: u32) -> Self {
+        self.weights_by_name.insert(output.to_string(), weight);
+        self
+    }
+
+    /// share the names of the lobes in the cortex
+    pub fn with_names(mut self, names: LobeNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// send the report to the output with the given name instead of a share
+    /// of the budget
+    ///
+    /// names are resolved when the cortex starts, so the lobe names must be
+    /// shared with `with_names`. starting fails if no output has the name.
+    pub fn with_observer(mut self, output: &str) -> Self {
+        self.book.observers.push(output.to_string());
+        self
+    }
 
-    total_spent: Budget,
+    fn resolve_weight(&self, output: cortical::NodeHdl) -> u32 {
+        if let Some(&weight) = self.weights_by_hdl.get(&output) {
+            return weight
+        }
 
-    allotted: HashMap<cortical::NodeHdl, Budget>,
-    spenders: HashMap<cortical::NodeHdl, Budget>,
-    spent: HashMap<cortical::NodeHdl, Budget>,
+        if let Some(name) = self.names.borrow().get(&output) {
+            if let Some(&weight) = self.weights_by_name.get(name) {
+                return weight
+            }
+        }
+
+        1
+    }
+}
+
+fn weighted_share(total: u32, weight: u32, total_weight: u32) -> u32 {
+    (total as u64 * weight as u64 / total_weight as u64) as u32
+}
+
+impl cortical::Lobe for WeightedLedgerLobe {
+    type Input = WeightedLedgerInput;
+    type Output = WeightedLedgerOutput;
+    type FeedbackInput = WeightedLedgerFeedbackInput;
+    type FeedbackOutput = WeightedLedgerFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        outputs: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        let outputs = self.book.start(hdl, &outputs, &self.names)?;
+
+        self.weights.clear();
+        self.spenders.clear();
+
+        for &o in &outputs {
+            let weight = self.resolve_weight(o);
+
+            self.weights.insert(o, weight);
+            self.spenders.insert(o, Budget::default());
+        }
+
+        for output in self.weights_by_hdl.keys() {
+            if !outputs.contains(output) {
+                bail!("weighted output is not connected to the ledger")
+            }
+        }
+
+        for name in self.weights_by_name.keys() {
+            let connected = outputs.iter().any(
+                |o| self.names.borrow().get(o) == Some(name)
+            );
+
+            if !connected {
+                bail!("weighted output {} is not connected to the ledger", name)
+            }
+        }
+
+        if self.weights.values().all(|&w| w == 0) {
+            bail!("weighted ledger needs at least one output with a weight")
+        }
+
+        self.outputs = outputs;
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        // zero all allotted budgets
+        self.book.clear_allotted();
+
+        refund_released(&mut self.spenders, &input.refunded);
+
+        if input.allotted.is_zero() {
+            return Ok(())
+        }
+
+        // idle and finished outputs give up their share right away
+        let active = active_outputs(&self.outputs, &self.book.requests);
+
+        let total = active.iter().fold(
+            input.allotted.budget, |acc, o| acc + self.spenders[o]
+        );
+        let total_weight = active.iter().map(|o| self.weights[o]).sum::<u32>();
+
+        if total_weight == 0 {
+            return Ok(())
+        }
+
+        let mut distributed = Budget::default();
+
+        for hdl in &active {
+            let weight = self.weights[hdl];
+            let total_spent = self.spenders[hdl];
+
+            let share = Budget {
+                minerals: weighted_share(total.minerals, weight, total_weight),
+                vespene: weighted_share(total.vespene, weight, total_weight),
+                food: weighted_share(total.food, weight, total_weight),
+                larva: weighted_share(total.larva, weight, total_weight),
+            };
+
+            distributed += share;
+
+            let allotted = share.saturating_sub(total_spent);
+
+            *self.book.allotted.get_mut(hdl).unwrap() = allotted;
+        }
+
+        // rounding leftovers go to the heaviest output
+        let heaviest = *active.iter().max_by_key(|o| self.weights[o]).unwrap();
+
+        *self.book.allotted.get_mut(&heaviest).unwrap() +=
+            total.saturating_sub(distributed)
+        ;
+
+        limit_allotted(&mut self.book.allotted, &active, input.allotted.budget);
+        apply_requests(&mut self.book.allotted, &active, &self.book.requests);
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, output: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            WeightedLedgerOutput {
+                split_budget: self.book.split_budget(output),
+                report: self.book.report(),
+            }
+        )
+    }
+
+    fn feedback(&mut self, input: Self::FeedbackInput) -> cortical::Result<()>
+    {
+        self.book.record(&input.each_spent, input.each_request)?;
+
+        for spent in &input.each_spent {
+            *self.spenders.get_mut(&spent.lobe).unwrap() += spent.budget;
+        }
+
+        Ok(())
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            WeightedLedgerFeedbackOutput {
+                spent: self.book.total_spent(),
+                request: self.book.combined_request(),
+            }
+        )
+    }
+}
+
+enum RankedOutput {
+    Hdl(cortical::NodeHdl),
+    Name(String),
+}
+
+/// funds outputs in strict priority order, saving up for expensive items
+///
+/// each output has a requested cost for its next item. the highest priority
+/// output must be able to afford its request before anything is given to
+/// outputs below it. an output without a request gets everything that is left.
+/// outputs that were not ranked come last in the order they were connected.
+pub struct PriorityLedgerLobe {
+    names: LobeNames,
+    ranking: Vec<(RankedOutput, Option<Budget>)>,
+
+    order: Vec<cortical::NodeHdl>,
+    configured: HashMap<cortical::NodeHdl, Budget>,
+
+    book: LedgerBook,
 }
 
 create_lobe_data! {
-    module: even_split_ledger,
+    module: priority_ledger,
 
     req allotted: LobeBudget,
     out split_budget: LobeBudget,
+    out report: LedgerReport,
 
     fbk var each_spent: LobeBudget,
     fbk out spent: LobeBudget,
+
+    fbk var each_request: LobeBudgetRequest,
+    fbk out request: LobeBudgetRequest,
 }
 
-pub use self::even_split_ledger::{
-    Input as EvenSplitLedgerInput,
-    Output as EvenSplitLedgerOutput,
-    FeedbackInput as EvenSplitLedgerFeedbackInput,
-    FeedbackOutput as EvenSplitLedgerFeedbackOutput,
+pub use self::priority_ledger::{
+    Input as PriorityLedgerInput,
+    Output as PriorityLedgerOutput,
+    FeedbackInput as PriorityLedgerFeedbackInput,
+    FeedbackOutput as PriorityLedgerFeedbackOutput,
 };
 
 constrain_lobe! {
-    lobe: EvenSplitLedgerLobe,
+    lobe: PriorityLedgerLobe,
     constraint: KeliConstraint,
     data: KeliData,
 
-    input: EvenSplitLedgerInput,
-    output: EvenSplitLedgerOutput,
-    feedback_input: EvenSplitLedgerFeedbackInput,
-    feedback_output: EvenSplitLedgerFeedbackOutput,
+    input: PriorityLedgerInput,
+    output: PriorityLedgerOutput,
+    feedback_input: PriorityLedgerFeedbackInput,
+    feedback_output: PriorityLedgerFeedbackOutput,
 
     req allotted: Budget,
     out split_budget: Budget,
+    out report: LedgerReport,
 
     fbk var each_spent: Budget,
     fbk out spent: Budget,
+
+    fbk var each_request: BudgetRequest,
+    fbk out request: BudgetRequest,
 }
 
-impl EvenSplitLedgerLobe {
+impl PriorityLedgerLobe {
     pub fn new() -> Self {
         Self {
-            hdl: None,
+            names: LobeNames::default(),
+            ranking: vec![ ],
 
-            total_spent: Budget::default(),
+            order: vec![ ],
+            configured: HashMap::new(),
 
-            allotted: HashMap::new(),
-            spenders: HashMap::new(),
-            spent: HashMap::new(),
+            book: LedgerBook::new().with_debts(),
+        }
+    }
+
+    /// rank the output with the given handle below all previously ranked
+    /// outputs
+    pub fn then_output(
+        mut self, output: cortical::NodeHdl, request: Option<Budget>
+    )
+        -> Self
+    {
+        self.ranking.push((RankedOutput::Hdl(output), request));
+        self
+    }
+
+    /// rank the output with the given name below all previously ranked
+    /// outputs
+    ///
+    /// names are resolved when the cortex starts, so the lobe names must be
+    /// shared with `with_names`.
+    pub fn then_named(mut self, output: &str, request: Option<Budget>)
+        -> Self
+    {
+        self.ranking.push((RankedOutput::Name(output.to_string()), request));
+        self
+    }
+
+    /// share the names of the lobes in the cortex
+    pub fn with_names(mut self, names: LobeNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// send the report to the output with the given name instead of a share
+    /// of the budget
+    ///
+    /// names are resolved when the cortex starts, so the lobe names must be
+    /// shared with `with_names`. starting fails if no output has the name.
+    pub fn with_observer(mut self, output: &str) -> Self {
+        self.book.observers.push(output.to_string());
+        self
+    }
+
+    /// the outputs in priority order
+    pub fn order(&self) -> &Vec<cortical::NodeHdl> {
+        &self.order
+    }
+
+    /// the cost the output needs before it is considered fully funded
+    ///
+    /// the cost in a request sent back by the output takes precedence over
+    /// the cost it was ranked with. a request without a cost leaves the
+    /// ranked cost in place.
+    pub fn requested(&self, output: cortical::NodeHdl) -> Option<Budget> {
+        match self.book.requests.get(&output).and_then(|r| r.cost) {
+            Some(cost) => Some(cost),
+            None => self.configured.get(&output).cloned()
+        }
+    }
+
+    fn resolve(&self, ranked: &RankedOutput) -> Option<cortical::NodeHdl> {
+        match *ranked {
+            RankedOutput::Hdl(hdl) => Some(hdl),
+            RankedOutput::Name(ref name) => self.names.borrow().iter().find(
+                |&(_, n)| n == name
+            ).map(
+                |(&hdl, _)| hdl
+            )
         }
     }
 }
 
-impl cortical::Lobe for EvenSplitLedgerLobe {
-    type Input = EvenSplitLedgerInput;
-    type Output = EvenSplitLedgerOutput;
-    type FeedbackInput = EvenSplitLedgerFeedbackInput;
-    type FeedbackOutput = EvenSplitLedgerFeedbackOutput;
+impl cortical::Lobe for PriorityLedgerLobe {
+    type Input = PriorityLedgerInput;
+    type Output = PriorityLedgerOutput;
+    type FeedbackInput = PriorityLedgerFeedbackInput;
+    type FeedbackOutput = PriorityLedgerFeedbackOutput;
 
     fn start(
         &mut self,
@@ -353,73 +2127,70 @@ impl cortical::Lobe for EvenSplitLedgerLobe {
     )
         -> cortical::Result<()>
     {
-        self.hdl = Some(hdl);
+        let outputs = self.book.start(hdl, &outputs, &self.names)?;
 
-        self.spenders.clear();
-        self.allotted.clear();
-        self.spent.clear();
+        let mut order = vec![ ];
+        let mut configured = HashMap::new();
 
-        for o in outputs {
-            self.spenders.insert(o, Budget::default());
-            self.allotted.insert(o, Budget::default());
-            self.spent.insert(o, Budget::default());
-        }
+        for &(ref ranked, request) in &self.ranking {
+            let output = match self.resolve(ranked) {
+                Some(output) => output,
+                None => bail!("ranked output is not connected to the ledger")
+            };
 
-        Ok(())
-    }
+            if !outputs.contains(&output) {
+                bail!("ranked output is not connected to the ledger")
+            }
 
-    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
-        // zero all allotted budgets
-        for (_, budget) in &mut self.allotted {
-            *budget = Budget::default();
+            if order.contains(&output) {
+                bail!("output ranked more than once")
+            }
+
+            order.push(output);
+
+            if let Some(request) = request {
+                configured.insert(output, request);
+            }
         }
 
-        if input.allotted.is_zero() {
-            return Ok(())
+        for &o in &outputs {
+            if !order.contains(&o) {
+                order.push(o);
+            }
         }
 
-        let total = self.total_spent + input.allotted.budget;
-        let split = total / self.allotted.len() as u32;
+        self.order = order;
+        self.configured = configured;
 
-        for (hdl, total_spent) in &mut self.spenders {
-            let mut allotted = split;
+        Ok(())
+    }
 
-            if allotted.minerals > total_spent.minerals {
-                allotted.minerals -= total_spent.minerals;
-            }
-            else {
-                allotted.minerals = 0;
-            }
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        // zero all allotted budgets
+        self.book.clear_allotted();
 
-            if allotted.vespene > total_spent.vespene {
-                allotted.vespene -= total_spent.vespene;
-            }
-            else {
-                allotted.vespene = 0;
-            }
+        let mut remaining = input.allotted.budget;
 
-            if allotted.food > total_spent.food {
-                allotted.food -= total_spent.food;
-            }
-            else {
-                allotted.food = 0;
-            }
+        // idle and finished outputs give up their place in line right away
+        for o in active_outputs(&self.order, &self.book.requests) {
+            match self.requested(o) {
+                Some(request) => {
+                    if !request.fits_within(&remaining) {
+                        // save up for this output before funding lower ones
+                        break
+                    }
 
-            if allotted.larva > total_spent.larva {
-                allotted.larva -= total_spent.larva;
-            }
-            else {
-                allotted.larva = 0;
+                    *self.book.allotted.get_mut(&o).unwrap() = request;
+                    remaining = remaining.saturating_sub(request);
+                },
+                None => {
+                    *self.book.allotted.get_mut(&o).unwrap() = remaining;
+                    break
+                }
             }
-
-            *self.allotted.get_mut(&hdl).unwrap() = allotted;
         }
 
-        let remaining = total - split * self.allotted.len() as u32;
-
-        // just distribute the remaining budget to a random output
-        let n = random::<usize>() % self.allotted.len();
-        *self.allotted.values_mut().nth(n).unwrap() += remaining;
+        self.book.repay_debts();
 
         Ok(())
     }
@@ -428,43 +2199,226 @@ impl cortical::Lobe for EvenSplitLedgerLobe {
         -> cortical::Result<Self::Output>
     {
         Ok(
-            EvenSplitLedgerOutput {
-                split_budget: LobeBudget {
-                    lobe: self.hdl.unwrap(),
-                    budget: self.allotted[&output],
-                }
+            PriorityLedgerOutput {
+                split_budget: self.book.split_budget(output),
+                report: self.book.report(),
             }
         )
     }
 
     fn feedback(&mut self, input: Self::FeedbackInput) -> cortical::Result<()>
     {
-        if input.each_spent.len() != self.allotted.len() {
-            bail!("did not receive feedback from all outputs")
-        }
-
-        for spent in input.each_spent {
-            self.total_spent += spent.budget;
-            *self.spenders.get_mut(&spent.lobe).unwrap() += spent.budget;
-            *self.spent.get_mut(&spent.lobe).unwrap() = spent.budget;
-        }
-
-        Ok(())
+        self.book.record(&input.each_spent, input.each_request)
     }
 
     fn tailor_feedback(&mut self, _: cortical::NodeHdl)
         -> cortical::Result<Self::FeedbackOutput>
     {
         Ok(
-            EvenSplitLedgerFeedbackOutput {
-                spent: LobeBudget {
-                    lobe: self.hdl.unwrap(),
-                    budget: self.spent.values().fold(
-                        Budget::default(),
-                        |acc, spent| acc + *spent
-                    )
-                }
+            PriorityLedgerFeedbackOutput {
+                spent: self.book.total_spent(),
+                request: self.book.combined_request(),
             }
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(minerals: u32, vespene: u32, food: u32, larva: u32) -> Budget {
+        Budget {
+            minerals: minerals,
+            vespene: vespene,
+            food: food,
+            larva: larva,
+        }
+    }
+
+    #[test]
+    fn checked_sub_fails_when_any_resource_runs_out() {
+        let bank = budget(100, 50, 2, 1);
+
+        assert_eq!(
+            bank.checked_sub(budget(100, 25, 2, 0)), Some(budget(0, 25, 0, 1))
+        );
+        assert_eq!(bank.checked_sub(budget(50, 0, 0, 2)), None);
+    }
+
+    #[test]
+    fn saturating_sub_stops_at_zero() {
+        let bank = budget(100, 50, 2, 1);
+
+        assert_eq!(
+            bank.saturating_sub(budget(150, 25, 3, 0)), budget(0, 25, 0, 1)
+        );
+    }
+
+    #[test]
+    fn operators_saturate() {
+        let max = u32::max_value();
+        let bank = budget(100, max, 2, 1);
+
+        assert_eq!(bank - budget(150, 0, 1, 0), budget(0, max, 1, 1));
+        assert_eq!(bank + budget(0, 1, 0, 0), budget(100, max, 2, 1));
+        assert_eq!(bank * 2, budget(200, max, 4, 2));
+    }
+
+    #[test]
+    fn fits_within_needs_every_resource() {
+        let bank = budget(100, 50, 2, 1);
+
+        assert!(budget(100, 50, 2, 1).fits_within(&bank));
+        assert!(budget(0, 0, 0, 0).fits_within(&bank));
+        assert!(!budget(100, 50, 2, 2).fits_within(&bank));
+        assert!(!budget(101, 0, 0, 0).fits_within(&bank));
+    }
+
+    #[test]
+    fn deltas_split_into_debt_and_surplus() {
+        let delta = budget(100, 0, 2, 1).delta(budget(50, 25, 2, 3));
+
+        assert_eq!(
+            delta,
+            BudgetDelta { minerals: 50, vespene: -25, food: 0, larva: -2 }
+        );
+        assert!(delta.is_debt());
+        assert_eq!(delta.debt(), budget(0, 25, 0, 2));
+        assert_eq!(delta.surplus(), budget(50, 0, 0, 0));
+
+        assert!(!budget(1, 1, 1, 1).delta(budget(1, 1, 1, 1)).is_debt());
+    }
+
+    #[test]
+    fn an_overspend_is_shared_out_on_the_next_split() {
+        // both outputs were allotted 200, but the first spent 300
+        let spent = [ budget(300, 0, 0, 0), budget(200, 0, 0, 0) ];
+        let (shares, remaining) = even_shares(budget(400, 0, 0, 0), &spent);
+
+        // every output has now spent or been given 450, so the overspend
+        // costs the first output 50 of its next share and nothing more
+        assert_eq!(shares, vec![ budget(150, 0, 0, 0), budget(250, 0, 0, 0) ]);
+        assert_eq!(remaining, Budget::default());
+    }
+
+    fn unit_type_data(unit_type: UnitType, minerals: u32, food: f32)
+        -> UnitTypeData
+    {
+        UnitTypeData {
+            unit_type: unit_type,
+            mineral_cost: minerals,
+            food_required: food,
+
+            ..UnitTypeData::default()
+        }
+    }
+
+    #[test]
+    fn zerglings_cost_a_pair_per_larva() {
+        // the game lists the cost and supply of a single zergling
+        let data = unit_type_data(UnitType::ZergZergling, 25, 0.5);
+
+        assert_eq!(Budget::cost_of(&data), budget(50, 0, 1, 1));
+    }
+
+    #[test]
+    fn larva_morphs_cost_one_larva_and_their_supply() {
+        let data = unit_type_data(UnitType::ZergDrone, 50, 1.0);
+
+        assert_eq!(Budget::cost_of(&data), budget(50, 0, 1, 1));
+    }
+
+    #[test]
+    fn drone_morphs_cost_neither_supply_nor_larva() {
+        let data = unit_type_data(UnitType::ZergSpawningPool, 200, 0.0);
+
+        assert_eq!(Budget::cost_of(&data), budget(200, 0, 0, 0));
+    }
+
+    #[test]
+    fn morph_sources_tell_larva_from_drones() {
+        assert_eq!(
+            MorphSource::of(UnitType::ZergZergling),
+            MorphSource::Larva { per_morph: 2 }
+        );
+        assert_eq!(
+            MorphSource::of(UnitType::ZergDrone),
+            MorphSource::Larva { per_morph: 1 }
+        );
+        assert_eq!(
+            MorphSource::of(UnitType::ZergOverlord),
+            MorphSource::Larva { per_morph: 1 }
+        );
+        assert_eq!(
+            MorphSource::of(UnitType::ZergSpawningPool), MorphSource::Drone
+        );
+        assert_eq!(
+            MorphSource::of(UnitType::ZergExtractor), MorphSource::Drone
+        );
+        assert_eq!(MorphSource::of(UnitType::ZergQueen), MorphSource::Other);
+    }
+
+    fn assert_income(income: Income, minerals: f32, vespene: f32) {
+        assert!(
+            (income.minerals - minerals).abs() < 0.001
+                && (income.vespene - vespene).abs() < 0.001,
+            "{:?} != {} minerals, {} vespene",
+            income,
+            minerals,
+            vespene
+        );
+    }
+
+    #[test]
+    fn income_is_measured_over_the_window() {
+        let mut forecast = IncomeForecastLobe::new(224, 10.0);
+
+        // 10 minerals and 5 vespene every 22.4 steps, ie. every second
+        for i in 0..11 {
+            forecast.measure(i * 224 / 10, 50 + i * 10, i * 5);
+        }
+
+        assert_income(forecast.income, 10.0, 5.0);
+    }
+
+    #[test]
+    fn spending_is_not_counted_as_income() {
+        let mut forecast = IncomeForecastLobe::new(224, 10.0);
+
+        forecast.measure(0, 100, 0);
+        forecast.measure(112, 150, 0);
+        forecast.measure(224, 0, 0);
+
+        // 50 minerals were gathered over 10 seconds, the rest was spent
+        assert_income(forecast.income, 5.0, 0.0);
+    }
+
+    #[test]
+    fn old_samples_leave_the_window() {
+        let mut forecast = IncomeForecastLobe::new(224, 10.0);
+
+        forecast.measure(0, 0, 0);
+        forecast.measure(224, 1000, 0);
+        forecast.measure(448, 1224, 0);
+
+        assert_eq!(forecast.samples.len(), 2);
+        assert_income(forecast.income, 22.4, 0.0);
+    }
+
+    #[test]
+    fn steps_going_backwards_start_over() {
+        let mut forecast = IncomeForecastLobe::new(224, 10.0);
+
+        forecast.measure(100, 0, 0);
+        forecast.measure(200, 500, 0);
+        forecast.measure(10, 50, 0);
+
+        assert_eq!(forecast.samples.len(), 1);
+        assert_income(forecast.income, 0.0, 0.0);
+
+        forecast.measure(234, 150, 0);
+
+        assert_income(forecast.income, 10.0, 0.0);
+    }
+}
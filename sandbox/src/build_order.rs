@@ -0,0 +1,797 @@
+use std::rc::Rc;
+
+use cortical;
+use rand::{ Rng };
+use sc2;
+use sc2::data::{
+    Ability, ActionTarget, Alliance, Point2, Tag, Unit, UnitType, UnitTypeData
+};
+
+use errors::{ Result, ErrorKind };
+use super::{
+    Budget,
+    ExpansionSites,
+    LobeBudget,
+    LobeBudgetRequest,
+    LobeRng,
+    MorphSource,
+    OccupancyGrid,
+    PlacementStrategy,
+    Reservation,
+    SpenderStatus,
+    KeliConstraint,
+    KeliData,
+
+    STEPS_PER_SECOND,
+
+    closest_drone,
+    find_placement,
+    free_drones,
+    free_geyser,
+    parse_unit_type,
+    seeded_rng,
+    unseeded_rng,
+};
+
+/// how long to wait for an ordered item to show up before ordering it again
+const ISSUE_TIMEOUT_SECS: f32 = 20.0;
+/// the most supply the game allows
+const MAX_SUPPLY: u32 = 200;
+
+/// when an item of a build order should be started
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BuildTrigger {
+    /// once the supply in use reaches the given amount
+    Supply(u32),
+    /// once the game has run for the given number of seconds
+    Time(u32),
+}
+
+/// a single item of a build order
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BuildStep {
+    /// when the item should be started
+    pub trigger:            BuildTrigger,
+    /// what to build
+    pub unit_type:          UnitType,
+}
+
+/// a list of units and structures to build in order
+///
+/// build orders are written as comma separated items, each made of a trigger
+/// and the name of a unit. triggers are either a supply count or a game time
+/// written as `m:ss`.
+///
+/// ```text
+/// 13 overlord, 16 hatch, 18 gas, 17 pool, 2:30 queen
+/// ```
+#[derive(Debug, Clone)]
+pub struct BuildOrder {
+    steps:                  Vec<BuildStep>,
+}
+
+impl BuildOrder {
+    /// parse a build order
+    pub fn parse(order: &str) -> Result<Self> {
+        let mut steps = vec![ ];
+
+        for item in order.split(',').map(|item| item.trim()) {
+            if item.is_empty() {
+                continue
+            }
+
+            let mut parts = item.splitn(2, char::is_whitespace);
+
+            let trigger = parts.next().unwrap();
+            let unit = match parts.next() {
+                Some(unit) => unit.trim(),
+                None => bail!(
+                    ErrorKind::InvalidBuildOrder(
+                        item.to_string(),
+                        "expected a trigger followed by a unit".to_string()
+                    )
+                )
+            };
+
+            steps.push(
+                BuildStep {
+                    trigger: parse_trigger(item, trigger)?,
+                    unit_type: parse_build_unit(item, unit)?,
+                }
+            );
+        }
+
+        if steps.is_empty() {
+            bail!(
+                ErrorKind::InvalidBuildOrder(
+                    order.to_string(), "no items".to_string()
+                )
+            )
+        }
+
+        Ok(Self { steps: steps })
+    }
+
+    /// the items in the order they are built
+    pub fn steps(&self) -> &Vec<BuildStep> {
+        &self.steps
+    }
+}
+
+fn parse_trigger(item: &str, trigger: &str) -> Result<BuildTrigger> {
+    let invalid = || ErrorKind::InvalidBuildOrder(
+        item.to_string(),
+        format!("expected a supply count or m:ss, found {:?}", trigger)
+    );
+
+    let mut parts = trigger.splitn(2, ':');
+
+    let first = match parts.next().unwrap().parse::<u32>() {
+        Ok(value) => value,
+        Err(_) => bail!(invalid())
+    };
+
+    match parts.next() {
+        Some(seconds) => match seconds.parse::<u32>() {
+            Ok(seconds) if seconds < 60 => Ok(
+                BuildTrigger::Time(first * 60 + seconds)
+            ),
+            _ => bail!(invalid())
+        },
+        None => Ok(BuildTrigger::Supply(first))
+    }
+}
+
+/// parse the short names commonly used in build orders, falling back to the
+/// full unit type name (ie. `ZergSpawningPool`)
+fn parse_build_unit(item: &str, unit: &str) -> Result<UnitType> {
+    let name = unit.to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+    ;
+
+    match name.as_str() {
+        "drone" => Ok(UnitType::ZergDrone),
+        "overlord" | "ovie" => Ok(UnitType::ZergOverlord),
+        "ling" | "lings" | "zergling" | "zerglings" => Ok(
+            UnitType::ZergZergling
+        ),
+        "queen" => Ok(UnitType::ZergQueen),
+        "roach" => Ok(UnitType::ZergRoach),
+        "hydra" | "hydralisk" => Ok(UnitType::ZergHydralisk),
+
+        "hatch" | "hatchery" => Ok(UnitType::ZergHatchery),
+        "gas" | "extractor" => Ok(UnitType::ZergExtractor),
+        "pool" | "spawning pool" => Ok(UnitType::ZergSpawningPool),
+        "evo" | "evolution chamber" => Ok(UnitType::ZergEvolutionChamber),
+        "warren" | "roach warren" => Ok(UnitType::ZergRoachWarren),
+        "bane nest" | "baneling nest" => Ok(UnitType::ZergBanelingNest),
+        "hydra den" | "hydralisk den" => Ok(UnitType::ZergHydraliskDen),
+        "spire" => Ok(UnitType::ZergSpire),
+        "spine" | "spine crawler" => Ok(UnitType::ZergSpineCrawler),
+        "spore" | "spore crawler" => Ok(UnitType::ZergSporeCrawler),
+
+        _ => match parse_unit_type(unit) {
+            Ok(unit_type) => Ok(unit_type),
+            Err(_) => bail!(
+                ErrorKind::InvalidBuildOrder(
+                    item.to_string(), format!("unknown unit {:?}", unit)
+                )
+            )
+        }
+    }
+}
+
+/// why a build order is not making progress
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BuildBlocker {
+    /// waiting for the supply in use to reach the trigger
+    Supply {
+        /// supply in use
+        current: u32,
+        /// supply needed by the trigger
+        required: u32
+    },
+    /// waiting for the game time to reach the trigger
+    Time {
+        /// seconds since the game started
+        current: u32,
+        /// seconds needed by the trigger
+        required: u32
+    },
+    /// the budget does not cover the cost of the item
+    Cost(Budget),
+    /// there is no larva, drone or hatchery to produce the item
+    NoProducer,
+    /// there is no free vespene geyser to build an extractor on
+    NoGeyser,
+    /// there is no room near a hatchery for the structure
+    NoPlacement,
+    /// there is no free base location to build a hatchery at
+    NoBaseLocation,
+    /// the game data has no costs for the unit type
+    NoUnitData,
+    /// the item was ordered and is waiting to show up in the game
+    Pending,
+}
+
+/// where a build order lobe is in its build order
+#[derive(Debug, Clone)]
+pub struct BuildOrderProgress {
+    /// the build order lobe
+    pub lobe:               cortical::NodeHdl,
+    /// the number of items that have shown up in the game
+    pub step:               usize,
+    /// the number of items in the build order
+    pub total:              usize,
+    /// the next item, or `None` once the build order is finished
+    pub current:            Option<BuildStep>,
+    /// why the next item has not been started yet
+    pub blocked:            Option<BuildBlocker>,
+}
+
+/// an item that was ordered but has not shown up in the game yet
+#[derive(Debug, Copy, Clone)]
+struct PendingItem {
+    ability:                Ability,
+    /// the unit that was given the order
+    unit:                   Tag,
+    /// how many of the unit type there were when the item was ordered
+    count:                  usize,
+    issued:                 u32,
+}
+
+/// executes a build order
+///
+/// items are started one at a time once their trigger is reached and the
+/// budget covers their cost. while waiting for a supply trigger, drones are
+/// built to reach it, along with an overlord whenever there is no room for
+/// them. hatcheries are built at the closest free base location, other
+/// structures are placed around a hatchery with the placement strategy and
+/// extractors on the free geyser closest to one.
+///
+/// the build order only moves on once the ordered unit or structure shows up
+/// in the game. the item is ordered again if it takes too long and the unit
+/// given the order is no longer carrying it out.
+pub struct BuildOrderLobe {
+    hdl:                    Option<cortical::NodeHdl>,
+
+    order:                  BuildOrder,
+    next:                   usize,
+    pending:                Option<PendingItem>,
+    blocked:                Option<BuildBlocker>,
+    placement:              PlacementStrategy,
+    sites:                  ExpansionSites,
+
+    spent:                  Budget,
+    reservations:           Vec<Reservation>,
+    request:                Option<Budget>,
+    urgency:                u32,
+    status:                 SpenderStatus,
+
+    rng:                    LobeRng,
+
+    commands:               Vec<sc2::Command>,
+}
+
+impl BuildOrderLobe {
+    /// execute the given build order
+    pub fn new(order: BuildOrder) -> Self {
+        Self {
+            hdl: None,
+
+            order: order,
+            next: 0,
+            pending: None,
+            blocked: None,
+            placement: PlacementStrategy::default(),
+            sites: ExpansionSites::new(),
+
+            spent: Budget::default(),
+            reservations: vec![ ],
+            request: None,
+            urgency: 1,
+            status: SpenderStatus::Active,
+
+            rng: unseeded_rng(),
+
+            commands: vec![ ],
+        }
+    }
+
+    /// use a seeded generator so that the same frames produce the same commands
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seeded_rng(seed);
+        self
+    }
+
+    /// set how urgently the budget for the next item is requested
+    pub fn with_urgency(mut self, urgency: u32) -> Self {
+        self.urgency = urgency;
+        self
+    }
+
+    /// choose where structures are placed around the hatchery
+    pub fn with_placement(mut self, placement: PlacementStrategy) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    fn waiting_for(&self, frame: &sc2::FrameData, trigger: BuildTrigger)
+        -> Option<BuildBlocker>
+    {
+        match trigger {
+            BuildTrigger::Supply(required) => {
+                let current = frame.state.food_used;
+
+                if current < required {
+                    Some(
+                        BuildBlocker::Supply {
+                            current: current, required: required
+                        }
+                    )
+                }
+                else {
+                    None
+                }
+            },
+            BuildTrigger::Time(required) => {
+                let current = (
+                    frame.state.current_step as f32 / STEPS_PER_SECOND
+                ) as u32;
+
+                if current < required {
+                    Some(
+                        BuildBlocker::Time {
+                            current: current, required: required
+                        }
+                    )
+                }
+                else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// move on to the next item once the pending one shows up, or forget it
+    /// so that it is ordered again once it times out and nobody is working on
+    /// it anymore
+    fn check_pending(&mut self, frame: &sc2::FrameData) {
+        let timeout = (ISSUE_TIMEOUT_SECS * STEPS_PER_SECOND) as u32;
+
+        let pending = match self.pending {
+            Some(pending) => pending,
+            None => return
+        };
+        let unit_type = self.order.steps()[self.next].unit_type;
+
+        if count_units(frame, unit_type, pending.ability) > pending.count {
+            self.pending = None;
+            self.next += 1;
+        }
+        else if pending.issued + timeout < frame.state.current_step
+            && !is_carrying_out(frame, pending.unit, pending.ability)
+        {
+            self.pending = None;
+        }
+    }
+
+    /// what to build while waiting for a supply trigger
+    ///
+    /// drones are built to reach the trigger, with an overlord first whenever
+    /// there is no room for them. nothing is built while an overlord is
+    /// already on its way.
+    fn supply_filler(&self, frame: &sc2::FrameData) -> Option<UnitType> {
+        if frame.state.food_used < frame.state.food_cap {
+            return Some(UnitType::ZergDrone)
+        }
+
+        if frame.state.food_cap >= MAX_SUPPLY {
+            return None
+        }
+
+        let morphing = match frame.data.unit_type_data.get(
+            &UnitType::ZergOverlord
+        ) {
+            Some(data) => is_ordered(frame, data.ability),
+            None => false
+        };
+
+        if morphing {
+            None
+        }
+        else {
+            Some(UnitType::ZergOverlord)
+        }
+    }
+
+    /// try to start building the unit type, returning its cost and the unit
+    /// that was given the order if a command was issued
+    fn build(
+        &mut self,
+        input: &BuildOrderInput,
+        unit_type: UnitType,
+        budget: Budget
+    )
+        -> ::std::result::Result<(Budget, Tag), BuildBlocker>
+    {
+        let frame = &input.frame;
+
+        let data = match frame.data.unit_type_data.get(&unit_type) {
+            Some(data) => Rc::clone(data),
+            None => return Err(BuildBlocker::NoUnitData)
+        };
+
+        let cost = Budget::cost_of(&data);
+
+        self.request = Some(cost);
+
+        if !cost.fits_within(&budget) {
+            return Err(BuildBlocker::Cost(cost))
+        }
+
+        let source = MorphSource::of(unit_type);
+
+        let (producer, target) = match source {
+            MorphSource::Larva { .. } => self.morph_larva(frame)?,
+            MorphSource::Drone if unit_type == UnitType::ZergHatchery => {
+                self.expand(frame)?
+            },
+            MorphSource::Drone => self.morph_drone(input, &data)?,
+            MorphSource::Other => self.train_at_hatchery(frame)?,
+        };
+
+        // the game only charges for a structure once the drone reaches the
+        // build site, everything else is paid up front
+        if source == MorphSource::Drone {
+            self.reservations.push(
+                Reservation {
+                    lobe: self.hdl.unwrap(),
+                    tag: producer.tag,
+                    ability: data.ability,
+                    cost: cost,
+                }
+            );
+        }
+
+        self.commands.push(
+            sc2::Command::Action {
+                units: vec![ Rc::clone(&producer) ],
+                ability: data.ability,
+                target: target
+            }
+        );
+
+        Ok((cost, producer.tag))
+    }
+
+    fn morph_larva(&mut self, frame: &sc2::FrameData)
+        -> ::std::result::Result<(Rc<Unit>, Option<ActionTarget>), BuildBlocker>
+    {
+        let larva = own_units(frame, UnitType::ZergLarva);
+
+        if larva.is_empty() {
+            return Err(BuildBlocker::NoProducer)
+        }
+
+        Ok((Rc::clone(&larva[self.rng.gen_range(0, larva.len())]), None))
+    }
+
+    /// send the drone closest to the nearest free base location to build a
+    /// hatchery there
+    fn expand(&mut self, frame: &sc2::FrameData)
+        -> ::std::result::Result<(Rc<Unit>, Option<ActionTarget>), BuildBlocker>
+    {
+        let location = match self.sites.closest_free() {
+            Some(location) => location,
+            None => return Err(BuildBlocker::NoBaseLocation)
+        };
+
+        match closest_drone(frame, location) {
+            Some(drone) => Ok((drone, Some(ActionTarget::Location(location)))),
+            None => Err(BuildBlocker::NoProducer)
+        }
+    }
+
+    fn morph_drone(&mut self, input: &BuildOrderInput, data: &UnitTypeData)
+        -> ::std::result::Result<(Rc<Unit>, Option<ActionTarget>), BuildBlocker>
+    {
+        let frame = &input.frame;
+
+        // drones already on their way to build something are left alone
+        let drones = free_drones(frame);
+        let hatcheries = own_units(frame, UnitType::ZergHatchery);
+
+        if drones.is_empty() || hatcheries.is_empty() {
+            return Err(BuildBlocker::NoProducer)
+        }
+
+        let drone = Rc::clone(&drones[self.rng.gen_range(0, drones.len())]);
+
+        let target = if data.unit_type == UnitType::ZergExtractor {
+            match free_geyser(frame, &hatcheries) {
+                Some(geyser) => ActionTarget::UnitTag(geyser.tag),
+                None => return Err(BuildBlocker::NoGeyser)
+            }
+        }
+        else {
+            let h = self.rng.gen_range(0, hatcheries.len());
+            let location = find_placement(
+                frame,
+                input.grid.as_ref(),
+                data.unit_type,
+                Point2::new(hatcheries[h].pos.x, hatcheries[h].pos.y),
+                self.placement,
+                &mut self.rng
+            );
+
+            match location {
+                Some(location) => ActionTarget::Location(location),
+                None => return Err(BuildBlocker::NoPlacement)
+            }
+        };
+
+        Ok((drone, Some(target)))
+    }
+
+    fn train_at_hatchery(&mut self, frame: &sc2::FrameData)
+        -> ::std::result::Result<(Rc<Unit>, Option<ActionTarget>), BuildBlocker>
+    {
+        let hatcheries: Vec<Rc<Unit>> = own_units(
+            frame, UnitType::ZergHatchery
+        ).into_iter().filter(
+            |h| h.build_progress >= 1.0 && h.orders.is_empty()
+        ).collect();
+
+        if hatcheries.is_empty() {
+            return Err(BuildBlocker::NoProducer)
+        }
+
+        Ok(
+            (
+                Rc::clone(&hatcheries[self.rng.gen_range(0, hatcheries.len())]),
+                None
+            )
+        )
+    }
+}
+
+/// the number of units of the given type, including the eggs and hatcheries
+/// that have already been paid to make one
+///
+/// drones carrying the order are left out since the game may still reject the
+/// structure once they reach the build site.
+fn count_units(frame: &sc2::FrameData, unit_type: UnitType, ability: Ability)
+    -> usize
+{
+    let paid = MorphSource::of(unit_type) != MorphSource::Drone;
+
+    frame.state.units.iter().filter(
+        |u| u.alliance == Alliance::Domestic && (
+            u.unit_type == unit_type
+                || (paid && u.orders.iter().any(|o| o.ability == ability))
+        )
+    ).count()
+}
+
+/// true if any of our units is carrying out the ability
+fn is_ordered(frame: &sc2::FrameData, ability: Ability) -> bool {
+    frame.state.units.iter().any(
+        |u| u.alliance == Alliance::Domestic
+            && u.orders.iter().any(|o| o.ability == ability)
+    )
+}
+
+/// true if the unit is still there and carrying out the ability
+fn is_carrying_out(frame: &sc2::FrameData, tag: Tag, ability: Ability)
+    -> bool
+{
+    frame.state.units.iter().any(
+        |u| u.tag == tag && u.orders.iter().any(|o| o.ability == ability)
+    )
+}
+
+fn own_units(frame: &sc2::FrameData, unit_type: UnitType) -> Vec<Rc<Unit>> {
+    frame.state.filter_units(
+        |u| u.unit_type == unit_type && u.alliance == Alliance::Domestic
+    )
+}
+
+create_lobe_data! {
+    module: build_order,
+
+    req frame: Rc<sc2::FrameData>,
+    opt budget: LobeBudget,
+    opt locations: Rc<Vec<Point2>>,
+    opt grid: Rc<OccupancyGrid>,
+
+    out commands: Vec<sc2::Command>,
+    out progress: BuildOrderProgress,
+
+    fbk out spent: LobeBudget,
+    fbk out request: LobeBudgetRequest,
+    fbk out reservations: Vec<Reservation>,
+}
+
+pub use self::build_order::{
+    Input as BuildOrderInput,
+    Output as BuildOrderOutput,
+    FeedbackInput as BuildOrderFeedbackInput,
+    FeedbackOutput as BuildOrderFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: BuildOrderLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: BuildOrderInput,
+    output: BuildOrderOutput,
+    feedback_input: BuildOrderFeedbackInput,
+    feedback_output: BuildOrderFeedbackOutput,
+
+    req frame: FrameData,
+    opt budget: Budget,
+    opt locations: PotentialBaseLocations,
+    opt grid: OccupancyGrid,
+
+    out commands: Commands,
+    out progress: BuildOrderProgress,
+
+    fbk out spent: Budget,
+    fbk out request: BudgetRequest,
+    fbk out reservations: Reservations,
+}
+
+impl cortical::Lobe for BuildOrderLobe {
+    type Input = BuildOrderInput;
+    type Output = BuildOrderOutput;
+    type FeedbackInput = BuildOrderFeedbackInput;
+    type FeedbackOutput = BuildOrderFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.hdl = Some(hdl);
+        self.next = 0;
+        self.pending = None;
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.spent = Budget::default();
+        self.reservations.clear();
+        self.commands.clear();
+
+        if let Some(ref locations) = input.locations {
+            self.sites.update(&input.frame, locations, None);
+        }
+
+        self.check_pending(&input.frame);
+
+        let step = match self.order.steps().get(self.next) {
+            Some(&step) => step,
+            None => {
+                self.blocked = None;
+                self.request = Some(Budget::default());
+                self.status = SpenderStatus::Done;
+
+                return Ok(())
+            }
+        };
+
+        if self.pending.is_some() {
+            self.blocked = Some(BuildBlocker::Pending);
+            self.request = Some(Budget::default());
+            self.status = SpenderStatus::Idle;
+
+            return Ok(())
+        }
+
+        let budget = match input.budget {
+            Some(ref budget) => budget.budget,
+            None => Budget::default()
+        };
+
+        match self.waiting_for(&input.frame, step.trigger) {
+            // build drones until the supply is reached
+            Some(blocker @ BuildBlocker::Supply { .. }) => {
+                let built = match self.supply_filler(&input.frame) {
+                    Some(unit_type) => self.build(
+                        &input, unit_type, budget
+                    ).ok(),
+                    None => None
+                };
+
+                self.spent = match built {
+                    Some((cost, _)) => cost,
+                    None => Budget::default()
+                };
+                self.blocked = Some(blocker);
+            },
+            Some(blocker) => {
+                let cost = input.frame.data.unit_type_data.get(
+                    &step.unit_type
+                ).map(
+                    |data| Budget::cost_of(data)
+                );
+
+                // save up for the item while waiting
+                self.request = cost;
+                self.blocked = Some(blocker);
+            },
+            None => match self.build(&input, step.unit_type, budget) {
+                Ok((cost, unit)) => {
+                    let ability = input.frame.data.unit_type_data[
+                        &step.unit_type
+                    ].ability;
+
+                    self.spent = cost;
+                    self.blocked = Some(BuildBlocker::Pending);
+                    self.pending = Some(
+                        PendingItem {
+                            ability: ability,
+                            unit: unit,
+                            count: count_units(
+                                &input.frame, step.unit_type, ability
+                            ),
+                            issued: input.frame.state.current_step,
+                        }
+                    );
+                },
+                Err(blocker) => {
+                    self.blocked = Some(blocker);
+                }
+            }
+        }
+
+        self.status = SpenderStatus::Active;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            BuildOrderOutput {
+                commands: self.commands.clone(),
+                progress: BuildOrderProgress {
+                    lobe: self.hdl.unwrap(),
+                    step: self.next,
+                    total: self.order.steps().len(),
+                    current: self.order.steps().get(self.next).cloned(),
+                    blocked: self.blocked,
+                }
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            BuildOrderFeedbackOutput {
+                spent: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.spent,
+                },
+                request: LobeBudgetRequest {
+                    lobe: self.hdl.unwrap(),
+                    cost: self.request,
+                    urgency: self.urgency,
+                    status: self.status,
+                },
+                reservations: self.reservations.clone(),
+            }
+        )
+    }
+}
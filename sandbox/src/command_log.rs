@@ -0,0 +1,379 @@
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::rc::Rc;
+
+use cortical;
+use cortical::{ ResultExt };
+use sc2;
+use sc2::data::{ ActionTarget };
+use serde_json;
+
+use errors::{ Result };
+use super::{ LobeNames, KeliConstraint, KeliData };
+
+/// the target of a logged action or debug text
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoggedTarget {
+    /// a point on the map
+    Location {
+        /// x coordinate
+        x: f32,
+        /// y coordinate
+        y: f32
+    },
+    /// a point in the world
+    World {
+        /// x coordinate
+        x: f32,
+        /// y coordinate
+        y: f32,
+        /// z coordinate
+        z: f32
+    },
+    /// a point on the screen
+    Screen {
+        /// x coordinate
+        x: f32,
+        /// y coordinate
+        y: f32
+    },
+    /// another unit
+    Unit {
+        /// the unit's tag
+        tag: u64
+    },
+}
+
+/// a serializable copy of an `sc2::Command`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum LoggedCommand {
+    /// a unit ability
+    Action {
+        /// tags of the units performing the ability
+        units: Vec<u64>,
+        /// the ability name
+        ability: String,
+        /// the target of the ability
+        target: Option<LoggedTarget>
+    },
+    /// a debug sphere
+    DebugSphere {
+        /// center of the sphere
+        center: (f32, f32, f32),
+        /// radius of the sphere
+        radius: f32,
+        /// rgb color
+        color: (u8, u8, u8)
+    },
+    /// debug text
+    DebugText {
+        /// the text
+        text: String,
+        /// rgb color
+        color: (u8, u8, u8),
+        /// where the text is drawn
+        target: Option<LoggedTarget>
+    },
+    /// any other command, in its debug representation
+    Other {
+        /// the debug representation of the command
+        debug: String
+    },
+}
+
+impl<'a> From<&'a sc2::Command> for LoggedCommand {
+    fn from(command: &'a sc2::Command) -> Self {
+        match *command {
+            sc2::Command::Action { ref units, ability, ref target } => {
+                LoggedCommand::Action {
+                    units: units.iter().map(|u| u.tag).collect(),
+                    ability: format!("{:?}", ability),
+                    target: match *target {
+                        Some(ActionTarget::Location(p)) => Some(
+                            LoggedTarget::Location { x: p.x, y: p.y }
+                        ),
+                        Some(ActionTarget::UnitTag(tag)) => Some(
+                            LoggedTarget::Unit { tag: tag }
+                        ),
+                        None => None
+                    }
+                }
+            },
+            sc2::Command::DebugSphere { center, radius, color } => {
+                LoggedCommand::DebugSphere {
+                    center: (center.x, center.y, center.z),
+                    radius: radius,
+                    color: color
+                }
+            },
+            sc2::Command::DebugText { ref text, color, ref target } => {
+                LoggedCommand::DebugText {
+                    text: text.clone(),
+                    color: color,
+                    target: match *target {
+                        Some(sc2::DebugTextTarget::World(p)) => Some(
+                            LoggedTarget::World { x: p.x, y: p.y, z: p.z }
+                        ),
+                        Some(sc2::DebugTextTarget::Screen(p)) => Some(
+                            LoggedTarget::Screen { x: p.x, y: p.y }
+                        ),
+                        None => None
+                    }
+                }
+            },
+            ref command => LoggedCommand::Other {
+                debug: format!("{:?}", command)
+            }
+        }
+    }
+}
+
+/// a single line of the command log
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandLogEntry {
+    /// the game step the command was issued on
+    pub step:               u32,
+    /// the name of the lobe that issued the command
+    pub lobe:               String,
+    /// the command
+    pub command:            LoggedCommand,
+}
+
+/// the commands issued by a single lobe
+#[derive(Debug, Clone)]
+pub struct LobeCommands {
+    /// the lobe that issued the commands
+    pub lobe:               cortical::NodeHdl,
+    /// the commands
+    pub commands:           Vec<sc2::Command>,
+}
+
+/// tags the commands of a single lobe with its node handle
+///
+/// the command log can't tell which of its inputs a command came from, so
+/// one tap is placed between every command source and the log.
+pub struct CommandTapLobe {
+    source:                 Option<cortical::NodeHdl>,
+    commands:               Vec<sc2::Command>,
+}
+
+impl CommandTapLobe {
+    /// tag the commands of whichever lobe is connected
+    pub fn new() -> Self {
+        Self { source: None, commands: vec![ ] }
+    }
+}
+
+create_lobe_data! {
+    module: command_tap,
+
+    req commands: Vec<sc2::Command>,
+
+    out commands: LobeCommands,
+}
+
+pub use self::command_tap::{
+    Input as CommandTapInput,
+    Output as CommandTapOutput,
+    FeedbackInput as CommandTapFeedbackInput,
+    FeedbackOutput as CommandTapFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: CommandTapLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: CommandTapInput,
+    output: CommandTapOutput,
+    feedback_input: CommandTapFeedbackInput,
+    feedback_output: CommandTapFeedbackOutput,
+
+    req commands: Commands,
+
+    out commands: LobeCommands,
+}
+
+impl cortical::Lobe for CommandTapLobe {
+    type Input = CommandTapInput;
+    type Output = CommandTapOutput;
+    type FeedbackInput = CommandTapFeedbackInput;
+    type FeedbackOutput = CommandTapFeedbackOutput;
+
+    fn start(
+        &mut self,
+        _: cortical::NodeHdl,
+        inputs: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        if inputs.len() != 1 {
+            bail!("a command tap needs exactly one input, got {}", inputs.len())
+        }
+
+        self.source = Some(inputs[0]);
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.commands = input.commands;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            CommandTapOutput {
+                commands: LobeCommands {
+                    lobe: self.source.unwrap(),
+                    commands: self.commands.clone(),
+                }
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(CommandTapFeedbackOutput { })
+    }
+}
+
+/// writes every command it receives to a JSON Lines file
+///
+/// connect a `CommandTapLobe` to every lobe that sends commands to the
+/// command merger and the taps to the log. commands are tagged with the name
+/// of the issuing lobe from the topology (or its node handle if it has no
+/// name).
+pub struct CommandLogLobe {
+    names:                  LobeNames,
+
+    writer:                 Option<BufWriter<File>>,
+}
+
+impl CommandLogLobe {
+    /// log to a new file at the given path
+    pub fn create<P: AsRef<Path>>(path: P, names: LobeNames) -> Result<Self> {
+        Ok(
+            Self {
+                names: names,
+
+                writer: Some(BufWriter::new(File::create(path)?)),
+            }
+        )
+    }
+
+    fn lobe_name(&self, hdl: cortical::NodeHdl) -> String {
+        match self.names.borrow().get(&hdl) {
+            Some(name) => name.clone(),
+            None => format!("{:?}", hdl)
+        }
+    }
+
+    fn write_commands(&mut self, step: u32, commands: &[LobeCommands])
+        -> Result<()>
+    {
+        let mut entries = vec![ ];
+
+        for lobe_commands in commands {
+            let lobe = self.lobe_name(lobe_commands.lobe);
+
+            for command in &lobe_commands.commands {
+                entries.push(
+                    CommandLogEntry {
+                        step: step,
+                        lobe: lobe.clone(),
+                        command: LoggedCommand::from(command),
+                    }
+                );
+            }
+        }
+
+        if let Some(ref mut writer) = self.writer {
+            for entry in entries {
+                serde_json::to_writer(&mut *writer, &entry)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+create_lobe_data! {
+    module: command_log,
+
+    req frame: Rc<sc2::FrameData>,
+    var commands: LobeCommands,
+}
+
+pub use self::command_log::{
+    Input as CommandLogInput,
+    Output as CommandLogOutput,
+    FeedbackInput as CommandLogFeedbackInput,
+    FeedbackOutput as CommandLogFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: CommandLogLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: CommandLogInput,
+    output: CommandLogOutput,
+    feedback_input: CommandLogFeedbackInput,
+    feedback_output: CommandLogFeedbackOutput,
+
+    req frame: FrameData,
+    var commands: LobeCommands,
+}
+
+impl cortical::Lobe for CommandLogLobe {
+    type Input = CommandLogInput;
+    type Output = CommandLogOutput;
+    type FeedbackInput = CommandLogFeedbackInput;
+    type FeedbackOutput = CommandLogFeedbackOutput;
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.write_commands(
+            input.frame.state.current_step, &input.commands
+        ).chain_err(
+            || cortical::ErrorKind::LobeError
+        )?;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(CommandLogOutput { })
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(CommandLogFeedbackOutput { })
+    }
+
+    fn stop(&mut self) -> cortical::Result<()> {
+        self.close().chain_err(|| cortical::ErrorKind::LobeError)?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 
 use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{ Ordering };
 use std::thread;
 
 use cortical;
@@ -15,7 +17,9 @@ use gtk::{
     WidgetExt,
     ContainerExt,
     ImageExt,
-    Image
+    Image,
+    CheckButton,
+    ToggleButtonExt
 };
 use relm::{
     Relm,
@@ -25,10 +29,11 @@ use relm::{
 use sc2;
 
 use errors::{ Result, Error, ErrorKind };
-use super::{ KeliConstraint, KeliData };
+use super::{ DebugToggle, KeliConstraint, KeliData };
 
 struct Model {
     close_receiver:         Option<oneshot::Receiver<()>>,
+    debug:                  DebugToggle,
 
     pathing_receiver:       Option<mpsc::Receiver<sc2::data::ImageData>>,
     placement_receiver:     Option<mpsc::Receiver<sc2::data::ImageData>>,
@@ -85,6 +90,7 @@ impl Update for DebugWindow {
     type Model = Model;
     type ModelParam = (
         oneshot::Receiver<()>,
+        DebugToggle,
 
         mpsc::Receiver<sc2::data::ImageData>,
         mpsc::Receiver<sc2::data::ImageData>,
@@ -99,6 +105,7 @@ impl Update for DebugWindow {
         _: &Relm<Self>,
         params: (
             oneshot::Receiver<()>,
+            DebugToggle,
 
             mpsc::Receiver<sc2::data::ImageData>,
             mpsc::Receiver<sc2::data::ImageData>,
@@ -112,13 +119,14 @@ impl Update for DebugWindow {
     {
         Model {
             close_receiver: Some(params.0),
+            debug: params.1,
 
-            pathing_receiver: Some(params.1),
-            placement_receiver: Some(params.2),
-            terrain_receiver: Some(params.3),
+            pathing_receiver: Some(params.2),
+            placement_receiver: Some(params.3),
+            terrain_receiver: Some(params.4),
 
-            creep_receiver: Some(params.4),
-            visibility_receiver: Some(params.5)
+            creep_receiver: Some(params.5),
+            visibility_receiver: Some(params.6)
         }
     }
 
@@ -166,6 +174,15 @@ impl Widget for DebugWindow {
         let creep = gtk::Image::new();
         let visibility = gtk::Image::new();
 
+        // flips debug drawing for every lobe sharing the toggle
+        let debug = CheckButton::new_with_label("draw debug");
+        let toggle = Arc::clone(&model.debug);
+
+        debug.set_active(toggle.load(Ordering::Relaxed));
+        debug.connect_toggled(
+            move |button| toggle.store(button.get_active(), Ordering::Relaxed)
+        );
+
         relm.connect_exec_ignore_err(
             mem::replace(&mut model.close_receiver, None).unwrap(),
             |_| Msg::Quit
@@ -193,6 +210,7 @@ impl Widget for DebugWindow {
             Msg::UpdateVisibility
         );
 
+        vbox.add(&debug);
         vbox.add(&pathing);
         vbox.add(&placement);
         vbox.add(&terrain);
@@ -217,6 +235,7 @@ impl Widget for DebugWindow {
 
 pub struct DebugWindowLobe {
     last_updated:           u32,
+    debug:                  DebugToggle,
 
     close_sender:           Option<oneshot::Sender<()>>,
 
@@ -234,6 +253,7 @@ impl DebugWindowLobe {
     pub fn new() -> Self {
         Self {
             last_updated: 0,
+            debug: DebugToggle::default(),
             close_sender: None,
 
             pathing_sender: None,
@@ -247,6 +267,12 @@ impl DebugWindowLobe {
         }
     }
 
+    /// turn debug drawing on and off from the window
+    pub fn with_debug_toggle(mut self, toggle: DebugToggle) -> Self {
+        self.debug = toggle;
+        self
+    }
+
     fn start_window(&mut self) -> Result<()> {
         let (close_tx, close_rx) = oneshot::channel();
 
@@ -257,6 +283,8 @@ impl DebugWindowLobe {
         let (creep_tx, creep_rx) = mpsc::channel(1);
         let (visibility_tx, visibility_rx) = mpsc::channel(1);
 
+        let debug = Arc::clone(&self.debug);
+
         self.close_sender = Some(close_tx);
 
         self.pathing_sender = Some(pathing_tx);
@@ -271,6 +299,7 @@ impl DebugWindowLobe {
                 DebugWindow::run(
                     (
                         close_rx,
+                        debug,
 
                         pathing_rx,
                         placement_rx,
@@ -1,22 +1,44 @@
 use std::rc::Rc;
 
 use cortical;
-use rand::random;
+use rand::{ Rng };
 use sc2;
-use sc2::data::{
-    UnitType, UnitTypeData, Point2, Vector2, ActionTarget
+use sc2::data::{ UnitType, UnitTypeData, Point2, ActionTarget };
+
+use super::{
+    Budget,
+    LobeBudget,
+    LobeBudgetRequest,
+    LobeRng,
+    OccupancyGrid,
+    PlacementStrategy,
+    Reservation,
+    SpenderStatus,
+    KeliConstraint,
+    KeliData,
+
+    find_placement,
+    free_drones,
+    free_geyser,
+    seeded_rng,
+    unseeded_rng,
 };
 
-use super::{ Budget, LobeBudget, KeliConstraint, KeliData };
-
 pub struct RandomDroneMorpherLobe {
     hdl:            Option<cortical::NodeHdl>,
 
     unit_type:      UnitType,
     data:           Option<Rc<UnitTypeData>>,
     one_and_done:   bool,
+    placement:      PlacementStrategy,
 
     spent:          Budget,
+    reservations:   Vec<Reservation>,
+    request:        Option<Budget>,
+    urgency:        u32,
+    status:         SpenderStatus,
+
+    rng:            LobeRng,
 
     commands:       Vec<sc2::Command>
 }
@@ -29,8 +51,15 @@ impl RandomDroneMorpherLobe {
             unit_type: unit_type,
             data: None,
             one_and_done: false,
+            placement: PlacementStrategy::default(),
 
             spent: Budget::default(),
+            reservations: vec![ ],
+            request: None,
+            urgency: 1,
+            status: SpenderStatus::Active,
+
+            rng: unseeded_rng(),
 
             commands: vec![ ]
         }
@@ -43,29 +72,73 @@ impl RandomDroneMorpherLobe {
             unit_type: unit_type,
             data: None,
             one_and_done: true,
+            placement: PlacementStrategy::default(),
 
             spent: Budget::default(),
+            reservations: vec![ ],
+            request: None,
+            urgency: 1,
+            status: SpenderStatus::Active,
+
+            rng: unseeded_rng(),
 
             commands: vec![ ]
         }
     }
 
-    fn morph_drone(
-        &self, input: &RandomDroneMorpherInput, data: &UnitTypeData
-    )
-        -> Option<sc2::Command>
-    {
+    /// use a seeded generator so that the same frames produce the same commands
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seeded_rng(seed);
+        self
+    }
+
+    /// set how urgently the budget for this lobe's morphs is requested
+    pub fn with_urgency(mut self, urgency: u32) -> Self {
+        self.urgency = urgency;
+        self
+    }
+
+    /// choose where structures are placed around the hatchery
+    pub fn with_placement(mut self, placement: PlacementStrategy) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    fn is_satisfied(&self, frame: &sc2::FrameData) -> bool {
         if self.one_and_done {
-            let existing = input.frame.state.filter_units(
+            let existing = frame.state.filter_units(
                 |u| u.unit_type == self.unit_type
             );
 
-            // only allow one to be built at a time
+            // one is enough, whether it is finished or still morphing
             if existing.len() >= 1 {
-                return None
+                return true
             }
         }
 
+        false
+    }
+
+    fn has_drones_and_hatcheries(&self, frame: &sc2::FrameData) -> bool {
+        let drones = frame.state.filter_units(
+            |u| u.unit_type == UnitType::ZergDrone
+        );
+        let hatcheries = frame.state.filter_units(
+            |u| u.unit_type == UnitType::ZergHatchery
+        );
+
+        drones.len() >= 1 && hatcheries.len() >= 1
+    }
+
+    fn morph_drone(
+        &mut self, input: &RandomDroneMorpherInput, data: &UnitTypeData
+    )
+        -> Option<sc2::Command>
+    {
+        if self.is_satisfied(&input.frame) {
+            return None
+        }
+
         let budget = {
             if let Some(ref budget) = input.budget {
                 budget.budget
@@ -75,16 +148,12 @@ impl RandomDroneMorpherLobe {
             }
         };
 
-        if data.mineral_cost > budget.minerals
-            || data.vespene_cost > budget.vespene
-            || data.food_required > budget.food as f32
-        {
+        if !Budget::cost_of(data).fits_within(&budget) {
             return None
         }
 
-        let drones = input.frame.state.filter_units(
-            |u| u.unit_type == UnitType::ZergDrone
-        );
+        // drones already on their way to build something are left alone
+        let drones = free_drones(&input.frame);
         let hatcheries = input.frame.state.filter_units(
             |u| u.unit_type == UnitType::ZergHatchery
         );
@@ -97,22 +166,37 @@ impl RandomDroneMorpherLobe {
             return None
         }
 
-        let h = random::<usize>() % hatcheries.len();
-        let mut location = Point2::new(
-            hatcheries[h].pos.x, hatcheries[h].pos.y
-        );
+        // extractors can only be built on top of a geyser
+        let target = if self.unit_type == UnitType::ZergExtractor {
+            match free_geyser(&input.frame, &hatcheries) {
+                Some(geyser) => ActionTarget::UnitTag(geyser.tag),
+                None => return None
+            }
+        }
+        else {
+            let h = self.rng.gen_range(0, hatcheries.len());
+            let location = find_placement(
+                &input.frame,
+                input.grid.as_ref(),
+                self.unit_type,
+                Point2::new(hatcheries[h].pos.x, hatcheries[h].pos.y),
+                self.placement,
+                &mut self.rng
+            );
 
-        location += 10.0 * Vector2::new(
-            random::<f32>() - 0.5, random::<f32>() - 0.5
-        );
+            match location {
+                Some(location) => ActionTarget::Location(location),
+                None => return None
+            }
+        };
 
         Some(
             sc2::Command::Action {
                 units: vec![
-                    Rc::clone(&drones[random::<usize>() % drones.len()])
+                    Rc::clone(&drones[self.rng.gen_range(0, drones.len())])
                 ],
                 ability: data.ability,
-                target: Some(ActionTarget::Location(location))
+                target: Some(target)
             }
         )
     }
@@ -123,10 +207,13 @@ create_lobe_data! {
 
     req frame: Rc<sc2::FrameData>,
     opt budget: LobeBudget,
+    opt grid: Rc<OccupancyGrid>,
 
     out commands: Vec<sc2::Command>,
 
     fbk out spent: LobeBudget,
+    fbk out request: LobeBudgetRequest,
+    fbk out reservations: Vec<Reservation>,
 }
 
 pub use self::random_drone_morpher::{
@@ -148,10 +235,13 @@ constrain_lobe! {
 
     req frame: FrameData,
     opt budget: Budget,
+    opt grid: OccupancyGrid,
 
     out commands: Commands,
 
     fbk out spent: Budget,
+    fbk out request: BudgetRequest,
+    fbk out reservations: Reservations,
 }
 
 impl cortical::Lobe for RandomDroneMorpherLobe {
@@ -175,6 +265,7 @@ impl cortical::Lobe for RandomDroneMorpherLobe {
 
     fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
         self.spent = Budget::default();
+        self.reservations.clear();
 
         if self.data.is_none() {
             if let Some(ref data) = input.frame.data.unit_type_data.get(
@@ -186,17 +277,44 @@ impl cortical::Lobe for RandomDroneMorpherLobe {
 
         let mut commands = vec![ ];
 
-        if let Some(ref data) = self.data {
+        if let Some(data) = self.data.clone() {
+            let cost = Budget::cost_of(&data);
+
             if let Some(command) = self.morph_drone(&input, &data) {
+                if let sc2::Command::Action {
+                    ref units, ability, ..
+                } = command {
+                    for u in units {
+                        self.reservations.push(
+                            Reservation {
+                                lobe: self.hdl.unwrap(),
+                                tag: u.tag,
+                                ability: ability,
+                                cost: cost,
+                            }
+                        );
+                    }
+                }
+
                 commands.push(command);
 
-                self.spent = Budget {
-                    minerals: data.mineral_cost,
-                    vespene: data.vespene_cost,
-                    food: data.food_required.ceil() as u32,
+                self.spent = cost;
+            }
 
-                    ..Budget::default()
-                }
+            // ask for the next morph unless there is nothing left to build.
+            // without a drone or a hatchery there is nothing to spend on yet,
+            // so give the budget back until there is
+            if self.is_satisfied(&input.frame) {
+                self.request = Some(Budget::default());
+                self.status = SpenderStatus::Done;
+            }
+            else if !self.has_drones_and_hatcheries(&input.frame) {
+                self.request = Some(Budget::default());
+                self.status = SpenderStatus::Idle;
+            }
+            else {
+                self.request = Some(cost);
+                self.status = SpenderStatus::Active;
             }
         }
         else {
@@ -226,7 +344,14 @@ impl cortical::Lobe for RandomDroneMorpherLobe {
                 spent: LobeBudget {
                     lobe: self.hdl.unwrap(),
                     budget: self.spent,
-                }
+                },
+                request: LobeBudgetRequest {
+                    lobe: self.hdl.unwrap(),
+                    cost: self.request,
+                    urgency: self.urgency,
+                    status: self.status,
+                },
+                reservations: self.reservations.clone(),
             }
         )
     }
@@ -1,7 +1,12 @@
 
+use std::io;
+
+use bincode;
 use cortical;
 use sc2;
+use serde_json;
 use tantrum;
+use toml;
 
 error_chain! {
     errors {
@@ -9,6 +14,45 @@ error_chain! {
             description("an error occurred while joining a thread"),
             display("an error occurred while joining a thread")
         }
+        UnknownLobe(name: String) {
+            description("unknown lobe"),
+            display("unknown lobe {:?}", name)
+        }
+        InvalidParameter(name: String, msg: String) {
+            description("invalid lobe parameter"),
+            display("invalid lobe parameter {:?}: {}", name, msg)
+        }
+        UnknownUnitType(name: String) {
+            description("unknown unit type"),
+            display("unknown unit type {:?}", name)
+        }
+        UnknownConstraint(name: String) {
+            description("unknown constraint"),
+            display("unknown constraint {:?}", name)
+        }
+        ConstraintMismatch(from: String, to: String, constraint: String) {
+            description("constraint is not supported by both lobes"),
+            display(
+                "constraint {} is not supported from {:?} to {:?}",
+                constraint,
+                from,
+                to
+            )
+        }
+        InvalidTopology(msg: String) {
+            description("invalid cortex topology"),
+            display("invalid cortex topology: {}", msg)
+        }
+        InvalidBuildOrder(item: String, msg: String) {
+            description("invalid build order"),
+            display("invalid build order item {:?}: {}", item, msg)
+        }
+    }
+    foreign_links {
+        Bincode(bincode::Error);
+        Io(io::Error);
+        Json(serde_json::Error);
+        Toml(toml::de::Error);
     }
     links {
         Sc2(sc2::Error, sc2::ErrorKind);
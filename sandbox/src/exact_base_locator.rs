@@ -0,0 +1,244 @@
+use std::rc::Rc;
+
+use cortical;
+use sc2;
+use sc2::data::{ ImageData, Point2 };
+use tantrum::{ ResourceCluster };
+
+use super::{
+    BaseLocationCache,
+    BaseLocationChanges,
+    KeliConstraint,
+    KeliData,
+
+    image_pixel,
+    is_geyser,
+};
+
+/// the width and height of a town hall in tiles
+const TOWN_HALL_FOOTPRINT: i32 = 5;
+/// the number of tiles the game keeps clear between a town hall and resources
+const RESOURCE_GAP: f32 = 3.0;
+/// how far beyond the resources of a cluster to look for a location
+const SEARCH_MARGIN: i32 = 10;
+
+/// the kind of a resource, which decides the size of its footprint
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResourceKind {
+    /// a 2x1 mineral field
+    Mineral,
+    /// a 3x3 vespene geyser
+    Geyser,
+}
+
+/// a resource that a base location has to be placed around
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BaseResource {
+    /// the center of the resource
+    pub pos:                Point2,
+    /// the kind of resource
+    pub kind:               ResourceKind,
+}
+
+impl BaseResource {
+    /// half of the width and height of the resource's footprint
+    fn half_extents(&self) -> (f32, f32) {
+        match self.kind {
+            ResourceKind::Mineral => (1.0, 0.5),
+            ResourceKind::Geyser => (1.5, 1.5),
+        }
+    }
+
+    /// true if a town hall centered at the location is too close
+    fn blocks(&self, location: Point2) -> bool {
+        let half = TOWN_HALL_FOOTPRINT as f32 / 2.0;
+        let (w, h) = self.half_extents();
+
+        (location.x - self.pos.x).abs() < half + w + RESOURCE_GAP
+            && (location.y - self.pos.y).abs() < half + h + RESOURCE_GAP
+    }
+}
+
+/// find the best town hall location for a cluster of resources
+///
+/// every location where the town hall's footprint lies on placeable tiles
+/// and keeps the gap the game enforces to every resource is considered. the
+/// one with the smallest total distance to the resources wins. returns
+/// `None` if there is no such location near the cluster.
+pub fn solve_base_location(placement: &ImageData, resources: &[BaseResource])
+    -> Option<Point2>
+{
+    if resources.is_empty() {
+        return None
+    }
+
+    // search the bounding box of the cluster, grown by the margin
+    let (x0, y0, x1, y1) = resources.iter().fold(
+        (::std::i32::MAX, ::std::i32::MAX, ::std::i32::MIN, ::std::i32::MIN),
+        |(x0, y0, x1, y1), r| (
+            x0.min(r.pos.x as i32 - SEARCH_MARGIN),
+            y0.min(r.pos.y as i32 - SEARCH_MARGIN),
+            x1.max(r.pos.x as i32 + SEARCH_MARGIN),
+            y1.max(r.pos.y as i32 + SEARCH_MARGIN)
+        )
+    );
+
+    let mut best: Option<(Point2, f32)> = None;
+
+    for y in y0..(y1 + 1) {
+        for x in x0..(x1 + 1) {
+            // town halls have an odd footprint, so they sit on tile centers
+            let location = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+            if !is_placeable(placement, location)
+                || resources.iter().any(|r| r.blocks(location))
+            {
+                continue
+            }
+
+            let total = resources.iter().map(
+                |r| {
+                    let dx = location.x - r.pos.x;
+                    let dy = location.y - r.pos.y;
+
+                    (dx * dx + dy * dy).sqrt()
+                }
+            ).sum::<f32>();
+
+            match best {
+                Some((_, best_total)) if best_total <= total => (),
+                _ => best = Some((location, total))
+            }
+        }
+    }
+
+    best.map(|(location, _)| location)
+}
+
+/// true if every tile of a town hall centered at the location is placeable
+fn is_placeable(placement: &ImageData, location: Point2) -> bool {
+    let x0 = location.x.floor() as i32 - TOWN_HALL_FOOTPRINT / 2;
+    let y0 = location.y.floor() as i32 - TOWN_HALL_FOOTPRINT / 2;
+
+    for y in y0..(y0 + TOWN_HALL_FOOTPRINT) {
+        for x in x0..(x0 + TOWN_HALL_FOOTPRINT) {
+            if x < 0 || y < 0 || x >= placement.width || y >= placement.height
+            {
+                return false
+            }
+
+            if !image_pixel(placement, (x + y * placement.width) as usize) {
+                return false
+            }
+        }
+    }
+
+    true
+}
+
+/// the resources of a cluster as the solver sees them
+///
+/// resources are told apart by type, since fogged ones report no contents.
+fn cluster_resources(cluster: &ResourceCluster) -> Vec<BaseResource> {
+    cluster.resources.iter().map(
+        |r| BaseResource {
+            pos: Point2::new(r.pos.x, r.pos.y),
+            kind: if is_geyser(r.unit_type) {
+                ResourceKind::Geyser
+            }
+            else {
+                ResourceKind::Mineral
+            }
+        }
+    ).collect()
+}
+
+/// finds base locations by checking every town hall footprint around a
+/// cluster against the placement grid
+///
+/// a drop-in replacement for `NudgeBaseLocatorLobe`. clusters without a valid
+/// location are left out.
+pub struct ExactBaseLocatorLobe {
+    cache:                  BaseLocationCache,
+}
+
+impl ExactBaseLocatorLobe {
+    /// locate bases with the exact solver
+    pub fn new() -> Self {
+        Self {
+            cache: BaseLocationCache::new(),
+        }
+    }
+}
+
+create_lobe_data! {
+    module: exact_base_locator,
+
+    req frame: Rc<sc2::FrameData>,
+    req clusters: Rc<Vec<ResourceCluster>>,
+
+    out locations: Rc<Vec<Point2>>,
+    out changes: BaseLocationChanges,
+}
+
+pub use self::exact_base_locator::{
+    Input as ExactBaseLocatorInput,
+    Output as ExactBaseLocatorOutput,
+    FeedbackInput as ExactBaseLocatorFeedbackInput,
+    FeedbackOutput as ExactBaseLocatorFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: ExactBaseLocatorLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: ExactBaseLocatorInput,
+    output: ExactBaseLocatorOutput,
+    feedback_input: ExactBaseLocatorFeedbackInput,
+    feedback_output: ExactBaseLocatorFeedbackOutput,
+
+    req frame: FrameData,
+    req clusters: Resources,
+
+    out locations: PotentialBaseLocations,
+    out changes: BaseLocationChanges,
+}
+
+impl cortical::Lobe for ExactBaseLocatorLobe {
+    type Input = ExactBaseLocatorInput;
+    type Output = ExactBaseLocatorOutput;
+    type FeedbackInput = ExactBaseLocatorFeedbackInput;
+    type FeedbackOutput = ExactBaseLocatorFeedbackOutput;
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        let placement = &input.frame.data.terrain_info.placement_grid;
+
+        // only clusters that changed since the last update are solved again
+        self.cache.update(
+            &input.clusters,
+            |cluster| solve_base_location(
+                placement, &cluster_resources(cluster)
+            )
+        );
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            ExactBaseLocatorOutput {
+                locations: self.cache.locations(),
+                changes: self.cache.changes().clone(),
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(ExactBaseLocatorFeedbackOutput { })
+    }
+}
@@ -0,0 +1,629 @@
+use std::collections::{ VecDeque };
+use std::rc::Rc;
+
+use cortical;
+use sc2;
+use sc2::data::{
+    ActionTarget, Alliance, ImageData, Point2, Tag, Unit, UnitType, UnitTypeData
+};
+
+use super::{
+    Budget,
+    LobeBudget,
+    LobeBudgetRequest,
+    MorphSource,
+    Reservation,
+    SpenderStatus,
+    KeliConstraint,
+    KeliData,
+
+    STEPS_PER_SECOND,
+
+    image_pixel,
+};
+
+/// how close a town hall has to be to a location to take it
+const TAKEN_RADIUS: f32 = 8.0;
+/// how far around a location to look for a reachable tile
+const REACH_RADIUS: i32 = 3;
+/// how long a drone has to start building before its claim is released
+const CLAIM_TIMEOUT_SECS: f32 = 60.0;
+/// how far a location can move between steps and still be the same site
+const SITE_TOLERANCE: f32 = 2.0;
+
+/// who holds a potential base location
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BaseStatus {
+    /// nobody has a town hall there
+    Free,
+    /// a drone has been sent to build a hatchery there
+    Claimed,
+    /// we have a town hall there
+    Ours,
+    /// the enemy has a town hall there
+    Enemy,
+}
+
+/// true for the main building of every race
+pub fn is_town_hall(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::ZergHatchery
+        | UnitType::ZergLair
+        | UnitType::ZergHive
+        | UnitType::TerranCommandCenter
+        | UnitType::TerranOrbitalCommand
+        | UnitType::TerranPlanetaryFortress
+        | UnitType::ProtossNexus => true,
+        _ => false
+    }
+}
+
+/// ground distances in tiles from a starting point over the pathing grid
+pub struct GroundDistances {
+    width:                  i32,
+    height:                 i32,
+
+    steps:                  Vec<Option<u32>>,
+}
+
+impl GroundDistances {
+    /// breadth first search from the given point
+    ///
+    /// the tiles around the start are always walkable, since the pathing grid
+    /// marks the town hall standing there as blocked.
+    pub fn search(pathing: &ImageData, start: Point2) -> Self {
+        let width = pathing.width;
+        let height = pathing.height;
+
+        let mut distances = Self {
+            width: width,
+            height: height,
+
+            steps: vec![ None; (width * height) as usize ],
+        };
+
+        let mut queue = VecDeque::new();
+
+        for (x, y) in distances.around(start) {
+            if let Some(i) = distances.index(x, y) {
+                distances.steps[i] = Some(0);
+                queue.push_back((x, y));
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let steps = distances.steps[distances.index(x, y).unwrap()]
+                .unwrap()
+            ;
+
+            for &(dx, dy) in &[ (1, 0), (-1, 0), (0, 1), (0, -1) ] {
+                let i = match distances.index(x + dx, y + dy) {
+                    Some(i) => i,
+                    None => continue
+                };
+
+                if distances.steps[i].is_none() && image_pixel(pathing, i) {
+                    distances.steps[i] = Some(steps + 1);
+                    queue.push_back((x + dx, y + dy));
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// the ground distance to a point, or `None` if it cannot be reached
+    ///
+    /// the closest reachable tile around the point is used, since base
+    /// locations and structures sit on tiles the pathing grid may block.
+    pub fn distance_to(&self, point: Point2) -> Option<u32> {
+        self.around(point).into_iter()
+            .filter_map(|(x, y)| self.index(x, y))
+            .filter_map(|i| self.steps[i])
+            .min()
+    }
+
+    fn around(&self, point: Point2) -> Vec<(i32, i32)> {
+        let cx = point.x.floor() as i32;
+        let cy = point.y.floor() as i32;
+
+        let mut tiles = vec![ ];
+
+        for y in (cy - REACH_RADIUS)..(cy + REACH_RADIUS + 1) {
+            for x in (cx - REACH_RADIUS)..(cx + REACH_RADIUS + 1) {
+                tiles.push((x, y));
+            }
+        }
+
+        tiles
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        }
+        else {
+            Some((x + y * self.width) as usize)
+        }
+    }
+}
+
+/// ranks potential base locations by ground distance from the main hatchery
+/// and keeps track of who holds each of them
+pub struct ExpansionSites {
+    main:                   Option<Point2>,
+    distances:              Option<GroundDistances>,
+    statuses:               Vec<(Point2, BaseStatus)>,
+}
+
+impl ExpansionSites {
+    pub fn new() -> Self {
+        Self { main: None, distances: None, statuses: vec![ ] }
+    }
+
+    /// the status of every potential base location from the last update
+    pub fn statuses(&self) -> &Vec<(Point2, BaseStatus)> {
+        &self.statuses
+    }
+
+    /// find out who holds each location, keeping the claimed location marked
+    /// until a town hall shows up there
+    pub fn update(
+        &mut self,
+        frame: &sc2::FrameData,
+        locations: &[Point2],
+        claimed: Option<Point2>
+    ) {
+        self.find_main(frame);
+
+        let town_halls = frame.state.filter_units(
+            |u| is_town_hall(u.unit_type)
+        );
+
+        self.statuses = locations.iter().map(
+            |&location| {
+                let holder = town_halls.iter().find(
+                    |u| distance_to_unit_squared(&location, u)
+                        < TAKEN_RADIUS * TAKEN_RADIUS
+                );
+                let claimed = match claimed {
+                    Some(claimed) => is_same_site(claimed, location),
+                    None => false
+                };
+
+                let status = match holder {
+                    Some(u) if u.alliance == Alliance::Domestic => {
+                        BaseStatus::Ours
+                    },
+                    Some(_) => BaseStatus::Enemy,
+                    None if claimed => BaseStatus::Claimed,
+                    None => BaseStatus::Free
+                };
+
+                (location, status)
+            }
+        ).collect();
+    }
+
+    /// true if a town hall stands at the location
+    pub fn is_taken(&self, location: Point2) -> bool {
+        self.statuses.iter().any(
+            |&(l, status)| is_same_site(l, location)
+                && (status == BaseStatus::Ours || status == BaseStatus::Enemy)
+        )
+    }
+
+    /// the closest free location that can be reached over the ground
+    pub fn closest_free(&self) -> Option<Point2> {
+        let distances = match self.distances {
+            Some(ref distances) => distances,
+            None => return None
+        };
+
+        self.statuses.iter()
+            .filter(|&&(_, status)| status == BaseStatus::Free)
+            .filter_map(
+                |&(location, _)| distances.distance_to(location).map(
+                    |d| (location, d)
+                )
+            )
+            .min_by_key(|&(_, d)| d)
+            .map(|(location, _)| location)
+    }
+
+    /// remember where the first hatchery stood and how far everything is
+    fn find_main(&mut self, frame: &sc2::FrameData) {
+        if self.main.is_some() {
+            return
+        }
+
+        let hatcheries = frame.state.filter_units(
+            |u| u.unit_type == UnitType::ZergHatchery
+                && u.alliance == Alliance::Domestic
+        );
+
+        if let Some(main) = hatcheries.first() {
+            let main = Point2::new(main.pos.x, main.pos.y);
+
+            self.main = Some(main);
+            self.distances = Some(
+                GroundDistances::search(
+                    &frame.data.terrain_info.pathing_grid, main
+                )
+            );
+        }
+    }
+}
+
+/// true if two base locations are the same site, allowing for locators
+/// nudging them around a little between steps
+pub fn is_same_site(a: Point2, b: Point2) -> bool {
+    let dx = a.x - b.x;
+    let dy = a.y - b.y;
+
+    dx * dx + dy * dy < SITE_TOLERANCE * SITE_TOLERANCE
+}
+
+/// true if the unit has been ordered to morph into a structure
+///
+/// these drones are on their way to a build site and the spender that sent
+/// them has reserved them, so nobody else should give them orders.
+pub fn has_build_order(frame: &sc2::FrameData, unit: &Unit) -> bool {
+    unit.orders.iter().any(
+        |o| frame.data.unit_type_data.values().any(
+            |data| data.ability == o.ability
+                && MorphSource::of(data.unit_type) == MorphSource::Drone
+        )
+    )
+}
+
+/// our drones that are free to be given orders
+pub fn free_drones(frame: &sc2::FrameData) -> Vec<Rc<Unit>> {
+    frame.state.filter_units(
+        |u| u.unit_type == UnitType::ZergDrone
+            && u.alliance == Alliance::Domestic
+            && !has_build_order(frame, u)
+    )
+}
+
+/// the free drone closest to a location
+pub fn closest_drone(frame: &sc2::FrameData, location: Point2)
+    -> Option<Rc<Unit>>
+{
+    free_drones(frame).into_iter().fold(
+        None,
+        |best: Option<Rc<Unit>>, d| match best {
+            Some(best) => if distance_to_unit_squared(&location, &d)
+                < distance_to_unit_squared(&location, &best)
+            {
+                Some(d)
+            }
+            else {
+                Some(best)
+            },
+            None => Some(d)
+        }
+    )
+}
+
+/// a drone on its way to build a hatchery
+struct Claim {
+    location:               Point2,
+    drone:                  Tag,
+    issued:                 u32,
+}
+
+/// takes the closest free base location whenever the budget allows
+///
+/// potential base locations are ranked by ground distance from the main
+/// hatchery with `ExpansionSites`. the closest drone without a build order is
+/// sent to morph a hatchery at the closest one nobody holds, and the location
+/// stays claimed until the hatchery appears, the drone dies or it takes too
+/// long to start building.
+pub struct ExpansionLobe {
+    hdl:                    Option<cortical::NodeHdl>,
+
+    data:                   Option<Rc<UnitTypeData>>,
+
+    sites:                  ExpansionSites,
+    claim:                  Option<Claim>,
+
+    spent:                  Budget,
+    reservations:           Vec<Reservation>,
+    request:                Option<Budget>,
+    urgency:                u32,
+    status:                 SpenderStatus,
+
+    commands:               Vec<sc2::Command>,
+}
+
+impl ExpansionLobe {
+    /// expand to the potential base locations
+    pub fn new() -> Self {
+        Self {
+            hdl: None,
+
+            data: None,
+
+            sites: ExpansionSites::new(),
+            claim: None,
+
+            spent: Budget::default(),
+            reservations: vec![ ],
+            request: None,
+            urgency: 1,
+            status: SpenderStatus::Active,
+
+            commands: vec![ ],
+        }
+    }
+
+    /// set how urgently the budget for the next hatchery is requested
+    pub fn with_urgency(mut self, urgency: u32) -> Self {
+        self.urgency = urgency;
+        self
+    }
+
+    /// the status of every potential base location from the last update
+    pub fn statuses(&self) -> &Vec<(Point2, BaseStatus)> {
+        self.sites.statuses()
+    }
+
+    /// release the claim once it is built or has failed
+    fn check_claim(&mut self, frame: &sc2::FrameData) {
+        let timeout = (CLAIM_TIMEOUT_SECS * STEPS_PER_SECOND) as u32;
+        let step = frame.state.current_step;
+        let ability = match self.data {
+            Some(ref data) => data.ability,
+            None => return
+        };
+
+        let release = match self.claim {
+            Some(ref claim) => {
+                let drone = frame.state.units.iter().find(
+                    |u| u.tag == claim.drone
+                );
+
+                let building = match drone {
+                    Some(drone) => drone.unit_type == UnitType::ZergDrone
+                        && drone.orders.iter().any(|o| o.ability == ability),
+                    None => false
+                };
+
+                // give the order a few steps to show up on the drone
+                let pending = step < claim.issued + STEPS_PER_SECOND as u32;
+
+                (!building && !pending) || claim.issued + timeout < step
+            },
+            None => false
+        };
+
+        if release {
+            self.claim = None;
+        }
+    }
+
+    fn update_statuses(
+        &mut self, frame: &sc2::FrameData, locations: &[Point2]
+    ) {
+        let claimed = self.claim.as_ref().map(|claim| claim.location);
+
+        self.sites.update(frame, locations, claimed);
+
+        // the hatchery has appeared, so the claim is done
+        let done = match claimed {
+            Some(claimed) => self.sites.is_taken(claimed),
+            None => false
+        };
+
+        if done {
+            self.claim = None;
+        }
+    }
+
+    fn expand(
+        &mut self,
+        input: &ExpansionInput,
+        location: Point2,
+        data: &UnitTypeData
+    )
+        -> Option<sc2::Command>
+    {
+        let budget = match input.budget {
+            Some(ref budget) => budget.budget,
+            None => return None
+        };
+
+        if !Budget::cost_of(data).fits_within(&budget) {
+            return None
+        }
+
+        let drone = match closest_drone(&input.frame, location) {
+            Some(drone) => drone,
+            None => return None
+        };
+
+        self.claim = Some(
+            Claim {
+                location: location,
+                drone: drone.tag,
+                issued: input.frame.state.current_step,
+            }
+        );
+
+        Some(
+            sc2::Command::Action {
+                units: vec![ drone ],
+                ability: data.ability,
+                target: Some(ActionTarget::Location(location))
+            }
+        )
+    }
+}
+
+fn distance_to_unit_squared(a: &Point2, b: &Unit) -> f32 {
+    let dx = a.x - b.pos.x;
+    let dy = a.y - b.pos.y;
+
+    dx * dx + dy * dy
+}
+
+create_lobe_data! {
+    module: expansion,
+
+    req frame: Rc<sc2::FrameData>,
+    req locations: Rc<Vec<Point2>>,
+    opt budget: LobeBudget,
+
+    out commands: Vec<sc2::Command>,
+
+    fbk out spent: LobeBudget,
+    fbk out request: LobeBudgetRequest,
+    fbk out reservations: Vec<Reservation>,
+}
+
+pub use self::expansion::{
+    Input as ExpansionInput,
+    Output as ExpansionOutput,
+    FeedbackInput as ExpansionFeedbackInput,
+    FeedbackOutput as ExpansionFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: ExpansionLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: ExpansionInput,
+    output: ExpansionOutput,
+    feedback_input: ExpansionFeedbackInput,
+    feedback_output: ExpansionFeedbackOutput,
+
+    req frame: FrameData,
+    req locations: PotentialBaseLocations,
+    opt budget: Budget,
+
+    out commands: Commands,
+
+    fbk out spent: Budget,
+    fbk out request: BudgetRequest,
+    fbk out reservations: Reservations,
+}
+
+impl cortical::Lobe for ExpansionLobe {
+    type Input = ExpansionInput;
+    type Output = ExpansionOutput;
+    type FeedbackInput = ExpansionFeedbackInput;
+    type FeedbackOutput = ExpansionFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.hdl = Some(hdl);
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.spent = Budget::default();
+        self.reservations.clear();
+
+        if self.data.is_none() {
+            if let Some(ref data) = input.frame.data.unit_type_data.get(
+                &UnitType::ZergHatchery
+            ) {
+                self.data = Some(Rc::clone(data));
+            }
+        }
+
+        let data = match self.data.clone() {
+            Some(data) => data,
+            None => bail!(
+                "unable to get UnitTypeData for {:?}", UnitType::ZergHatchery
+            )
+        };
+
+        let cost = Budget::cost_of(&data);
+        let mut commands = vec![ ];
+
+        self.check_claim(&input.frame);
+        self.update_statuses(&input.frame, &input.locations);
+
+        let location = match self.claim {
+            Some(_) => None,
+            None => self.sites.closest_free()
+        };
+
+        if let Some(location) = location {
+            if let Some(command) = self.expand(&input, location, &data) {
+                if let sc2::Command::Action { ref units, ability, .. } = command
+                {
+                    for u in units {
+                        self.reservations.push(
+                            Reservation {
+                                lobe: self.hdl.unwrap(),
+                                tag: u.tag,
+                                ability: ability,
+                                cost: cost,
+                            }
+                        );
+                    }
+                }
+
+                commands.push(command);
+
+                self.spent = cost;
+            }
+        }
+
+        // while a drone is on its way or there is nowhere left to go, there
+        // is nothing to spend on
+        if self.claim.is_none() && location.is_some() {
+            self.request = Some(cost);
+            self.status = SpenderStatus::Active;
+        }
+        else {
+            self.request = Some(Budget::default());
+            self.status = SpenderStatus::Idle;
+        }
+
+        self.commands = commands;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            ExpansionOutput {
+                commands: self.commands.clone()
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            ExpansionFeedbackOutput {
+                spent: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.spent,
+                },
+                request: LobeBudgetRequest {
+                    lobe: self.hdl.unwrap(),
+                    cost: self.request,
+                    urgency: self.urgency,
+                    status: self.status,
+                },
+                reservations: self.reservations.clone(),
+            }
+        )
+    }
+}
@@ -0,0 +1,289 @@
+use std::rc::Rc;
+
+use cortical;
+use rand::{ Rng };
+use sc2;
+use sc2::data::{ Alliance, Unit, UnitType, UnitTypeData };
+
+use super::{
+    Budget,
+    LobeBudget,
+    LobeBudgetRequest,
+    LobeRng,
+    MorphSource,
+    SpenderStatus,
+    KeliConstraint,
+    KeliData,
+
+    seeded_rng,
+    unseeded_rng,
+};
+
+/// morphs larva into a unit type whenever the budget allows
+///
+/// as many larva as the budget covers are morphed every step, starting with
+/// the larva closest to a hatchery. the game charges for larva morphs as soon
+/// as they are ordered, so nothing is reserved.
+pub struct LarvaMorpherLobe {
+    hdl:            Option<cortical::NodeHdl>,
+
+    unit_type:      UnitType,
+    data:           Option<Rc<UnitTypeData>>,
+
+    spent:          Budget,
+    request:        Option<Budget>,
+    urgency:        u32,
+    status:         SpenderStatus,
+
+    rng:            LobeRng,
+
+    commands:       Vec<sc2::Command>
+}
+
+impl LarvaMorpherLobe {
+    /// morph larva into the given unit type
+    pub fn new(unit_type: UnitType) -> Self {
+        Self {
+            hdl: None,
+
+            unit_type: unit_type,
+            data: None,
+
+            spent: Budget::default(),
+            request: None,
+            urgency: 1,
+            status: SpenderStatus::Active,
+
+            rng: unseeded_rng(),
+
+            commands: vec![ ]
+        }
+    }
+
+    /// use a seeded generator so that the same frames produce the same commands
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seeded_rng(seed);
+        self
+    }
+
+    /// set how urgently the budget for this lobe's morphs is requested
+    pub fn with_urgency(mut self, urgency: u32) -> Self {
+        self.urgency = urgency;
+        self
+    }
+
+    /// larva that belong to the bot, closest to a hatchery, lair or hive
+    /// first
+    fn select_larva(&mut self, frame: &sc2::FrameData) -> Vec<Rc<Unit>> {
+        let hatcheries = frame.state.filter_units(
+            |u| is_larva_producer(u.unit_type)
+                && u.alliance == Alliance::Domestic
+        );
+        let mut larva = frame.state.filter_units(
+            |u| u.unit_type == UnitType::ZergLarva
+                && u.alliance == Alliance::Domestic
+        );
+
+        // shuffle first so larva at the same distance are picked at random
+        self.rng.shuffle(&mut larva);
+
+        let distance = |l: &Rc<Unit>| hatcheries.iter().map(
+            |h| {
+                let dx = h.pos.x - l.pos.x;
+                let dy = h.pos.y - l.pos.y;
+
+                dx * dx + dy * dy
+            }
+        ).fold(::std::f32::MAX, f32::min);
+
+        larva.sort_by(
+            |a, b| distance(a).partial_cmp(&distance(b)).unwrap()
+        );
+
+        larva
+    }
+
+    fn morph_larva(
+        &self,
+        input: &LarvaMorpherInput,
+        larva: &Vec<Rc<Unit>>,
+        cost: Budget
+    )
+        -> Option<sc2::Command>
+    {
+        let budget = match input.budget {
+            Some(ref budget) => budget.budget,
+            None => return None
+        };
+
+        let mut remaining = budget;
+        let mut units = vec![ ];
+
+        for l in larva {
+            match remaining.checked_sub(cost) {
+                Some(left) => {
+                    remaining = left;
+                    units.push(Rc::clone(l));
+                },
+                None => break
+            }
+        }
+
+        if units.is_empty() {
+            return None
+        }
+
+        Some(
+            sc2::Command::Action {
+                units: units,
+                ability: self.data.as_ref().unwrap().ability,
+                target: None
+            }
+        )
+    }
+}
+
+/// true for the structures that spawn larva
+fn is_larva_producer(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::ZergHatchery
+        | UnitType::ZergLair
+        | UnitType::ZergHive => true,
+        _ => false
+    }
+}
+
+create_lobe_data! {
+    module: larva_morpher,
+
+    req frame: Rc<sc2::FrameData>,
+    opt budget: LobeBudget,
+
+    out commands: Vec<sc2::Command>,
+
+    fbk out spent: LobeBudget,
+    fbk out request: LobeBudgetRequest,
+}
+
+pub use self::larva_morpher::{
+    Input as LarvaMorpherInput,
+    Output as LarvaMorpherOutput,
+    FeedbackInput as LarvaMorpherFeedbackInput,
+    FeedbackOutput as LarvaMorpherFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: LarvaMorpherLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: LarvaMorpherInput,
+    output: LarvaMorpherOutput,
+    feedback_input: LarvaMorpherFeedbackInput,
+    feedback_output: LarvaMorpherFeedbackOutput,
+
+    req frame: FrameData,
+    opt budget: Budget,
+
+    out commands: Commands,
+
+    fbk out spent: Budget,
+    fbk out request: BudgetRequest,
+}
+
+impl cortical::Lobe for LarvaMorpherLobe {
+    type Input = LarvaMorpherInput;
+    type Output = LarvaMorpherOutput;
+    type FeedbackInput = LarvaMorpherFeedbackInput;
+    type FeedbackOutput = LarvaMorpherFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        if let MorphSource::Larva { .. } = MorphSource::of(self.unit_type) {
+            self.hdl = Some(hdl);
+
+            Ok(())
+        }
+        else {
+            bail!("{:?} is not morphed from larva", self.unit_type)
+        }
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.spent = Budget::default();
+
+        if self.data.is_none() {
+            if let Some(ref data) = input.frame.data.unit_type_data.get(
+                &self.unit_type
+            ) {
+                self.data = Some(Rc::clone(data));
+            }
+        }
+
+        let mut commands = vec![ ];
+
+        if let Some(data) = self.data.clone() {
+            let cost = Budget::cost_of(&data);
+            let larva = self.select_larva(&input.frame);
+
+            if let Some(command) = self.morph_larva(&input, &larva, cost) {
+                if let sc2::Command::Action { ref units, .. } = command {
+                    self.spent = cost * units.len() as u32;
+                }
+
+                commands.push(command);
+            }
+
+            // without larva there is nothing to spend on until more spawn
+            self.request = Some(cost);
+            self.status = if larva.is_empty() {
+                SpenderStatus::Idle
+            }
+            else {
+                SpenderStatus::Active
+            };
+        }
+        else {
+            bail!("unable to get UnitTypeData for {:?}", self.unit_type);
+        }
+
+        self.commands = commands;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            LarvaMorpherOutput {
+                commands: self.commands.clone()
+            }
+        )
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            LarvaMorpherFeedbackOutput {
+                spent: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.spent,
+                },
+                request: LobeBudgetRequest {
+                    lobe: self.hdl.unwrap(),
+                    cost: self.request,
+                    urgency: self.urgency,
+                    status: self.status,
+                },
+            }
+        )
+    }
+}
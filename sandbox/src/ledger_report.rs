@@ -0,0 +1,250 @@
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::path::Path;
+use std::rc::Rc;
+
+use cortical;
+use cortical::{ ResultExt };
+use sc2;
+
+use errors::{ Result };
+use super::{
+    Budget,
+    LedgerReport,
+    LobeNames,
+    KeliConstraint,
+    KeliData,
+};
+
+const CSV_HEADER: &'static str = "step,ledger,lobe,\
+    allotted_minerals,allotted_vespene,allotted_food,allotted_larva,\
+    spent_minerals,spent_vespene,spent_food,spent_larva,\
+    idle_minerals,idle_vespene,idle_food,idle_larva,\
+    starved_steps,\
+    debt_minerals,debt_vespene,debt_food,debt_larva\n";
+
+/// collects the reports of ledgers and summarizes them
+///
+/// the summary shows what each output of every ledger was allotted, what it
+/// spent, how much budget it left idle and what it still owes from
+/// overspending, making starving, hoarding or overspending spenders easy to
+/// spot. it can be printed when the game ends, and the history of every step
+/// can also be written to a CSV file.
+///
+/// ledgers only fill in their reports once their feedback is in, so each
+/// report arrives with the next frame and the last step of a game is never
+/// reported.
+pub struct LedgerReportLobe {
+    names:                  LobeNames,
+
+    reports:                Vec<LedgerReport>,
+    last_step:              Option<u32>,
+
+    print_summary:          bool,
+    writer:                 Option<BufWriter<File>>,
+}
+
+impl LedgerReportLobe {
+    /// report on the ledgers connected to the lobe
+    pub fn new(names: LobeNames) -> Self {
+        Self {
+            names: names,
+
+            reports: vec![ ],
+            last_step: None,
+
+            print_summary: false,
+            writer: None,
+        }
+    }
+
+    /// print the summary when the game ends
+    pub fn with_summary(mut self) -> Self {
+        self.print_summary = true;
+        self
+    }
+
+    /// also write the history of every step to a new CSV file
+    pub fn with_csv<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(CSV_HEADER.as_bytes())?;
+
+        self.writer = Some(writer);
+
+        Ok(self)
+    }
+
+    /// the latest report of every ledger
+    pub fn reports(&self) -> &Vec<LedgerReport> {
+        &self.reports
+    }
+
+    /// write the reports recorded for the given game step
+    fn write_reports(&mut self, step: u32) -> Result<()> {
+        let writer = match self.writer {
+            Some(ref mut writer) => writer,
+            None => return Ok(())
+        };
+
+        for report in &self.reports {
+            let ledger = lobe_name(&self.names, report.ledger);
+
+            for entry in &report.entries {
+                write!(
+                    writer,
+                    "{},{},{},{},{},{},{},{}\n",
+                    step,
+                    ledger,
+                    lobe_name(&self.names, entry.lobe),
+                    csv_budget(&entry.allotted),
+                    csv_budget(&entry.spent),
+                    csv_budget(&entry.idle),
+                    entry.starved_steps,
+                    csv_budget(&entry.debt)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// what every output of every ledger has been allotted and spent so far
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+
+        for report in &self.reports {
+            summary += &format!(
+                "ledger {} after {} steps:\n",
+                lobe_name(&self.names, report.ledger),
+                report.steps
+            );
+
+            for entry in &report.entries {
+                summary += &format!(
+                    "    {}: allotted {:?}, spent {:?}, idle {:?}, \
+                        starved for {} steps, owes {:?}\n",
+                    lobe_name(&self.names, entry.lobe),
+                    entry.total_allotted,
+                    entry.total_spent,
+                    entry.idle,
+                    entry.starved_steps,
+                    entry.debt
+                );
+            }
+        }
+
+        summary
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn lobe_name(names: &LobeNames, hdl: cortical::NodeHdl) -> String {
+    match names.borrow().get(&hdl) {
+        Some(name) => name.clone(),
+        None => format!("{:?}", hdl)
+    }
+}
+
+fn csv_budget(budget: &Budget) -> String {
+    format!(
+        "{},{},{},{}",
+        budget.minerals,
+        budget.vespene,
+        budget.food,
+        budget.larva
+    )
+}
+
+create_lobe_data! {
+    module: ledger_report,
+
+    req frame: Rc<sc2::FrameData>,
+    var reports: LedgerReport,
+}
+
+pub use self::ledger_report::{
+    Input as LedgerReportInput,
+    Output as LedgerReportOutput,
+    FeedbackInput as LedgerReportFeedbackInput,
+    FeedbackOutput as LedgerReportFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: LedgerReportLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: LedgerReportInput,
+    output: LedgerReportOutput,
+    feedback_input: LedgerReportFeedbackInput,
+    feedback_output: LedgerReportFeedbackOutput,
+
+    req frame: FrameData,
+    var reports: LedgerReport,
+}
+
+impl cortical::Lobe for LedgerReportLobe {
+    type Input = LedgerReportInput;
+    type Output = LedgerReportOutput;
+    type FeedbackInput = LedgerReportFeedbackInput;
+    type FeedbackOutput = LedgerReportFeedbackOutput;
+
+    fn start(
+        &mut self,
+        _: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.reports.clear();
+        self.last_step = None;
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        // the reports cover everything up to the feedback of the last frame
+        self.reports = input.reports;
+
+        if let Some(step) = self.last_step {
+            self.write_reports(step).chain_err(
+                || cortical::ErrorKind::LobeError
+            )?;
+        }
+
+        self.last_step = Some(input.frame.state.current_step);
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(LedgerReportOutput { })
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(LedgerReportFeedbackOutput { })
+    }
+
+    fn stop(&mut self) -> cortical::Result<()> {
+        if self.print_summary {
+            print!("{}", self.summary());
+        }
+
+        self.close().chain_err(|| cortical::ErrorKind::LobeError)?;
+
+        Ok(())
+    }
+}
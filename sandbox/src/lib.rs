@@ -1,5 +1,6 @@
 #![warn(missing_docs)]
 
+extern crate bincode;
 extern crate futures;
 extern crate gdk_pixbuf;
 extern crate gtk;
@@ -7,7 +8,9 @@ extern crate nalgebra as na;
 extern crate rand;
 extern crate relm;
 extern crate sc2;
+extern crate serde_json;
 extern crate tantrum;
+extern crate toml;
 
 #[macro_use]
 extern crate cortical;
@@ -18,11 +21,26 @@ extern crate relm_derive;
 #[macro_use]
 extern crate serde_derive;
 
+mod base_locations;
+mod base_sites;
 mod budgeters;
+mod build_order;
+mod command_log;
 mod debug_window;
 mod errors;
+mod exact_base_locator;
+mod expansion;
 mod drone_morphers;
+mod larva_morphers;
+mod ledger_report;
 mod nudge_base_locator;
+mod occupancy;
+mod placement;
+mod recording;
+mod registry;
+mod replay;
+mod rng;
+mod topology;
 
 use cortical::{ CortexBuilder };
 use tantrum::{
@@ -50,11 +68,26 @@ use tantrum::{
     CommandMergerFeedbackOutput,
 };
 
+pub use base_locations::*;
+pub use base_sites::*;
 pub use budgeters::*;
+pub use build_order::*;
+pub use command_log::*;
 pub use debug_window::*;
 pub use errors::*;
+pub use exact_base_locator::*;
+pub use expansion::*;
 pub use drone_morphers::*;
+pub use larva_morphers::*;
+pub use ledger_report::*;
 pub use nudge_base_locator::*;
+pub use occupancy::*;
+pub use placement::*;
+pub use recording::*;
+pub use registry::*;
+pub use replay::*;
+pub use rng::*;
+pub use topology::*;
 
 create_cortex! {
     module: keli_cortex,
@@ -62,7 +95,16 @@ create_cortex! {
         FrameData:                  Rc<sc2::FrameData>,
         Resources:                  Rc<Vec<ResourceCluster>>,
         PotentialBaseLocations:     Rc<Vec<sc2::data::Point2>>,
+        BaseLocationChanges:        BaseLocationChanges,
+        BaseSites:                  Rc<Vec<BaseSite>>,
         Budget:                     LobeBudget,
+        BudgetRequest:              LobeBudgetRequest,
+        IncomeRate:                 Income,
+        Reservations:               Vec<Reservation>,
+        LedgerReport:               LedgerReport,
+        BuildOrderProgress:         BuildOrderProgress,
+        OccupancyGrid:              Rc<OccupancyGrid>,
+        LobeCommands:               LobeCommands,
         Commands:                   Vec<sc2::Command>
     },
     input: FrameData,
@@ -1,21 +1,42 @@
 
+use std::collections::{ BTreeMap };
+use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
 
 use cortical;
 use na::{ normalize, distance_squared };
 use sc2;
-use sc2::data::{ Point2, Vector2, Point3 };
+use sc2::data::{ Point2, Tag, Vector2, Point3 };
 use tantrum::{ ResourceCluster };
 
-use super::{ KeliConstraint, KeliData };
+use super::{
+    BaseLocationCache,
+    BaseLocationChanges,
+    KeliConstraint,
+    KeliData,
+
+    cluster_key,
+};
+
+/// turns debug drawing on or off while the bot is running
+///
+/// the debug window flips it from its own thread, so it is atomic.
+pub type DebugToggle = Arc<AtomicBool>;
 
 /// finds base locations using an iterative algorithm
 ///
 /// nudges location little by little until distance reaches a threshold
 pub struct NudgeBaseLocatorLobe {
-    locations:              Option<Rc<Vec<Point2>>>,
-    debug_commands:         Option<Vec<sc2::Command>>,
-    debug:                  bool
+    cache:                  BaseLocationCache,
+    /// the drawings of every cluster, by the tags of its resources
+    debug_draws:            BTreeMap<Vec<Tag>, Vec<sc2::Command>>,
+    debug:                  DebugToggle,
+    /// whether this lobe is drawing, until the toggle is flipped
+    drawing:                bool,
+    /// the state of the toggle when it was last checked
+    toggled:                bool,
 }
 
 create_lobe_data! {
@@ -24,6 +45,7 @@ create_lobe_data! {
     req clusters: Rc<Vec<ResourceCluster>>,
 
     out locations: Rc<Vec<Point2>>,
+    out changes: BaseLocationChanges,
     out debug_commands: Vec<sc2::Command>,
 }
 
@@ -47,24 +69,52 @@ constrain_lobe! {
     req clusters: Resources,
 
     out locations: PotentialBaseLocations,
+    out changes: BaseLocationChanges,
     out debug_commands: Commands,
 }
 
 impl NudgeBaseLocatorLobe {
+    /// find base locations without drawing anything
     pub fn new() -> Self {
         Self {
-            locations: Some(Rc::from(vec![ ])),
-            debug_commands: Some(vec![ ]),
-            debug: false
+            cache: BaseLocationCache::new(),
+            debug_draws: BTreeMap::new(),
+            debug: Arc::new(AtomicBool::new(false)),
+            drawing: false,
+            toggled: false,
         }
     }
 
+    /// draw every iteration of every cluster, along with the chosen
+    /// locations
     pub fn with_debug() -> Self {
-        Self {
-            locations: Some(Rc::from(vec![ ])),
-            debug_commands: Some(vec![ ]),
-            debug: true
+        Self { drawing: true, ..Self::new() }
+    }
+
+    /// share a toggle for debug drawing with other parts of the bot
+    ///
+    /// the lobe keeps drawing (or not) as it was constructed until the
+    /// toggle is flipped, the toggle itself is left alone.
+    pub fn with_debug_toggle(mut self, toggle: DebugToggle) -> Self {
+        self.toggled = toggle.load(Ordering::Relaxed);
+        self.debug = toggle;
+        self
+    }
+
+    /// the toggle for debug drawing
+    pub fn debug_toggle(&self) -> DebugToggle {
+        Arc::clone(&self.debug)
+    }
+
+    fn is_debugging(&mut self) -> bool {
+        let toggled = self.debug.load(Ordering::Relaxed);
+
+        if toggled != self.toggled {
+            self.toggled = toggled;
+            self.drawing = toggled;
         }
+
+        self.drawing
     }
 
     fn find_closest_resource(&self, location: Point2, resources: &Vec<Point2>)
@@ -87,11 +137,17 @@ impl NudgeBaseLocatorLobe {
         closest
     }
 
-    fn find_base_location(&mut self, cluster: &ResourceCluster) -> Point2 {
+    /// find the location for a cluster along with the debug drawings of
+    /// every iteration it took to get there, if asked to draw them
+    fn find_base_location(&self, cluster: &ResourceCluster, draw: bool)
+        -> (Point2, Vec<sc2::Command>)
+    {
         let resources: Vec<Point2> = cluster.resources.iter().map(
             |r| Point2::new(r.pos.x, r.pos.y)
         ).collect();
 
+        let z = cluster.resources[0].pos.z;
+
         // initialize location as the center of mass
         let mut location = Point2::from_coordinates(
             resources.iter().fold(
@@ -103,25 +159,23 @@ impl NudgeBaseLocatorLobe {
         const DESIRED: f32 = 37.0;
         const NUDGE_FACTOR: f32 = 4.0;
 
+        let mut commands = vec![ ];
+
         for i in 0..MAX_ITERATIONS {
             // find the closest resource and nudge the location away from it
             let closest = self.find_closest_resource(location, &resources);
 
             let dist = distance_squared(&location, &closest);
 
-            if self.debug {
-                let g =
-                    ((i as f32 + 1.0) / (MAX_ITERATIONS as f32) * 127.0) as u8
-                    + 128
-                ;
-
-                let mut commands = vec![ ];
+            let g =
+                ((i as f32 + 1.0) / (MAX_ITERATIONS as f32) * 127.0) as u8
+                + 128
+            ;
 
+            if draw {
                 commands.push(
                     sc2::Command::DebugSphere {
-                        center: Point3::new(
-                            location.x, location.y, cluster.resources[0].pos.z
-                        ),
+                        center: Point3::new(location.x, location.y, z),
                         radius: 2.0,
                         color: (g, g, g)
                     }
@@ -132,17 +186,11 @@ impl NudgeBaseLocatorLobe {
                         color: (g, g, g),
                         target: Some(
                             sc2::DebugTextTarget::World(
-                                Point3::new(
-                                    location.x,
-                                    location.y,
-                                    cluster.resources[0].pos.z
-                                )
+                                Point3::new(location.x, location.y, z)
                             )
                         )
                     }
                 );
-
-                self.debug_commands = Some(commands);
             }
 
             let direction = normalize(&(location.coords - closest.coords));
@@ -152,11 +200,49 @@ impl NudgeBaseLocatorLobe {
             }
             else {
                 let nudge = ((DESIRED - dist) / DESIRED) * NUDGE_FACTOR;
+                let previous = location;
+
                 location += nudge * direction;
+
+                // trace the path of the location from one iteration to the
+                // next
+                if draw {
+                    commands.push(
+                        sc2::Command::DebugLine {
+                            p1: Point3::new(previous.x, previous.y, z),
+                            p2: Point3::new(location.x, location.y, z),
+                            color: (g, g, g)
+                        }
+                    );
+                }
             }
         }
 
-        location
+        if !draw {
+            return (location, commands)
+        }
+
+        // the chosen location stands out from the iterations
+        commands.push(
+            sc2::Command::DebugSphere {
+                center: Point3::new(location.x, location.y, z),
+                radius: 2.5,
+                color: (0, 255, 0)
+            }
+        );
+        commands.push(
+            sc2::Command::DebugText {
+                text: "base".to_string(),
+                color: (0, 255, 0),
+                target: Some(
+                    sc2::DebugTextTarget::World(
+                        Point3::new(location.x, location.y, z)
+                    )
+                )
+            }
+        );
+
+        (location, commands)
     }
 }
 
@@ -167,13 +253,50 @@ impl cortical::Lobe for NudgeBaseLocatorLobe {
     type FeedbackOutput = NudgeBaseLocatorFeedbackOutput;
 
     fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
-        let mut locations = vec![ ];
+        let draw = self.is_debugging();
 
-        for cluster in input.clusters.iter() {
-            locations.push(self.find_base_location(cluster));
+        // only clusters that changed since the last update are solved again
+        let mut cache = mem::replace(&mut self.cache, BaseLocationCache::new());
+        let mut solved = vec![ ];
+
+        cache.update(
+            &input.clusters,
+            |cluster| {
+                let (location, commands) = self.find_base_location(
+                    cluster, draw
+                );
+
+                if draw {
+                    solved.push((cluster_key(cluster), commands));
+                }
+
+                Some(location)
+            }
+        );
+
+        self.cache = cache;
+
+        if !draw {
+            self.debug_draws.clear();
+
+            return Ok(())
         }
 
-        self.locations = Some(Rc::from(locations));
+        // keep drawing the clusters that are still around, and draw the ones
+        // that were solved while debugging was off
+        let mut draws = mem::replace(&mut self.debug_draws, BTreeMap::new());
+
+        draws.extend(solved);
+
+        for cluster in input.clusters.iter() {
+            let key = cluster_key(cluster);
+            let commands = match draws.remove(&key) {
+                Some(commands) => commands,
+                None => self.find_base_location(cluster, true).1
+            };
+
+            self.debug_draws.insert(key, commands);
+        }
 
         Ok(())
     }
@@ -183,8 +306,16 @@ impl cortical::Lobe for NudgeBaseLocatorLobe {
     {
         Ok(
             NudgeBaseLocatorOutput {
-                locations: Rc::clone(self.locations.as_ref().unwrap()),
-                debug_commands: self.debug_commands.as_ref().unwrap().clone()
+                locations: self.cache.locations(),
+                changes: self.cache.changes().clone(),
+                debug_commands: if self.is_debugging() {
+                    self.debug_draws.values()
+                        .flat_map(|commands| commands.clone())
+                        .collect()
+                }
+                else {
+                    vec![ ]
+                }
             }
         )
     }
@@ -195,3 +326,34 @@ impl cortical::Lobe for NudgeBaseLocatorLobe {
         Ok(NudgeBaseLocatorFeedbackOutput { })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lobes_keep_their_own_drawing_state_until_toggled() {
+        let toggle = DebugToggle::default();
+
+        let mut quiet = NudgeBaseLocatorLobe::new()
+            .with_debug_toggle(Arc::clone(&toggle))
+        ;
+        let mut drawing = NudgeBaseLocatorLobe::with_debug()
+            .with_debug_toggle(Arc::clone(&toggle))
+        ;
+
+        assert!(!toggle.load(Ordering::Relaxed));
+        assert!(!quiet.is_debugging());
+        assert!(drawing.is_debugging());
+
+        toggle.store(true, Ordering::Relaxed);
+
+        assert!(quiet.is_debugging());
+        assert!(drawing.is_debugging());
+
+        toggle.store(false, Ordering::Relaxed);
+
+        assert!(!quiet.is_debugging());
+        assert!(!drawing.is_debugging());
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::{ HashMap, HashSet };
+use std::rc::Rc;
+
+use cortical;
+use sc2;
+use sc2::data::{ Point2, Tag, Unit };
+
+use super::{
+    KeliConstraint,
+    KeliData,
+
+    image_pixel,
+    is_geyser,
+    is_mineral_field,
+    structure_footprint,
+};
+
+/// what occupies a tile of the map
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Tile {
+    /// terrain that nothing can be built on
+    Unbuildable,
+    /// buildable terrain with nothing on it
+    Free,
+    /// covered by the footprint of a structure
+    Structure,
+    /// covered by a mineral field or a vespene geyser
+    Resource,
+}
+
+/// a per-tile map of the buildable terrain and what is built on it
+#[derive(Debug, Clone)]
+pub struct OccupancyGrid {
+    width:                  i32,
+    height:                 i32,
+
+    tiles:                  Vec<Tile>,
+}
+
+impl OccupancyGrid {
+    /// the grid for a single frame, built from scratch
+    ///
+    /// lobes connected to an `OccupancyGridLobe` should use its grid instead,
+    /// which only touches the tiles that changed.
+    pub fn from_frame(frame: &sc2::FrameData) -> Self {
+        let placement = &frame.data.terrain_info.placement_grid;
+        let cells = (placement.width * placement.height) as usize;
+
+        let mut grid = Self {
+            width: placement.width,
+            height: placement.height,
+
+            tiles: (0..cells).map(
+                |i| if image_pixel(placement, i) {
+                    Tile::Free
+                }
+                else {
+                    Tile::Unbuildable
+                }
+            ).collect(),
+        };
+
+        let footprints = frame.state.units.iter().filter_map(
+            |u| Footprint::of(u)
+        );
+
+        for footprint in footprints {
+            for y in footprint.y..(footprint.y + footprint.height) {
+                for x in footprint.x..(footprint.x + footprint.width) {
+                    let i = match grid.index(x, y) {
+                        Some(i) => i,
+                        None => continue
+                    };
+
+                    // structures win over resources, like in the lobe
+                    if grid.tiles[i] != Tile::Structure {
+                        grid.tiles[i] = footprint.tile;
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// the width of the map in tiles
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// the height of the map in tiles
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// the tile at the given coordinates, unbuildable if outside of the map
+    pub fn tile(&self, x: i32, y: i32) -> Tile {
+        match self.index(x, y) {
+            Some(i) => self.tiles[i],
+            None => Tile::Unbuildable
+        }
+    }
+
+    /// true if a structure of the given footprint fits at the location
+    pub fn fits(&self, center: Point2, footprint: u32) -> bool {
+        let half = footprint as f32 / 2.0;
+
+        let x0 = (center.x - half).round() as i32;
+        let y0 = (center.y - half).round() as i32;
+
+        for y in y0..(y0 + footprint as i32) {
+            for x in x0..(x0 + footprint as i32) {
+                if self.tile(x, y) != Tile::Free {
+                    return false
+                }
+            }
+        }
+
+        true
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        }
+        else {
+            Some((x + y * self.width) as usize)
+        }
+    }
+}
+
+/// the tiles covered by a structure or resource
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Footprint {
+    x:                      i32,
+    y:                      i32,
+    width:                  i32,
+    height:                 i32,
+
+    tile:                   Tile,
+}
+
+impl Footprint {
+    /// the footprint of a unit, if it occupies tiles
+    ///
+    /// resources are matched by type, since fogged ones report no contents.
+    /// structures are matched by type as well, so a hatchery keeps its tiles
+    /// while it morphs into a lair. flying units never occupy tiles, and
+    /// neither do ground units that are not structures.
+    fn of(unit: &Unit) -> Option<Self> {
+        if unit.is_flying {
+            return None
+        }
+
+        let (width, height, tile) = if is_mineral_field(unit.unit_type) {
+            (2, 1, Tile::Resource)
+        }
+        else if is_geyser(unit.unit_type) {
+            (3, 3, Tile::Resource)
+        }
+        else if let Some(size) = structure_footprint(unit.unit_type) {
+            (size as i32, size as i32, Tile::Structure)
+        }
+        else {
+            return None
+        };
+
+        Some(
+            Self {
+                x: (unit.pos.x - width as f32 / 2.0).round() as i32,
+                y: (unit.pos.y - height as f32 / 2.0).round() as i32,
+                width: width,
+                height: height,
+
+                tile: tile,
+            }
+        )
+    }
+}
+
+/// keeps track of which tiles are occupied by structures and resources
+///
+/// the grid starts from the placement grid of the map. footprints are added
+/// as structures and resources appear and removed when they die or are mined
+/// out, so only the tiles that changed are touched every step.
+pub struct OccupancyGridLobe {
+    grid:                   Option<Rc<OccupancyGrid>>,
+
+    buildable:              Vec<bool>,
+    structures:             Vec<u32>,
+    resources:              Vec<u32>,
+
+    footprints:             HashMap<Tag, Footprint>,
+}
+
+impl OccupancyGridLobe {
+    /// track occupancy starting from the first frame
+    pub fn new() -> Self {
+        Self {
+            grid: None,
+
+            buildable: vec![ ],
+            structures: vec![ ],
+            resources: vec![ ],
+
+            footprints: HashMap::new(),
+        }
+    }
+
+    fn init(&mut self, frame: &sc2::FrameData) {
+        let placement = &frame.data.terrain_info.placement_grid;
+        let cells = (placement.width * placement.height) as usize;
+
+        self.buildable = (0..cells).map(|i| image_pixel(placement, i))
+            .collect()
+        ;
+        self.structures = vec![ 0; cells ];
+        self.resources = vec![ 0; cells ];
+        self.footprints.clear();
+
+        self.grid = Some(
+            Rc::new(
+                OccupancyGrid {
+                    width: placement.width,
+                    height: placement.height,
+
+                    tiles: self.buildable.iter().map(
+                        |&buildable| if buildable {
+                            Tile::Free
+                        }
+                        else {
+                            Tile::Unbuildable
+                        }
+                    ).collect(),
+                }
+            )
+        );
+    }
+
+    fn update_grid(&mut self, frame: &sc2::FrameData) {
+        let mut current = HashSet::new();
+        let mut added = vec![ ];
+
+        for u in &frame.state.units {
+            if let Some(footprint) = Footprint::of(u) {
+                if self.footprints.get(&u.tag) != Some(&footprint) {
+                    added.push((u.tag, footprint));
+                }
+                else {
+                    current.insert(u.tag);
+                }
+            }
+        }
+
+        // footprints that disappeared or changed shape are removed first
+        let removed: Vec<Tag> = self.footprints.keys()
+            .filter(|tag| !current.contains(tag))
+            .cloned()
+            .collect()
+        ;
+
+        for tag in removed {
+            let footprint = self.footprints.remove(&tag).unwrap();
+            self.mark(footprint, false);
+        }
+
+        for (tag, footprint) in added {
+            self.footprints.insert(tag, footprint);
+            self.mark(footprint, true);
+        }
+    }
+
+    /// add or remove a footprint and refresh the tiles it covers
+    fn mark(&mut self, footprint: Footprint, occupied: bool) {
+        let grid = Rc::make_mut(self.grid.as_mut().unwrap());
+
+        for y in footprint.y..(footprint.y + footprint.height) {
+            for x in footprint.x..(footprint.x + footprint.width) {
+                let i = match grid.index(x, y) {
+                    Some(i) => i,
+                    None => continue
+                };
+
+                {
+                    let counts = match footprint.tile {
+                        Tile::Structure => &mut self.structures,
+                        _ => &mut self.resources,
+                    };
+
+                    if occupied {
+                        counts[i] += 1;
+                    }
+                    else if counts[i] > 0 {
+                        counts[i] -= 1;
+                    }
+                }
+
+                grid.tiles[i] = if self.structures[i] > 0 {
+                    Tile::Structure
+                }
+                else if self.resources[i] > 0 {
+                    Tile::Resource
+                }
+                else if self.buildable[i] {
+                    Tile::Free
+                }
+                else {
+                    Tile::Unbuildable
+                };
+            }
+        }
+    }
+}
+
+create_lobe_data! {
+    module: occupancy_grid,
+
+    req frame: Rc<sc2::FrameData>,
+
+    out grid: Rc<OccupancyGrid>,
+}
+
+pub use self::occupancy_grid::{
+    Input as OccupancyGridInput,
+    Output as OccupancyGridOutput,
+    FeedbackInput as OccupancyGridFeedbackInput,
+    FeedbackOutput as OccupancyGridFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: OccupancyGridLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: OccupancyGridInput,
+    output: OccupancyGridOutput,
+    feedback_input: OccupancyGridFeedbackInput,
+    feedback_output: OccupancyGridFeedbackOutput,
+
+    req frame: FrameData,
+
+    out grid: OccupancyGrid,
+}
+
+impl cortical::Lobe for OccupancyGridLobe {
+    type Input = OccupancyGridInput;
+    type Output = OccupancyGridOutput;
+    type FeedbackInput = OccupancyGridFeedbackInput;
+    type FeedbackOutput = OccupancyGridFeedbackOutput;
+
+    fn start(
+        &mut self,
+        _: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.grid = None;
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        if self.grid.is_none() {
+            self.init(&input.frame);
+        }
+
+        self.update_grid(&input.frame);
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        match self.grid {
+            Some(ref grid) => Ok(
+                OccupancyGridOutput { grid: Rc::clone(grid) }
+            ),
+            None => bail!("occupancy grid has not received a frame")
+        }
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(OccupancyGridFeedbackOutput { })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sc2::data::{ UnitType };
+
+    use super::super::{
+        RecordedAlliance,
+        RecordedDisplayType,
+        RecordedFrame,
+        RecordedImage,
+        RecordedState,
+        RecordedTerrain,
+        RecordedUnit,
+        Recording,
+    };
+
+    const MAP_SIZE: i32 = 64;
+
+    fn image(data: Vec<u8>) -> RecordedImage {
+        RecordedImage {
+            width: MAP_SIZE, height: MAP_SIZE, bits_per_pixel: 8, data: data
+        }
+    }
+
+    fn unit(tag: Tag, unit_type: UnitType, x: f32, y: f32) -> RecordedUnit {
+        RecordedUnit {
+            tag: tag,
+            unit_type: unit_type as u32,
+            display_type: RecordedDisplayType::Visible,
+            alliance: RecordedAlliance::Domestic,
+            owner: 1,
+            pos: (x, y, 0.0),
+            facing: 0.0,
+            radius: 0.5,
+            build_progress: 1.0,
+            health: (40.0, 40.0),
+            shield: (0.0, 0.0),
+            energy: (0.0, 0.0),
+            is_flying: false,
+            is_burrowed: false,
+            orders: vec![ ],
+            mineral_contents: 0,
+            vespene_contents: 0,
+        }
+    }
+
+    /// a buildable map with the given units on each step
+    fn frames(steps: Vec<Vec<RecordedUnit>>) -> Vec<Rc<sc2::FrameData>> {
+        let cells = (MAP_SIZE * MAP_SIZE) as usize;
+
+        let recording = Recording {
+            terrain: RecordedTerrain {
+                width: MAP_SIZE,
+                height: MAP_SIZE,
+                pathing_grid: image(vec![ 1; cells ]),
+                placement_grid: image(vec![ 1; cells ]),
+                terrain_height: image(vec![ 0; cells ]),
+                playable_min: (0.0, 0.0),
+                playable_max: (MAP_SIZE as f32, MAP_SIZE as f32),
+                enemy_start_locations: vec![ ],
+            },
+            unit_types: vec![ ],
+            frames: steps.into_iter().enumerate().map(
+                |(step, units)| RecordedFrame {
+                    state: RecordedState {
+                        current_step: step as u32,
+                        minerals: 0,
+                        vespene: 0,
+                        food_used: 0,
+                        food_cap: 0,
+                        larva_count: 0,
+                    },
+                    units: units,
+                    creep: image(vec![ 0; cells ]),
+                    visibility: image(vec![ 0; cells ]),
+                }
+            ).collect(),
+        };
+
+        recording.frame_data().unwrap()
+    }
+
+    #[test]
+    fn structures_of_every_race_occupy_tiles() {
+        let mut overlord = unit(5, UnitType::ZergOverlord, 50.5, 50.5);
+        overlord.is_flying = true;
+
+        let grid = OccupancyGrid::from_frame(
+            &frames(
+                vec![
+                    vec![
+                        unit(1, UnitType::ZergLair, 10.5, 10.5),
+                        unit(2, UnitType::TerranSupplyDepot, 30.0, 30.0),
+                        unit(3, UnitType::ProtossNexus, 40.5, 10.5),
+                        unit(4, UnitType::ZergDrone, 30.5, 50.5),
+                        overlord,
+                    ],
+                ]
+            )[0]
+        );
+
+        // the corners of the lair, depot and nexus footprints
+        for &(x, y) in &[
+            (8, 8), (12, 12), (29, 29), (30, 30), (38, 8), (42, 12)
+        ] {
+            assert_eq!(grid.tile(x, y), Tile::Structure, "({}, {})", x, y);
+        }
+
+        // just outside of them
+        for &(x, y) in &[ (7, 10), (13, 10), (28, 30), (31, 30), (43, 10) ] {
+            assert_eq!(grid.tile(x, y), Tile::Free, "({}, {})", x, y);
+        }
+
+        // neither the drone nor the overlord take up room
+        assert_eq!(grid.tile(30, 50), Tile::Free);
+        assert_eq!(grid.tile(50, 50), Tile::Free);
+    }
+
+    #[test]
+    fn hatcheries_keep_their_tiles_while_morphing_into_a_lair() {
+        let frames = frames(
+            vec![
+                vec![ unit(1, UnitType::ZergHatchery, 10.5, 10.5) ],
+                vec![ unit(1, UnitType::ZergLair, 10.5, 10.5) ],
+                vec![ ],
+            ]
+        );
+
+        let mut lobe = OccupancyGridLobe::new();
+        lobe.init(&frames[0]);
+
+        let mut tiles = vec![ ];
+
+        for frame in &frames {
+            lobe.update_grid(frame);
+            tiles.push(lobe.grid.as_ref().unwrap().tile(10, 10));
+        }
+
+        // the lair's footprint was only counted once, so it is freed as soon
+        // as it is gone
+        assert_eq!(tiles, vec![ Tile::Structure, Tile::Structure, Tile::Free ]);
+    }
+}
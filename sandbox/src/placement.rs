@@ -0,0 +1,409 @@
+use std::rc::Rc;
+
+use rand::{ Rng };
+use sc2;
+use sc2::data::{ Alliance, ImageData, Point2, Unit, UnitType };
+
+use super::{ MorphSource, OccupancyGrid, Tile };
+
+/// how far from the anchor to look for a location
+const SEARCH_RADIUS: i32 = 15;
+
+/// how to choose between the valid locations for a structure
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PlacementStrategy {
+    /// any valid location
+    Random,
+    /// as close to the anchor as possible
+    Compact,
+    /// as far from other structures as possible
+    Spread,
+    /// as close to the anchor's mineral line as possible
+    NearMinerals,
+    /// on the side of the anchor facing away from its mineral line, as if the
+    /// mineral line was mirrored across the anchor
+    AwayFromMinerals,
+}
+
+impl Default for PlacementStrategy {
+    fn default() -> Self {
+        PlacementStrategy::Random
+    }
+}
+
+/// the width and height of a structure in grid cells, or `None` if the unit
+/// type is not a structure
+///
+/// covers the buildings of every race, since the enemy's take up room too.
+/// lifted terran buildings are flying units rather than structures.
+pub fn structure_footprint(unit_type: UnitType) -> Option<u32> {
+    match unit_type {
+        UnitType::ZergHatchery
+        | UnitType::ZergLair
+        | UnitType::ZergHive
+        | UnitType::TerranCommandCenter
+        | UnitType::TerranOrbitalCommand
+        | UnitType::TerranPlanetaryFortress
+        | UnitType::ProtossNexus => Some(5),
+
+        UnitType::ZergExtractor
+        | UnitType::ZergSpawningPool
+        | UnitType::ZergEvolutionChamber
+        | UnitType::ZergRoachWarren
+        | UnitType::ZergBanelingNest
+        | UnitType::ZergHydraliskDen
+        | UnitType::ZergInfestationPit
+        | UnitType::ZergUltraliskCavern
+        | UnitType::ZergNydusNetwork
+        | UnitType::TerranRefinery
+        | UnitType::TerranBarracks
+        | UnitType::TerranFactory
+        | UnitType::TerranStarport
+        | UnitType::TerranEngineeringBay
+        | UnitType::TerranArmory
+        | UnitType::TerranBunker
+        | UnitType::TerranGhostAcademy
+        | UnitType::TerranFusionCore
+        | UnitType::ProtossAssimilator
+        | UnitType::ProtossGateway
+        | UnitType::ProtossWarpGate
+        | UnitType::ProtossForge
+        | UnitType::ProtossCyberneticsCore
+        | UnitType::ProtossTwilightCouncil
+        | UnitType::ProtossStargate
+        | UnitType::ProtossRoboticsFacility
+        | UnitType::ProtossRoboticsBay
+        | UnitType::ProtossFleetBeacon
+        | UnitType::ProtossTemplarArchive => Some(3),
+
+        UnitType::ZergSpire
+        | UnitType::ZergGreaterSpire
+        | UnitType::ZergSpineCrawler
+        | UnitType::ZergSporeCrawler
+        | UnitType::TerranSupplyDepot
+        | UnitType::TerranSupplyDepotLowered
+        | UnitType::TerranMissileTurret
+        | UnitType::TerranBarracksTechLab
+        | UnitType::TerranBarracksReactor
+        | UnitType::TerranFactoryTechLab
+        | UnitType::TerranFactoryReactor
+        | UnitType::TerranStarportTechLab
+        | UnitType::TerranStarportReactor
+        | UnitType::ProtossPylon
+        | UnitType::ProtossPhotonCannon
+        | UnitType::ProtossShieldBattery
+        | UnitType::ProtossDarkShrine => Some(2),
+
+        UnitType::ZergCreepTumor
+        | UnitType::ZergCreepTumorBurrowed
+        | UnitType::ZergCreepTumorQueen
+        | UnitType::TerranSensorTower => Some(1),
+
+        _ => None
+    }
+}
+
+/// whether a structure must be placed on creep
+pub fn needs_creep(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::ZergHatchery | UnitType::ZergExtractor => false,
+        _ => MorphSource::of(unit_type) == MorphSource::Drone
+    }
+}
+
+/// which cells of the map a structure can be placed on
+///
+/// combines the occupancy grid with the creep layer of the frame.
+pub struct PlacementGrid {
+    width:                  i32,
+    height:                 i32,
+
+    occupancy:              Rc<OccupancyGrid>,
+    creep:                  Vec<bool>,
+}
+
+impl PlacementGrid {
+    /// build the grid for the given frame, working out the occupied tiles
+    /// from scratch
+    pub fn from_frame(frame: &sc2::FrameData) -> Self {
+        Self::with_occupancy(frame, Rc::new(OccupancyGrid::from_frame(frame)))
+    }
+
+    /// build the grid for the given frame on top of a shared occupancy grid
+    pub fn with_occupancy(
+        frame: &sc2::FrameData, occupancy: Rc<OccupancyGrid>
+    )
+        -> Self
+    {
+        let creep = &frame.map.creep;
+
+        let width = occupancy.width();
+        let height = occupancy.height();
+        let cells = (width * height) as usize;
+
+        Self {
+            width: width,
+            height: height,
+
+            occupancy: occupancy,
+            creep: (0..cells).map(|i| image_pixel(creep, i)).collect(),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        }
+        else {
+            Some((x + y * self.width) as usize)
+        }
+    }
+
+    /// true if a structure of the given footprint fits at the location
+    pub fn can_place(&self, center: Point2, footprint: u32, creep: bool)
+        -> bool
+    {
+        let half = footprint as f32 / 2.0;
+
+        let x0 = (center.x - half).round() as i32;
+        let y0 = (center.y - half).round() as i32;
+
+        for y in y0..(y0 + footprint as i32) {
+            for x in x0..(x0 + footprint as i32) {
+                match self.index(x, y) {
+                    Some(i) => if self.occupancy.tile(x, y) != Tile::Free
+                        || (creep && !self.creep[i])
+                    {
+                        return false
+                    },
+                    None => return false
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// reads the pixel at the given index of a 1 or 8 bit image as a bool
+pub fn image_pixel(image: &ImageData, i: usize) -> bool {
+    if image.bits_per_pixel == 1 {
+        match image.data.get(i / 8) {
+            Some(byte) => byte & (0x80 >> (i % 8)) != 0,
+            None => false
+        }
+    }
+    else {
+        match image.data.get(i) {
+            Some(&value) => value != 0,
+            None => false
+        }
+    }
+}
+
+/// true if the unit type is a mineral field of any kind
+///
+/// resources are told apart by type since fogged snapshots report no
+/// contents.
+pub fn is_mineral_field(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::NeutralMineralField
+        | UnitType::NeutralMineralField750
+        | UnitType::NeutralRichMineralField
+        | UnitType::NeutralRichMineralField750
+        | UnitType::NeutralLabMineralField
+        | UnitType::NeutralLabMineralField750
+        | UnitType::NeutralBattleStationMineralField
+        | UnitType::NeutralBattleStationMineralField750
+        | UnitType::NeutralPurifierMineralField
+        | UnitType::NeutralPurifierMineralField750
+        | UnitType::NeutralPurifierRichMineralField
+        | UnitType::NeutralPurifierRichMineralField750 => true,
+
+        _ => false
+    }
+}
+
+/// true if the unit type is a vespene geyser of any kind
+pub fn is_geyser(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::NeutralVespeneGeyser
+        | UnitType::NeutralRichVespeneGeyser
+        | UnitType::NeutralProtossVespeneGeyser
+        | UnitType::NeutralPurifierVespeneGeyser
+        | UnitType::NeutralShakurasVespeneGeyser
+        | UnitType::NeutralSpacePlatformGeyser => true,
+
+        _ => false
+    }
+}
+
+/// the geyser closest to a hatchery that does not have an extractor yet
+pub fn free_geyser(frame: &sc2::FrameData, hatcheries: &[Rc<Unit>])
+    -> Option<Rc<Unit>>
+{
+    let position = |u: &Unit| Point2::new(u.pos.x, u.pos.y);
+
+    let extractors = frame.state.filter_units(
+        |u| u.unit_type == UnitType::ZergExtractor
+    );
+    let geysers = frame.state.filter_units(
+        |u| is_geyser(u.unit_type) && !extractors.iter().any(
+            |e| distance_squared(&position(e), &position(u)) < 1.0
+        )
+    );
+
+    let closest = |g: &Rc<Unit>| hatcheries.iter().map(
+        |h| distance_squared(&position(h), &position(g))
+    ).fold(::std::f32::MAX, f32::min);
+
+    geysers.into_iter().fold(
+        None,
+        |best: Option<Rc<Unit>>, g| match best {
+            Some(best) => if closest(&g) < closest(&best) {
+                Some(g)
+            }
+            else {
+                Some(best)
+            },
+            None => Some(g)
+        }
+    )
+}
+
+/// find a location for a structure around an anchor (usually a hatchery)
+///
+/// only locations where the structure fits on buildable terrain, on creep if
+/// it needs it, and clear of every structure and resource are considered.
+/// the shared occupancy grid is used when there is one. the strategy picks
+/// between them. returns `None` if the structure does not fit anywhere
+/// within the search radius.
+pub fn find_placement<R: Rng>(
+    frame: &sc2::FrameData,
+    occupancy: Option<&Rc<OccupancyGrid>>,
+    unit_type: UnitType,
+    anchor: Point2,
+    strategy: PlacementStrategy,
+    rng: &mut R
+)
+    -> Option<Point2>
+{
+    let grid = match occupancy {
+        Some(occupancy) => PlacementGrid::with_occupancy(
+            frame, Rc::clone(occupancy)
+        ),
+        None => PlacementGrid::from_frame(frame)
+    };
+
+    let footprint = match structure_footprint(unit_type) {
+        Some(footprint) => footprint,
+        None => return None
+    };
+    let creep = needs_creep(unit_type);
+
+    // odd footprints are centered on a cell, even ones on a cell corner
+    let offset = if footprint % 2 == 1 { 0.5 } else { 0.0 };
+
+    let mut candidates = vec![ ];
+
+    for dy in -SEARCH_RADIUS..(SEARCH_RADIUS + 1) {
+        for dx in -SEARCH_RADIUS..(SEARCH_RADIUS + 1) {
+            let center = Point2::new(
+                anchor.x.floor() + dx as f32 + offset,
+                anchor.y.floor() + dy as f32 + offset
+            );
+
+            if grid.can_place(center, footprint, creep) {
+                candidates.push(center);
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return None
+    }
+
+    let minerals = mineral_line(frame, anchor);
+    let structures = own_structures(frame);
+
+    let score = |p: &Point2| -> f32 {
+        match strategy {
+            PlacementStrategy::Random => 0.0,
+            PlacementStrategy::Compact => distance_squared(p, &anchor),
+            PlacementStrategy::Spread => -structures.iter().map(
+                |s| distance_squared(p, s)
+            ).fold(::std::f32::MAX, f32::min),
+            PlacementStrategy::NearMinerals => match minerals {
+                Some(ref m) => distance_squared(p, m),
+                None => distance_squared(p, &anchor)
+            },
+            PlacementStrategy::AwayFromMinerals => match minerals {
+                Some(ref m) => distance_squared(
+                    p,
+                    &Point2::new(2.0 * anchor.x - m.x, 2.0 * anchor.y - m.y)
+                ),
+                None => distance_squared(p, &anchor)
+            },
+        }
+    };
+
+    // shuffle so that ties (and every location for the random strategy) are
+    // broken at random
+    rng.shuffle(&mut candidates);
+
+    candidates.into_iter().fold(
+        None,
+        |best: Option<(Point2, f32)>, p| {
+            let s = score(&p);
+
+            match best {
+                Some((_, best_score)) if best_score <= s => best,
+                _ => Some((p, s))
+            }
+        }
+    ).map(
+        |(p, _)| p
+    )
+}
+
+fn distance_squared(a: &Point2, b: &Point2) -> f32 {
+    let dx = a.x - b.x;
+    let dy = a.y - b.y;
+
+    dx * dx + dy * dy
+}
+
+/// the center of the mineral fields near the anchor
+fn mineral_line(frame: &sc2::FrameData, anchor: Point2) -> Option<Point2> {
+    let radius = SEARCH_RADIUS as f32;
+
+    let minerals: Vec<Point2> = frame.state.units.iter()
+        .filter(|u| is_mineral_field(u.unit_type))
+        .map(|u| Point2::new(u.pos.x, u.pos.y))
+        .filter(|p| distance_squared(p, &anchor) < radius * radius)
+        .collect()
+    ;
+
+    if minerals.is_empty() {
+        return None
+    }
+
+    let n = minerals.len() as f32;
+
+    Some(
+        Point2::new(
+            minerals.iter().map(|p| p.x).sum::<f32>() / n,
+            minerals.iter().map(|p| p.y).sum::<f32>() / n
+        )
+    )
+}
+
+fn own_structures(frame: &sc2::FrameData) -> Vec<Point2> {
+    frame.state.units.iter()
+        .filter(
+            |u| u.alliance == Alliance::Domestic
+                && structure_footprint(u.unit_type).is_some()
+        )
+        .map(|u| Point2::new(u.pos.x, u.pos.y))
+        .collect()
+}
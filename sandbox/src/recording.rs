@@ -0,0 +1,761 @@
+use std::collections::{ HashMap };
+use std::fs::File;
+use std::io::{ BufRead, BufReader, BufWriter, Write };
+use std::path::Path;
+use std::rc::Rc;
+
+use bincode;
+use cortical;
+use cortical::{ ResultExt };
+use sc2;
+use sc2::data::{
+    Ability,
+    ActionTarget,
+    Alliance,
+    DisplayType,
+    ImageData,
+    Point2,
+    Point3,
+    TerrainInfo,
+    Unit,
+    UnitOrder,
+    UnitType,
+    UnitTypeData,
+};
+
+use errors::{ Result };
+use super::{ KeliConstraint, KeliData };
+
+const RECORDING_VERSION: u32 = 3;
+
+/// a grid or image layer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedImage {
+    /// width in pixels
+    pub width:              i32,
+    /// height in pixels
+    pub height:             i32,
+    /// bits used by each pixel
+    pub bits_per_pixel:     i32,
+    /// raw pixel data
+    pub data:               Vec<u8>,
+}
+
+/// static information about the map, recorded once per game
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedTerrain {
+    /// width of the map in tiles
+    pub width:              i32,
+    /// height of the map in tiles
+    pub height:             i32,
+    /// where ground units can move
+    pub pathing_grid:       RecordedImage,
+    /// where structures can be placed
+    pub placement_grid:     RecordedImage,
+    /// the height of the terrain
+    pub terrain_height:     RecordedImage,
+    /// the lower corner of the playable area
+    pub playable_min:       (f32, f32),
+    /// the upper corner of the playable area
+    pub playable_max:       (f32, f32),
+    /// where the opponents may have started
+    pub enemy_start_locations: Vec<(f32, f32)>,
+}
+
+/// the cost and ability of a unit type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedUnitType {
+    /// the unit type id
+    pub unit_type:          u32,
+    /// the name the game uses for the unit type
+    pub name:               String,
+    /// mineral cost
+    pub mineral_cost:       u32,
+    /// vespene cost
+    pub vespene_cost:       u32,
+    /// supply cost
+    pub food_required:      f32,
+    /// supply provided
+    pub food_provided:      f32,
+    /// game steps it takes to produce the unit
+    pub build_time:         f32,
+    /// the ability that produces the unit
+    pub ability:            u32,
+}
+
+/// the player a unit belongs to relative to the bot
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RecordedAlliance {
+    /// owned by the bot
+    Domestic,
+    /// owned by an ally
+    Ally,
+    /// not owned by anyone (resources, rocks)
+    Neutral,
+    /// owned by an opponent
+    Enemy,
+}
+
+/// how much the bot can currently see of a unit
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RecordedDisplayType {
+    /// in sight
+    Visible,
+    /// remembered from when it was last seen, under the fog of war
+    Snapshot,
+    /// not visible at all
+    Hidden,
+}
+
+/// the target of a unit order
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedTarget {
+    /// targets a point on the map
+    Location(f32, f32),
+    /// targets another unit
+    Unit(u64),
+}
+
+/// an order a unit is currently carrying out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedOrder {
+    /// the ability id
+    pub ability:            u32,
+    /// the target of the ability
+    pub target:             Option<RecordedTarget>,
+    /// progress of the order from 0 to 1
+    pub progress:           f32,
+}
+
+/// a single unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedUnit {
+    /// unique tag
+    pub tag:                u64,
+    /// the unit type id
+    pub unit_type:          u32,
+    /// whether the unit is in sight or remembered
+    pub display_type:       RecordedDisplayType,
+    /// who owns the unit
+    pub alliance:           RecordedAlliance,
+    /// the player id of the owner
+    pub owner:              i32,
+    /// position of the unit
+    pub pos:                (f32, f32, f32),
+    /// direction the unit faces in radians
+    pub facing:             f32,
+    /// radius of the unit
+    pub radius:             f32,
+    /// construction progress from 0 to 1
+    pub build_progress:     f32,
+    /// current and maximum health
+    pub health:             (f32, f32),
+    /// current and maximum shields
+    pub shield:             (f32, f32),
+    /// current and maximum energy
+    pub energy:             (f32, f32),
+    /// true if the unit is in the air
+    pub is_flying:          bool,
+    /// true if the unit is burrowed
+    pub is_burrowed:        bool,
+    /// current orders
+    pub orders:             Vec<RecordedOrder>,
+    /// minerals left in a mineral field
+    pub mineral_contents:   u32,
+    /// vespene left in a geyser
+    pub vespene_contents:   u32,
+}
+
+/// the player state during a single step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedState {
+    /// the game step
+    pub current_step:       u32,
+    /// minerals in the bank
+    pub minerals:           u32,
+    /// vespene in the bank
+    pub vespene:            u32,
+    /// supply in use
+    pub food_used:          u32,
+    /// supply available
+    pub food_cap:           u32,
+    /// larva available
+    pub larva_count:        u32,
+}
+
+/// everything that changes from step to step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// player state
+    pub state:              RecordedState,
+    /// all visible units
+    pub units:              Vec<RecordedUnit>,
+    /// creep layer
+    pub creep:              RecordedImage,
+    /// visibility layer
+    pub visibility:         RecordedImage,
+}
+
+/// a frame as it is written to disk
+///
+/// creep and visibility rarely change between steps, so a layer is only
+/// written when it differs from the one in the frame before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct StoredFrame {
+    state:                  RecordedState,
+    units:                  Vec<RecordedUnit>,
+    creep:                  Option<RecordedImage>,
+    visibility:             Option<RecordedImage>,
+}
+
+/// the last creep and visibility layers written or read
+#[derive(Debug, Clone, Default)]
+struct StoredLayers {
+    creep:                  Option<RecordedImage>,
+    visibility:             Option<RecordedImage>,
+}
+
+impl StoredLayers {
+    /// leave out the layers that did not change since the last frame
+    fn store(&mut self, frame: RecordedFrame) -> StoredFrame {
+        StoredFrame {
+            state: frame.state,
+            units: frame.units,
+            creep: store_layer(&mut self.creep, frame.creep),
+            visibility: store_layer(&mut self.visibility, frame.visibility),
+        }
+    }
+
+    /// fill in the layers that were left out with the last ones read
+    fn restore(&mut self, frame: StoredFrame) -> Result<RecordedFrame> {
+        Ok(
+            RecordedFrame {
+                state: frame.state,
+                units: frame.units,
+                creep: restore_layer(&mut self.creep, frame.creep)?,
+                visibility: restore_layer(
+                    &mut self.visibility, frame.visibility
+                )?,
+            }
+        )
+    }
+}
+
+fn store_layer(last: &mut Option<RecordedImage>, image: RecordedImage)
+    -> Option<RecordedImage>
+{
+    if last.as_ref() == Some(&image) {
+        None
+    }
+    else {
+        *last = Some(image.clone());
+
+        Some(image)
+    }
+}
+
+fn restore_layer(last: &mut Option<RecordedImage>, image: Option<RecordedImage>)
+    -> Result<RecordedImage>
+{
+    if let Some(image) = image {
+        *last = Some(image);
+    }
+
+    match *last {
+        Some(ref image) => Ok(image.clone()),
+        None => bail!("recording starts without a creep or visibility layer")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordingHeader {
+    version:                u32,
+    terrain:                RecordedTerrain,
+    unit_types:             Vec<RecordedUnitType>,
+}
+
+/// a game recorded with the frame recorder lobe
+///
+/// recordings are a header with the static game data followed by a stream of
+/// frames, all encoded with bincode. creep and visibility are only written on
+/// the frames where they change.
+///
+/// only what the lobes read is recorded. replayed frames leave the following
+/// at their defaults:
+///
+/// * units: everything not in `RecordedUnit`, such as cloak, sensor ranges,
+///   weapon cooldown, add-ons, cargo, harvester counts and buffs
+/// * unit types: everything not in `RecordedUnitType`, such as race, tech
+///   requirements, attributes, movement speed, armor and weapons
+/// * game data: ability, upgrade and buff data
+/// * game state: everything but the step, the bank, supply, larva and units
+/// * map state: everything but creep and visibility
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// static map information
+    pub terrain:            RecordedTerrain,
+    /// unit type costs
+    pub unit_types:         Vec<RecordedUnitType>,
+    /// every recorded step
+    pub frames:             Vec<RecordedFrame>,
+}
+
+impl Recording {
+    /// load a recording from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let header: RecordingHeader = bincode::deserialize_from(
+            &mut reader, bincode::Infinite
+        )?;
+
+        if header.version != RECORDING_VERSION {
+            bail!("unsupported recording version {}", header.version)
+        }
+
+        let mut frames = vec![ ];
+        let mut layers = StoredLayers::default();
+
+        while !reader.fill_buf()?.is_empty() {
+            let frame = bincode::deserialize_from(
+                &mut reader, bincode::Infinite
+            )?;
+
+            frames.push(layers.restore(frame)?);
+        }
+
+        Ok(
+            Self {
+                terrain: header.terrain,
+                unit_types: header.unit_types,
+                frames: frames
+            }
+        )
+    }
+
+    /// write the recording to disk in the same format the recorder uses
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            terrain: self.terrain.clone(),
+            unit_types: self.unit_types.clone(),
+        };
+
+        bincode::serialize_into(&mut writer, &header, bincode::Infinite)?;
+
+        let mut layers = StoredLayers::default();
+
+        for frame in &self.frames {
+            bincode::serialize_into(
+                &mut writer, &layers.store(frame.clone()), bincode::Infinite
+            )?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// convert the recording into frames that can be replayed into a bot
+    pub fn frame_data(&self) -> Result<Vec<Rc<sc2::FrameData>>> {
+        let mut unit_type_data = HashMap::new();
+
+        for t in &self.unit_types {
+            let unit_type = to_unit_type(t.unit_type)?;
+
+            unit_type_data.insert(
+                unit_type,
+                Rc::new(
+                    UnitTypeData {
+                        unit_type: unit_type,
+                        name: t.name.clone(),
+                        mineral_cost: t.mineral_cost,
+                        vespene_cost: t.vespene_cost,
+                        food_required: t.food_required,
+                        food_provided: t.food_provided,
+                        build_time: t.build_time,
+                        ability: to_ability(t.ability)?,
+
+                        ..UnitTypeData::default()
+                    }
+                )
+            );
+        }
+
+        let data = Rc::new(
+            sc2::GameData {
+                unit_type_data: unit_type_data,
+                terrain_info: self.terrain.to_terrain_info(),
+
+                ..sc2::GameData::default()
+            }
+        );
+
+        let mut frames = Vec::with_capacity(self.frames.len());
+
+        for frame in &self.frames {
+            frames.push(Rc::new(frame.to_frame_data(Rc::clone(&data))?));
+        }
+
+        Ok(frames)
+    }
+}
+
+impl RecordedFrame {
+    /// convert the frame into game frame data using the given static data
+    pub fn to_frame_data(&self, data: Rc<sc2::GameData>)
+        -> Result<sc2::FrameData>
+    {
+        let mut units = Vec::with_capacity(self.units.len());
+
+        for u in &self.units {
+            let mut orders = Vec::with_capacity(u.orders.len());
+
+            for o in &u.orders {
+                orders.push(
+                    UnitOrder {
+                        ability: to_ability(o.ability)?,
+                        target: match o.target {
+                            Some(RecordedTarget::Location(x, y)) => Some(
+                                ActionTarget::Location(Point2::new(x, y))
+                            ),
+                            Some(RecordedTarget::Unit(tag)) => Some(
+                                ActionTarget::UnitTag(tag)
+                            ),
+                            None => None
+                        },
+                        progress: o.progress,
+                    }
+                );
+            }
+
+            units.push(
+                Rc::new(
+                    Unit {
+                        tag: u.tag,
+                        unit_type: to_unit_type(u.unit_type)?,
+                        display_type: match u.display_type {
+                            RecordedDisplayType::Visible => {
+                                DisplayType::Visible
+                            },
+                            RecordedDisplayType::Snapshot => {
+                                DisplayType::Snapshot
+                            },
+                            RecordedDisplayType::Hidden => DisplayType::Hidden,
+                        },
+                        alliance: match u.alliance {
+                            RecordedAlliance::Domestic => Alliance::Domestic,
+                            RecordedAlliance::Ally => Alliance::Ally,
+                            RecordedAlliance::Neutral => Alliance::Neutral,
+                            RecordedAlliance::Enemy => Alliance::Enemy,
+                        },
+                        owner: u.owner,
+                        pos: Point3::new(u.pos.0, u.pos.1, u.pos.2),
+                        facing: u.facing,
+                        radius: u.radius,
+                        build_progress: u.build_progress,
+                        health: u.health.0,
+                        health_max: u.health.1,
+                        shield: u.shield.0,
+                        shield_max: u.shield.1,
+                        energy: u.energy.0,
+                        energy_max: u.energy.1,
+                        is_flying: u.is_flying,
+                        is_burrowed: u.is_burrowed,
+                        orders: orders,
+                        mineral_contents: u.mineral_contents,
+                        vespene_contents: u.vespene_contents,
+
+                        ..Unit::default()
+                    }
+                )
+            );
+        }
+
+        Ok(
+            sc2::FrameData {
+                state: sc2::GameState {
+                    current_step: self.state.current_step,
+                    minerals: self.state.minerals,
+                    vespene: self.state.vespene,
+                    food_used: self.state.food_used,
+                    food_cap: self.state.food_cap,
+                    larva_count: self.state.larva_count,
+                    units: units,
+
+                    ..sc2::GameState::default()
+                },
+                data: data,
+                map: sc2::MapState {
+                    creep: to_image(&self.creep),
+                    visibility: to_image(&self.visibility),
+                },
+
+                ..sc2::FrameData::default()
+            }
+        )
+    }
+
+    /// capture the dynamic parts of a game frame
+    pub fn from_frame_data(frame: &sc2::FrameData) -> Self {
+        Self {
+            state: RecordedState {
+                current_step: frame.state.current_step,
+                minerals: frame.state.minerals,
+                vespene: frame.state.vespene,
+                food_used: frame.state.food_used,
+                food_cap: frame.state.food_cap,
+                larva_count: frame.state.larva_count,
+            },
+            units: frame.state.units.iter().map(|u| record_unit(u)).collect(),
+            creep: record_image(&frame.map.creep),
+            visibility: record_image(&frame.map.visibility),
+        }
+    }
+}
+
+impl RecordedTerrain {
+    /// capture the map information of a game
+    pub fn from_terrain_info(terrain: &TerrainInfo) -> Self {
+        Self {
+            width: terrain.width,
+            height: terrain.height,
+            pathing_grid: record_image(&terrain.pathing_grid),
+            placement_grid: record_image(&terrain.placement_grid),
+            terrain_height: record_image(&terrain.terrain_height),
+            playable_min: (terrain.playable_min.x, terrain.playable_min.y),
+            playable_max: (terrain.playable_max.x, terrain.playable_max.y),
+            enemy_start_locations: terrain.enemy_start_locations.iter()
+                .map(|p| (p.x, p.y))
+                .collect(),
+        }
+    }
+
+    /// convert the recorded map information back into terrain info
+    pub fn to_terrain_info(&self) -> TerrainInfo {
+        TerrainInfo {
+            width: self.width,
+            height: self.height,
+            pathing_grid: to_image(&self.pathing_grid),
+            placement_grid: to_image(&self.placement_grid),
+            terrain_height: to_image(&self.terrain_height),
+            playable_min: Point2::new(self.playable_min.0, self.playable_min.1),
+            playable_max: Point2::new(self.playable_max.0, self.playable_max.1),
+            enemy_start_locations: self.enemy_start_locations.iter()
+                .map(|&(x, y)| Point2::new(x, y))
+                .collect(),
+        }
+    }
+}
+
+impl RecordedUnitType {
+    /// capture the recorded parts of a unit type
+    pub fn from_unit_type_data(t: &UnitTypeData) -> Self {
+        Self {
+            unit_type: t.unit_type as u32,
+            name: t.name.clone(),
+            mineral_cost: t.mineral_cost,
+            vespene_cost: t.vespene_cost,
+            food_required: t.food_required,
+            food_provided: t.food_provided,
+            build_time: t.build_time,
+            ability: t.ability as u32,
+        }
+    }
+}
+
+fn record_image(image: &ImageData) -> RecordedImage {
+    RecordedImage {
+        width: image.width,
+        height: image.height,
+        bits_per_pixel: image.bits_per_pixel,
+        data: image.data.clone(),
+    }
+}
+
+fn to_image(image: &RecordedImage) -> ImageData {
+    ImageData {
+        width: image.width,
+        height: image.height,
+        bits_per_pixel: image.bits_per_pixel,
+        data: image.data.clone(),
+    }
+}
+
+fn record_unit(u: &Unit) -> RecordedUnit {
+    RecordedUnit {
+        tag: u.tag,
+        unit_type: u.unit_type as u32,
+        display_type: match u.display_type {
+            DisplayType::Visible => RecordedDisplayType::Visible,
+            DisplayType::Snapshot => RecordedDisplayType::Snapshot,
+            DisplayType::Hidden => RecordedDisplayType::Hidden,
+        },
+        alliance: match u.alliance {
+            Alliance::Domestic => RecordedAlliance::Domestic,
+            Alliance::Ally => RecordedAlliance::Ally,
+            Alliance::Neutral => RecordedAlliance::Neutral,
+            Alliance::Enemy => RecordedAlliance::Enemy,
+        },
+        owner: u.owner,
+        pos: (u.pos.x, u.pos.y, u.pos.z),
+        facing: u.facing,
+        radius: u.radius,
+        build_progress: u.build_progress,
+        health: (u.health, u.health_max),
+        shield: (u.shield, u.shield_max),
+        energy: (u.energy, u.energy_max),
+        is_flying: u.is_flying,
+        is_burrowed: u.is_burrowed,
+        orders: u.orders.iter().map(
+            |o| RecordedOrder {
+                ability: o.ability as u32,
+                target: match o.target {
+                    Some(ActionTarget::Location(p)) => Some(
+                        RecordedTarget::Location(p.x, p.y)
+                    ),
+                    Some(ActionTarget::UnitTag(tag)) => Some(
+                        RecordedTarget::Unit(tag)
+                    ),
+                    None => None
+                },
+                progress: o.progress,
+            }
+        ).collect(),
+        mineral_contents: u.mineral_contents,
+        vespene_contents: u.vespene_contents,
+    }
+}
+
+fn to_unit_type(id: u32) -> Result<UnitType> {
+    Ok(<UnitType as sc2::FromProto<u32>>::from_proto(id)?)
+}
+
+fn to_ability(id: u32) -> Result<Ability> {
+    Ok(<Ability as sc2::FromProto<u32>>::from_proto(id)?)
+}
+
+/// records every frame it receives to a file
+///
+/// the recording can be loaded with `Recording::load` and replayed without
+/// the game.
+pub struct FrameRecorderLobe {
+    writer:                 Option<BufWriter<File>>,
+    wrote_header:           bool,
+    layers:                 StoredLayers,
+}
+
+impl FrameRecorderLobe {
+    /// record to a new file at the given path
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(
+            Self {
+                writer: Some(BufWriter::new(File::create(path)?)),
+                wrote_header: false,
+                layers: StoredLayers::default(),
+            }
+        )
+    }
+
+    fn write_header(&mut self, frame: &sc2::FrameData) -> Result<()> {
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            terrain: RecordedTerrain::from_terrain_info(
+                &frame.data.terrain_info
+            ),
+            unit_types: frame.data.unit_type_data.values()
+                .map(|t| RecordedUnitType::from_unit_type_data(t))
+                .collect(),
+        };
+
+        if let Some(ref mut writer) = self.writer {
+            bincode::serialize_into(writer, &header, bincode::Infinite)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &sc2::FrameData) -> Result<()> {
+        if !self.wrote_header {
+            self.write_header(frame)?;
+            self.wrote_header = true;
+        }
+
+        let stored = self.layers.store(RecordedFrame::from_frame_data(frame));
+
+        if let Some(ref mut writer) = self.writer {
+            bincode::serialize_into(writer, &stored, bincode::Infinite)?;
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+create_lobe_data! {
+    module: frame_recorder,
+
+    req frame: Rc<sc2::FrameData>,
+}
+
+pub use self::frame_recorder::{
+    Input as FrameRecorderInput,
+    Output as FrameRecorderOutput,
+    FeedbackInput as FrameRecorderFeedbackInput,
+    FeedbackOutput as FrameRecorderFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: FrameRecorderLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: FrameRecorderInput,
+    output: FrameRecorderOutput,
+    feedback_input: FrameRecorderFeedbackInput,
+    feedback_output: FrameRecorderFeedbackOutput,
+
+    req frame: FrameData,
+}
+
+impl cortical::Lobe for FrameRecorderLobe {
+    type Input = FrameRecorderInput;
+    type Output = FrameRecorderOutput;
+    type FeedbackInput = FrameRecorderFeedbackInput;
+    type FeedbackOutput = FrameRecorderFeedbackOutput;
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.write_frame(&input.frame).chain_err(
+            || cortical::ErrorKind::LobeError
+        )?;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(FrameRecorderOutput { })
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(FrameRecorderFeedbackOutput { })
+    }
+
+    fn stop(&mut self) -> cortical::Result<()> {
+        self.close().chain_err(|| cortical::ErrorKind::LobeError)?;
+
+        Ok(())
+    }
+}
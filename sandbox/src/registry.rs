@@ -0,0 +1,874 @@
+use std::cell::RefCell;
+use std::collections::{ BTreeMap, HashMap };
+use std::rc::Rc;
+use std::sync::Arc;
+
+use cortical;
+use sc2::data::{ UnitType };
+use tantrum::{ ResourceLobe, FrameForwarderLobe, CommandMergerLobe };
+use toml;
+
+use errors::{ Result, ErrorKind };
+use super::{
+    Budget,
+    DebugToggle,
+    KeliConstraint,
+    KeliData,
+
+    WholeBudgetLobe,
+    IncomeForecastLobe,
+    ReservationLedgerLobe,
+    EvenSplitBudgetLobe,
+    EvenSplitLedgerLobe,
+    WeightedLedgerLobe,
+    PriorityLedgerLobe,
+    RandomDroneMorpherLobe,
+    LarvaMorpherLobe,
+    BuildOrder,
+    BuildOrderLobe,
+    ExpansionLobe,
+    BaseSiteLobe,
+    ExactBaseLocatorLobe,
+    NudgeBaseLocatorLobe,
+    OccupancyGridLobe,
+    PlacementStrategy,
+    DebugWindowLobe,
+    FrameRecorderLobe,
+    CommandLogLobe,
+    CommandTapLobe,
+    LedgerReportLobe,
+};
+
+/// a boxed lobe that can be added to a keli cortex
+pub type KeliNode = Box<cortical::Node<KeliConstraint, KeliData>>;
+
+/// constructs a lobe from a set of parameters
+pub type LobeFactory = Box<Fn(&LobeParams) -> Result<KeliNode>>;
+
+/// the constraints a lobe consumes and produces
+#[derive(Debug, Clone, Default)]
+pub struct LobeSignature {
+    /// constraints accepted from connections
+    pub inputs:             Vec<KeliConstraint>,
+    /// constraints sent along connections
+    pub outputs:            Vec<KeliConstraint>,
+    /// constraints accepted from feedback edges
+    pub feedback_inputs:    Vec<KeliConstraint>,
+    /// constraints sent along feedback edges
+    pub feedback_outputs:   Vec<KeliConstraint>,
+}
+
+/// names of the lobes in a cortex, filled in as the cortex is built
+pub type LobeNames = Rc<RefCell<HashMap<cortical::NodeHdl, String>>>;
+
+/// named parameters passed to a lobe factory
+#[derive(Debug, Clone, Default)]
+pub struct LobeParams {
+    values:                 toml::value::Table,
+    names:                  LobeNames,
+    debug:                  DebugToggle,
+    seed:                   Option<u64>,
+}
+
+impl LobeParams {
+    /// create an empty parameter map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// create a parameter map from a toml table
+    pub fn from_table(values: toml::value::Table) -> Self {
+        Self {
+            values: values,
+            names: LobeNames::default(),
+            debug: DebugToggle::default(),
+            seed: None
+        }
+    }
+
+    /// share the names of the lobes in the cortex being built
+    pub fn with_names(mut self, names: LobeNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// the names of the lobes in the cortex being built
+    pub fn names(&self) -> LobeNames {
+        Rc::clone(&self.names)
+    }
+
+    /// share the toggle for debug drawing
+    pub fn with_debug_toggle(mut self, toggle: DebugToggle) -> Self {
+        self.debug = toggle;
+        self
+    }
+
+    /// the toggle for debug drawing, shared by every lobe in the cortex that
+    /// draws and the debug window
+    pub fn debug_toggle(&self) -> DebugToggle {
+        Arc::clone(&self.debug)
+    }
+
+    /// seed lobes that make random decisions
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// the seed for lobes that make random decisions, if runs should be
+    /// reproducible
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// seed a lobe with its builder method if a seed was given
+    pub fn seeded<L, F>(&self, lobe: L, with_seed: F) -> L
+        where F: FnOnce(L, u64) -> L
+    {
+        match self.seed {
+            Some(seed) => with_seed(lobe, seed),
+            None => lobe
+        }
+    }
+
+    /// set a parameter
+    pub fn with<V: Into<toml::Value>>(mut self, key: &str, value: V) -> Self {
+        self.values.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// get a raw parameter value
+    pub fn get(&self, key: &str) -> Option<&toml::Value> {
+        self.values.get(key)
+    }
+
+    /// get a required string parameter
+    pub fn string(&self, key: &str) -> Result<&str> {
+        match self.values.get(key) {
+            Some(&toml::Value::String(ref s)) => Ok(s),
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected a string".to_string()
+                )
+            ),
+            None => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "missing required parameter".to_string()
+                )
+            )
+        }
+    }
+
+    /// get an optional integer parameter
+    pub fn u32_or(&self, key: &str, default: u32) -> Result<u32> {
+        match self.values.get(key) {
+            Some(value) => parse_u32(key, value),
+            None => Ok(default)
+        }
+    }
+
+    /// get an optional flag parameter
+    pub fn bool_or(&self, key: &str, default: bool) -> Result<bool> {
+        match self.values.get(key) {
+            Some(&toml::Value::Boolean(b)) => Ok(b),
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected true or false".to_string()
+                )
+            ),
+            None => Ok(default)
+        }
+    }
+
+    /// get an optional number parameter
+    pub fn f32_or(&self, key: &str, default: f32) -> Result<f32> {
+        match self.values.get(key) {
+            Some(&toml::Value::Float(f)) => Ok(f as f32),
+            Some(&toml::Value::Integer(i)) => Ok(i as f32),
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected a number".to_string()
+                )
+            ),
+            None => Ok(default)
+        }
+    }
+
+    /// get an optional table of integer parameters, such as weights by name
+    pub fn u32_table(&self, key: &str) -> Result<Vec<(String, u32)>> {
+        let table = match self.values.get(key) {
+            Some(&toml::Value::Table(ref table)) => table,
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected a table".to_string()
+                )
+            ),
+            None => return Ok(vec![ ])
+        };
+
+        let mut values = vec![ ];
+
+        for (name, value) in table {
+            values.push(
+                (name.clone(), parse_u32(&format!("{}.{}", key, name), value)?)
+            );
+        }
+
+        Ok(values)
+    }
+
+    /// get an optional array of strings, such as a list of lobe names
+    pub fn strings(&self, key: &str) -> Result<Vec<&str>> {
+        let array = match self.values.get(key) {
+            Some(&toml::Value::Array(ref array)) => array,
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected an array".to_string()
+                )
+            ),
+            None => return Ok(vec![ ])
+        };
+
+        let mut strings = vec![ ];
+
+        for value in array {
+            match *value {
+                toml::Value::String(ref s) => strings.push(s.as_str()),
+                _ => bail!(
+                    ErrorKind::InvalidParameter(
+                        key.to_string(),
+                        "expected an array of strings".to_string()
+                    )
+                )
+            }
+        }
+
+        Ok(strings)
+    }
+
+    /// get an optional array of tables, such as a list of ranked outputs
+    pub fn tables(&self, key: &str) -> Result<Vec<&toml::value::Table>> {
+        let array = match self.values.get(key) {
+            Some(&toml::Value::Array(ref array)) => array,
+            Some(_) => bail!(
+                ErrorKind::InvalidParameter(
+                    key.to_string(), "expected an array".to_string()
+                )
+            ),
+            None => return Ok(vec![ ])
+        };
+
+        let mut tables = vec![ ];
+
+        for value in array {
+            match *value {
+                toml::Value::Table(ref table) => tables.push(table),
+                _ => bail!(
+                    ErrorKind::InvalidParameter(
+                        key.to_string(),
+                        "expected an array of tables".to_string()
+                    )
+                )
+            }
+        }
+
+        Ok(tables)
+    }
+
+    /// get a required unit type parameter
+    pub fn unit_type(&self, key: &str) -> Result<UnitType> {
+        parse_unit_type(self.string(key)?)
+    }
+
+    /// get an optional placement strategy parameter
+    pub fn placement_or(&self, key: &str, default: PlacementStrategy)
+        -> Result<PlacementStrategy>
+    {
+        match self.get(key) {
+            Some(_) => match self.string(key)? {
+                "random" => Ok(PlacementStrategy::Random),
+                "compact" => Ok(PlacementStrategy::Compact),
+                "spread" => Ok(PlacementStrategy::Spread),
+                "near_minerals" => Ok(PlacementStrategy::NearMinerals),
+                "away_from_minerals" => {
+                    Ok(PlacementStrategy::AwayFromMinerals)
+                },
+                strategy => bail!(
+                    ErrorKind::InvalidParameter(
+                        key.to_string(),
+                        format!("unknown placement strategy {:?}", strategy)
+                    )
+                )
+            },
+            None => Ok(default)
+        }
+    }
+}
+
+struct LobeEntry {
+    signature:              LobeSignature,
+    factory:                LobeFactory,
+}
+
+/// constructs lobes by name
+///
+/// lobe names follow the `Type::constructor` convention, with plain `Type`
+/// referring to the `new` constructor.
+pub struct LobeRegistry {
+    lobes:                  BTreeMap<String, LobeEntry>,
+}
+
+impl LobeRegistry {
+    /// create a registry without any lobes
+    pub fn empty() -> Self {
+        Self { lobes: BTreeMap::new() }
+    }
+
+    /// create a registry with every lobe offered by this crate
+    pub fn new() -> Self {
+        use self::KeliConstraint::*;
+
+        let mut registry = Self::empty();
+
+        registry.register(
+            "FrameForwarderLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ FrameData ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(FrameForwarderLobe::new()))
+        );
+        registry.register(
+            "CommandMergerLobe",
+            LobeSignature {
+                inputs: vec![ Commands ],
+                outputs: vec![ Commands ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(CommandMergerLobe::new()))
+        );
+        registry.register(
+            "ResourceLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ Resources, Commands ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(ResourceLobe::new()))
+        );
+        registry.register(
+            "ResourceLobe::with_debug",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ Resources, Commands ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(ResourceLobe::with_debug()))
+        );
+
+        registry.register(
+            "WholeBudgetLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ Budget ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(WholeBudgetLobe::new()))
+        );
+        registry.register(
+            "IncomeForecastLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ Budget, IncomeRate ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    IncomeForecastLobe::new(
+                        params.u32_or("window", 224)?,
+                        params.f32_or("horizon", 10.0)?
+                    )
+                )
+            )
+        );
+        registry.register(
+            "ReservationLedgerLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, Budget ],
+                outputs: vec![ Budget, Reservations ],
+                feedback_inputs: vec![ Reservations ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    ReservationLedgerLobe::new(params.u32_or("timeout", 448)?)
+                )
+            )
+        );
+        registry.register(
+            "EvenSplitBudgetLobe",
+            LobeSignature {
+                inputs: vec![ Budget ],
+                outputs: vec![ Budget ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(EvenSplitBudgetLobe::new()))
+        );
+        registry.register(
+            "EvenSplitLedgerLobe",
+            LobeSignature {
+                inputs: vec![ Budget, Reservations ],
+                outputs: vec![ Budget, LedgerReport ],
+                feedback_inputs: vec![ Budget, BudgetRequest ],
+                feedback_outputs: vec![ Budget, BudgetRequest ],
+            },
+            |params| {
+                let mut lobe = EvenSplitLedgerLobe::new().with_names(
+                    params.names()
+                );
+
+                for observer in params.strings("observers")? {
+                    lobe = lobe.with_observer(observer);
+                }
+
+                Ok(
+                    Box::new(
+                        params.seeded(lobe, EvenSplitLedgerLobe::with_seed)
+                    )
+                )
+            }
+        );
+
+        registry.register(
+            "WeightedLedgerLobe",
+            LobeSignature {
+                inputs: vec![ Budget, Reservations ],
+                outputs: vec![ Budget, LedgerReport ],
+                feedback_inputs: vec![ Budget, BudgetRequest ],
+                feedback_outputs: vec![ Budget, BudgetRequest ],
+            },
+            |params| {
+                let mut lobe = WeightedLedgerLobe::new().with_names(
+                    params.names()
+                );
+
+                for (name, weight) in params.u32_table("weights")? {
+                    lobe = lobe.with_named_weight(&name, weight);
+                }
+                for observer in params.strings("observers")? {
+                    lobe = lobe.with_observer(observer);
+                }
+
+                Ok(Box::new(lobe))
+            }
+        );
+
+        registry.register(
+            "PriorityLedgerLobe",
+            LobeSignature {
+                inputs: vec![ Budget ],
+                outputs: vec![ Budget, LedgerReport ],
+                feedback_inputs: vec![ Budget, BudgetRequest ],
+                feedback_outputs: vec![ Budget, BudgetRequest ],
+            },
+            |params| {
+                let mut lobe = PriorityLedgerLobe::new().with_names(
+                    params.names()
+                );
+
+                for observer in params.strings("observers")? {
+                    lobe = lobe.with_observer(observer);
+                }
+
+                for output in params.tables("priorities")? {
+                    let name = match output.get("lobe") {
+                        Some(&toml::Value::String(ref name)) => name,
+                        _ => bail!(
+                            ErrorKind::InvalidParameter(
+                                "priorities".to_string(),
+                                "each output needs a lobe name".to_string()
+                            )
+                        )
+                    };
+
+                    lobe = lobe.then_named(name, parse_budget(output)?);
+                }
+
+                Ok(Box::new(lobe))
+            }
+        );
+
+        registry.register(
+            "RandomDroneMorpherLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, Budget, OccupancyGrid ],
+                outputs: vec![ Commands ],
+                feedback_outputs: vec![
+                    Budget, BudgetRequest, Reservations
+                ],
+                ..LobeSignature::default()
+            },
+            |params| {
+                let lobe = RandomDroneMorpherLobe::new(
+                    params.unit_type("unit_type")?
+                )
+                    .with_urgency(params.u32_or("urgency", 1)?)
+                    .with_placement(
+                        params.placement_or(
+                            "placement", PlacementStrategy::default()
+                        )?
+                    )
+                ;
+
+                Ok(
+                    Box::new(
+                        params.seeded(lobe, RandomDroneMorpherLobe::with_seed)
+                    )
+                )
+            }
+        );
+        registry.register(
+            "RandomDroneMorpherLobe::one_and_done",
+            LobeSignature {
+                inputs: vec![ FrameData, Budget, OccupancyGrid ],
+                outputs: vec![ Commands ],
+                feedback_outputs: vec![
+                    Budget, BudgetRequest, Reservations
+                ],
+                ..LobeSignature::default()
+            },
+            |params| {
+                let lobe = RandomDroneMorpherLobe::one_and_done(
+                    params.unit_type("unit_type")?
+                )
+                    .with_urgency(params.u32_or("urgency", 1)?)
+                    .with_placement(
+                        params.placement_or(
+                            "placement", PlacementStrategy::default()
+                        )?
+                    )
+                ;
+
+                Ok(
+                    Box::new(
+                        params.seeded(lobe, RandomDroneMorpherLobe::with_seed)
+                    )
+                )
+            }
+        );
+
+        registry.register(
+            "LarvaMorpherLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, Budget ],
+                outputs: vec![ Commands ],
+                feedback_outputs: vec![ Budget, BudgetRequest ],
+                ..LobeSignature::default()
+            },
+            |params| {
+                let lobe = LarvaMorpherLobe::new(
+                    params.unit_type("unit_type")?
+                ).with_urgency(params.u32_or("urgency", 1)?);
+
+                Ok(
+                    Box::new(params.seeded(lobe, LarvaMorpherLobe::with_seed))
+                )
+            }
+        );
+        registry.register(
+            "BuildOrderLobe",
+            LobeSignature {
+                inputs: vec![
+                    FrameData, Budget, PotentialBaseLocations, OccupancyGrid
+                ],
+                outputs: vec![ Commands, BuildOrderProgress ],
+                feedback_outputs: vec![
+                    Budget, BudgetRequest, Reservations
+                ],
+                ..LobeSignature::default()
+            },
+            |params| {
+                let lobe = BuildOrderLobe::new(
+                    BuildOrder::parse(params.string("order")?)?
+                )
+                    .with_urgency(params.u32_or("urgency", 1)?)
+                    .with_placement(
+                        params.placement_or(
+                            "placement", PlacementStrategy::default()
+                        )?
+                    )
+                ;
+
+                Ok(
+                    Box::new(params.seeded(lobe, BuildOrderLobe::with_seed))
+                )
+            }
+        );
+        registry.register(
+            "ExpansionLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, PotentialBaseLocations, Budget ],
+                outputs: vec![ Commands ],
+                feedback_outputs: vec![
+                    Budget, BudgetRequest, Reservations
+                ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    ExpansionLobe::new().with_urgency(
+                        params.u32_or("urgency", 1)?
+                    )
+                )
+            )
+        );
+        registry.register(
+            "BaseSiteLobe",
+            LobeSignature {
+                inputs: vec![
+                    FrameData,
+                    Resources,
+                    PotentialBaseLocations,
+                    BaseLocationChanges
+                ],
+                outputs: vec![ BaseSites ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(BaseSiteLobe::new()))
+        );
+        registry.register(
+            "ExactBaseLocatorLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, Resources ],
+                outputs: vec![ PotentialBaseLocations, BaseLocationChanges ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(ExactBaseLocatorLobe::new()))
+        );
+        registry.register(
+            "NudgeBaseLocatorLobe",
+            LobeSignature {
+                inputs: vec![ Resources ],
+                outputs: vec![
+                    PotentialBaseLocations, BaseLocationChanges, Commands
+                ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    NudgeBaseLocatorLobe::new()
+                        .with_debug_toggle(params.debug_toggle())
+                )
+            )
+        );
+        registry.register(
+            "NudgeBaseLocatorLobe::with_debug",
+            LobeSignature {
+                inputs: vec![ Resources ],
+                outputs: vec![
+                    PotentialBaseLocations, BaseLocationChanges, Commands
+                ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    NudgeBaseLocatorLobe::with_debug()
+                        .with_debug_toggle(params.debug_toggle())
+                )
+            )
+        );
+        registry.register(
+            "OccupancyGridLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                outputs: vec![ OccupancyGrid ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(OccupancyGridLobe::new()))
+        );
+
+        registry.register(
+            "DebugWindowLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    DebugWindowLobe::new()
+                        .with_debug_toggle(params.debug_toggle())
+                )
+            )
+        );
+        registry.register(
+            "FrameRecorderLobe",
+            LobeSignature {
+                inputs: vec![ FrameData ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(FrameRecorderLobe::create(params.string("path")?)?)
+            )
+        );
+        registry.register(
+            "CommandTapLobe",
+            LobeSignature {
+                inputs: vec![ Commands ],
+                outputs: vec![ LobeCommands ],
+                ..LobeSignature::default()
+            },
+            |_| Ok(Box::new(CommandTapLobe::new()))
+        );
+        registry.register(
+            "CommandLogLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, LobeCommands ],
+                ..LobeSignature::default()
+            },
+            |params| Ok(
+                Box::new(
+                    CommandLogLobe::create(
+                        params.string("path")?, params.names()
+                    )?
+                )
+            )
+        );
+        registry.register(
+            "LedgerReportLobe",
+            LobeSignature {
+                inputs: vec![ FrameData, LedgerReport ],
+                ..LobeSignature::default()
+            },
+            |params| {
+                let mut lobe = LedgerReportLobe::new(params.names());
+
+                if params.bool_or("summary", false)? {
+                    lobe = lobe.with_summary();
+                }
+
+                Ok(
+                    Box::new(
+                        match params.get("csv") {
+                            Some(_) => lobe.with_csv(params.string("csv")?)?,
+                            None => lobe
+                        }
+                    )
+                )
+            }
+        );
+
+        registry
+    }
+
+    /// add a named lobe factory, replacing any existing lobe with that name
+    pub fn register<F>(
+        &mut self, name: &str, signature: LobeSignature, factory: F
+    )
+        where F: Fn(&LobeParams) -> Result<KeliNode> + 'static
+    {
+        self.lobes.insert(
+            name.to_string(),
+            LobeEntry { signature: signature, factory: Box::new(factory) }
+        );
+    }
+
+    /// the names of all registered lobes in sorted order
+    pub fn names(&self) -> Vec<&str> {
+        self.lobes.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// the constraints the named lobe consumes and produces
+    pub fn signature(&self, name: &str) -> Result<&LobeSignature> {
+        match self.lobes.get(name) {
+            Some(entry) => Ok(&entry.signature),
+            None => bail!(ErrorKind::UnknownLobe(name.to_string()))
+        }
+    }
+
+    /// construct the named lobe
+    pub fn create(&self, name: &str, params: &LobeParams) -> Result<KeliNode> {
+        match self.lobes.get(name) {
+            Some(entry) => (entry.factory)(params),
+            None => bail!(ErrorKind::UnknownLobe(name.to_string()))
+        }
+    }
+}
+
+impl Default for LobeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// read a budget from the `minerals`, `vespene`, `food` and `larva` keys of a
+/// table, returning `None` if none of them are present
+pub fn parse_budget(table: &toml::value::Table) -> Result<Option<Budget>> {
+    let mut budget = Budget::default();
+    let mut found = false;
+
+    for (key, field) in vec![
+        ("minerals", &mut budget.minerals),
+        ("vespene", &mut budget.vespene),
+        ("food", &mut budget.food),
+        ("larva", &mut budget.larva),
+    ] {
+        if let Some(value) = table.get(key) {
+            *field = parse_u32(key, value)?;
+            found = true;
+        }
+    }
+
+    if found {
+        Ok(Some(budget))
+    }
+    else {
+        Ok(None)
+    }
+}
+
+/// parse an integer parameter that has to fit in a u32
+fn parse_u32(key: &str, value: &toml::Value) -> Result<u32> {
+    match *value {
+        toml::Value::Integer(i) if i >= 0 && i <= u32::max_value() as i64 => {
+            Ok(i as u32)
+        },
+        _ => bail!(
+            ErrorKind::InvalidParameter(
+                key.to_string(), "expected a non-negative integer".to_string()
+            )
+        )
+    }
+}
+
+/// parse the name of a zerg unit type
+pub fn parse_unit_type(name: &str) -> Result<UnitType> {
+    match name {
+        "ZergDrone" => Ok(UnitType::ZergDrone),
+        "ZergOverlord" => Ok(UnitType::ZergOverlord),
+        "ZergZergling" => Ok(UnitType::ZergZergling),
+        "ZergQueen" => Ok(UnitType::ZergQueen),
+        "ZergRoach" => Ok(UnitType::ZergRoach),
+        "ZergHydralisk" => Ok(UnitType::ZergHydralisk),
+        "ZergMutalisk" => Ok(UnitType::ZergMutalisk),
+        "ZergCorruptor" => Ok(UnitType::ZergCorruptor),
+        "ZergInfestor" => Ok(UnitType::ZergInfestor),
+        "ZergUltralisk" => Ok(UnitType::ZergUltralisk),
+        "ZergViper" => Ok(UnitType::ZergViper),
+
+        "ZergHatchery" => Ok(UnitType::ZergHatchery),
+        "ZergExtractor" => Ok(UnitType::ZergExtractor),
+        "ZergSpawningPool" => Ok(UnitType::ZergSpawningPool),
+        "ZergEvolutionChamber" => Ok(UnitType::ZergEvolutionChamber),
+        "ZergRoachWarren" => Ok(UnitType::ZergRoachWarren),
+        "ZergBanelingNest" => Ok(UnitType::ZergBanelingNest),
+        "ZergHydraliskDen" => Ok(UnitType::ZergHydraliskDen),
+        "ZergSpire" => Ok(UnitType::ZergSpire),
+        "ZergInfestationPit" => Ok(UnitType::ZergInfestationPit),
+        "ZergUltraliskCavern" => Ok(UnitType::ZergUltraliskCavern),
+        "ZergSpineCrawler" => Ok(UnitType::ZergSpineCrawler),
+        "ZergSporeCrawler" => Ok(UnitType::ZergSporeCrawler),
+
+        _ => bail!(ErrorKind::UnknownUnitType(name.to_string()))
+    }
+}
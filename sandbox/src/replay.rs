@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cortical;
+use sc2;
+use tantrum::{ BotCortex };
+
+use errors::{ Result };
+use super::{
+    create_keli_bot,
+    Budget,
+    BuildOrderProgress,
+    LobeBudget,
+    LobeBudgetRequest,
+    KeliCortex,
+    KeliConstraint,
+    KeliData,
+};
+
+/// the commands a bot emitted for a single frame
+#[derive(Debug, Clone)]
+pub struct ReplayStep {
+    /// the game step of the frame
+    pub step:               u32,
+    /// the commands emitted in response to the frame
+    pub commands:           Vec<sc2::Command>,
+}
+
+/// drives a bot with a fixed sequence of frames without launching the game
+///
+/// the first frame starts the bot, every following frame updates it and the
+/// last frame stops it, mirroring what the coordinator does during a game.
+pub struct FrameReplay<A: sc2::Agent> {
+    agent:                  A,
+    frames:                 Vec<Rc<sc2::FrameData>>,
+}
+
+impl<A: sc2::Agent> FrameReplay<A> {
+    /// replay the frames into the given agent
+    pub fn new(agent: A, frames: Vec<Rc<sc2::FrameData>>) -> Self {
+        Self { agent: agent, frames: frames }
+    }
+
+    /// the agent being driven
+    pub fn agent(&self) -> &A {
+        &self.agent
+    }
+
+    /// feed every frame to the agent and collect the emitted commands
+    pub fn run(&mut self) -> Result<Vec<ReplayStep>> {
+        let mut steps = Vec::with_capacity(self.frames.len());
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            let commands = if i == 0 {
+                self.agent.start(Rc::clone(frame))?
+            }
+            else {
+                self.agent.update(Rc::clone(frame))?
+            };
+
+            steps.push(
+                ReplayStep {
+                    step: frame.state.current_step,
+                    commands: commands
+                }
+            );
+        }
+
+        if let Some(frame) = self.frames.last() {
+            self.agent.stop(Rc::clone(frame))?;
+        }
+
+        Ok(steps)
+    }
+}
+
+impl FrameReplay<BotCortex> {
+    /// wrap the keli cortex in a bot and replay the frames into it
+    pub fn from_cortex(cortex: KeliCortex, frames: Vec<Rc<sc2::FrameData>>)
+        -> Result<Self>
+    {
+        Ok(Self::new(create_keli_bot(cortex)?, frames))
+    }
+}
+
+/// the budget seen by a probe during a single step
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BudgetProbeRecord {
+    /// the budget passed down to the lobes below the probe
+    pub allotted:           Budget,
+    /// the spending reported back up through the probe
+    pub spent:              Budget,
+}
+
+/// shared log of everything a budget probe has seen
+pub type BudgetProbeLog = Rc<RefCell<Vec<BudgetProbeRecord>>>;
+
+/// passes a budget through unchanged while recording it
+///
+/// insert it on any budget edge (with matching feedback) to assert on the
+/// budget and spending that flow across it.
+pub struct BudgetProbeLobe {
+    hdl:                    Option<cortical::NodeHdl>,
+
+    allotted:               Budget,
+    spent:                  Budget,
+    requests:               Vec<LobeBudgetRequest>,
+
+    log:                    BudgetProbeLog,
+}
+
+impl BudgetProbeLobe {
+    /// create a probe and the log it records to
+    pub fn new() -> (Self, BudgetProbeLog) {
+        let log = Rc::new(RefCell::new(vec![ ]));
+
+        (Self::with_log(Rc::clone(&log)), log)
+    }
+
+    /// create a probe that records to an existing log
+    ///
+    /// useful when the probe is built by a lobe factory, since the log can be
+    /// created up front and shared with the factory.
+    pub fn with_log(log: BudgetProbeLog) -> Self {
+        Self {
+            hdl: None,
+
+            allotted: Budget::default(),
+            spent: Budget::default(),
+            requests: vec![ ],
+
+            log: log,
+        }
+    }
+}
+
+create_lobe_data! {
+    module: budget_probe,
+
+    req budget: LobeBudget,
+    out budget: LobeBudget,
+
+    fbk var each_spent: LobeBudget,
+    fbk out spent: LobeBudget,
+
+    fbk var each_request: LobeBudgetRequest,
+    fbk out request: LobeBudgetRequest,
+}
+
+pub use self::budget_probe::{
+    Input as BudgetProbeInput,
+    Output as BudgetProbeOutput,
+    FeedbackInput as BudgetProbeFeedbackInput,
+    FeedbackOutput as BudgetProbeFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: BudgetProbeLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: BudgetProbeInput,
+    output: BudgetProbeOutput,
+    feedback_input: BudgetProbeFeedbackInput,
+    feedback_output: BudgetProbeFeedbackOutput,
+
+    req budget: Budget,
+    out budget: Budget,
+
+    fbk var each_spent: Budget,
+    fbk out spent: Budget,
+
+    fbk var each_request: BudgetRequest,
+    fbk out request: BudgetRequest,
+}
+
+impl cortical::Lobe for BudgetProbeLobe {
+    type Input = BudgetProbeInput;
+    type Output = BudgetProbeOutput;
+    type FeedbackInput = BudgetProbeFeedbackInput;
+    type FeedbackOutput = BudgetProbeFeedbackOutput;
+
+    fn start(
+        &mut self,
+        hdl: cortical::NodeHdl,
+        _: Vec<cortical::NodeHdl>,
+        _: Vec<cortical::NodeHdl>
+    )
+        -> cortical::Result<()>
+    {
+        self.hdl = Some(hdl);
+
+        Ok(())
+    }
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.allotted = input.budget.budget;
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(
+            BudgetProbeOutput {
+                budget: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.allotted,
+                }
+            }
+        )
+    }
+
+    fn feedback(&mut self, input: Self::FeedbackInput) -> cortical::Result<()>
+    {
+        self.spent = input.each_spent.iter().fold(
+            Budget::default(), |acc, spent| acc + spent.budget
+        );
+        self.requests = input.each_request;
+
+        self.log.borrow_mut().push(
+            BudgetProbeRecord { allotted: self.allotted, spent: self.spent }
+        );
+
+        Ok(())
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(
+            BudgetProbeFeedbackOutput {
+                spent: LobeBudget {
+                    lobe: self.hdl.unwrap(),
+                    budget: self.spent,
+                },
+                request: LobeBudgetRequest::combine(
+                    self.hdl.unwrap(), &self.requests
+                ),
+            }
+        )
+    }
+}
+
+/// shared log of the progress reported by a build order, one entry per step
+pub type BuildOrderProbeLog = Rc<RefCell<Vec<BuildOrderProgress>>>;
+
+/// records the progress of a build order lobe
+///
+/// connect it to the progress output of a build order lobe to assert on the
+/// item it is working on and what is blocking it.
+pub struct BuildOrderProbeLobe {
+    log:                    BuildOrderProbeLog,
+}
+
+impl BuildOrderProbeLobe {
+    /// create a probe and the log it records to
+    pub fn new() -> (Self, BuildOrderProbeLog) {
+        let log = Rc::new(RefCell::new(vec![ ]));
+
+        (Self::with_log(Rc::clone(&log)), log)
+    }
+
+    /// create a probe that records to an existing log
+    pub fn with_log(log: BuildOrderProbeLog) -> Self {
+        Self { log: log }
+    }
+}
+
+create_lobe_data! {
+    module: build_order_probe,
+
+    req progress: BuildOrderProgress,
+}
+
+pub use self::build_order_probe::{
+    Input as BuildOrderProbeInput,
+    Output as BuildOrderProbeOutput,
+    FeedbackInput as BuildOrderProbeFeedbackInput,
+    FeedbackOutput as BuildOrderProbeFeedbackOutput,
+};
+
+constrain_lobe! {
+    lobe: BuildOrderProbeLobe,
+    constraint: KeliConstraint,
+    data: KeliData,
+
+    input: BuildOrderProbeInput,
+    output: BuildOrderProbeOutput,
+    feedback_input: BuildOrderProbeFeedbackInput,
+    feedback_output: BuildOrderProbeFeedbackOutput,
+
+    req progress: BuildOrderProgress,
+}
+
+impl cortical::Lobe for BuildOrderProbeLobe {
+    type Input = BuildOrderProbeInput;
+    type Output = BuildOrderProbeOutput;
+    type FeedbackInput = BuildOrderProbeFeedbackInput;
+    type FeedbackOutput = BuildOrderProbeFeedbackOutput;
+
+    fn update(&mut self, input: Self::Input) -> cortical::Result<()> {
+        self.log.borrow_mut().push(input.progress);
+
+        Ok(())
+    }
+
+    fn tailor_output(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::Output>
+    {
+        Ok(BuildOrderProbeOutput { })
+    }
+
+    fn tailor_feedback(&mut self, _: cortical::NodeHdl)
+        -> cortical::Result<Self::FeedbackOutput>
+    {
+        Ok(BuildOrderProbeFeedbackOutput { })
+    }
+}
@@ -0,0 +1,23 @@
+use rand;
+use rand::{ SeedableRng, XorShiftRng };
+
+/// the random number generator used by lobes
+pub type LobeRng = XorShiftRng;
+
+/// create a generator that produces the same sequence for the same seed
+pub fn seeded_rng(seed: u64) -> LobeRng {
+    // xorshift must not be seeded with all zeros, so mix in some constants
+    XorShiftRng::from_seed(
+        [
+            seed as u32,
+            (seed >> 32) as u32,
+            0x9E37_79B9,
+            0x7F4A_7C15
+        ]
+    )
+}
+
+/// create a generator seeded from the system's entropy
+pub fn unseeded_rng() -> LobeRng {
+    rand::weak_rng()
+}
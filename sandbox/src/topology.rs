@@ -0,0 +1,420 @@
+use std::collections::{ HashMap };
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use cortical;
+use cortical::{ CortexBuilder };
+use rand::{ Rng };
+use toml;
+
+use errors::{ Result, ErrorKind };
+use super::{
+    DebugToggle,
+    KeliCortex,
+    KeliConstraint,
+
+    LobeRegistry,
+    LobeNames,
+    LobeParams,
+    LobeSignature,
+
+    seeded_rng,
+};
+
+/// declarative description of a keli cortex
+///
+/// lobes are declared by name and type, then wired together with connections
+/// and feedback edges.
+///
+/// ```toml
+/// input = "frame_forwarder"
+/// output = "command_merger"
+///
+/// [[lobes]]
+/// name = "spawning_pool"
+/// type = "RandomDroneMorpherLobe"
+/// constructor = "one_and_done"
+/// params = { unit_type = "ZergSpawningPool" }
+///
+/// [[connections]]
+/// from = "frame_forwarder"
+/// to = "spawning_pool"
+/// constraints = [ "FrameData" ]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Topology {
+    /// the lobe that receives frame data from the bot
+    pub input:              String,
+    /// the lobe whose commands are sent to the game
+    pub output:             String,
+    /// seed for every lobe that makes random decisions
+    pub seed:               Option<u64>,
+
+    /// the lobes in the cortex
+    #[serde(default)]
+    pub lobes:              Vec<LobeSpec>,
+    /// forward connections between lobes
+    #[serde(default)]
+    pub connections:        Vec<EdgeSpec>,
+    /// feedback connections between lobes
+    #[serde(default)]
+    pub feedback:           Vec<EdgeSpec>,
+}
+
+/// a single lobe declaration
+#[derive(Debug, Clone, Deserialize)]
+pub struct LobeSpec {
+    /// unique name used to refer to this lobe in edges
+    pub name:               String,
+    /// the type of lobe to construct
+    #[serde(rename = "type")]
+    pub kind:               String,
+    /// the constructor to use (defaults to "new")
+    pub constructor:        Option<String>,
+    /// constructor parameters
+    #[serde(default)]
+    pub params:             toml::value::Table,
+}
+
+/// a connection or feedback edge between two lobes
+#[derive(Debug, Clone, Deserialize)]
+pub struct EdgeSpec {
+    /// the name of the source lobe
+    pub from:               String,
+    /// the name of the destination lobe
+    pub to:                 String,
+    /// the constraints carried along this edge
+    pub constraints:        Vec<String>,
+}
+
+impl LobeSpec {
+    /// the registry name of this lobe, i.e. `Type` or `Type::constructor`
+    pub fn registry_name(&self) -> String {
+        match self.constructor {
+            Some(ref constructor) if constructor != "new" => format!(
+                "{}::{}", self.kind, constructor
+            ),
+            _ => self.kind.clone()
+        }
+    }
+}
+
+impl Topology {
+    /// parse a topology from a toml string
+    pub fn from_str(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// read and parse a topology file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut s = String::new();
+        File::open(path)?.read_to_string(&mut s)?;
+
+        Self::from_str(&s)
+    }
+
+    /// record every frame received by the input lobe to the given path
+    pub fn record_frames(&mut self, path: &Path) {
+        let mut params = toml::value::Table::new();
+        params.insert(
+            "path".to_string(),
+            toml::Value::String(path.to_string_lossy().into_owned())
+        );
+
+        self.lobes.push(
+            LobeSpec {
+                name: "frame_recorder".to_string(),
+                kind: "FrameRecorderLobe".to_string(),
+                constructor: None,
+                params: params,
+            }
+        );
+        self.connections.push(
+            EdgeSpec {
+                from: self.input.clone(),
+                to: "frame_recorder".to_string(),
+                constraints: vec![ "FrameData".to_string() ],
+            }
+        );
+    }
+
+    /// log every command sent to the output lobe to the given path
+    pub fn log_commands(&mut self, path: &Path) {
+        let mut params = toml::value::Table::new();
+        params.insert(
+            "path".to_string(),
+            toml::Value::String(path.to_string_lossy().into_owned())
+        );
+
+        let sources: Vec<String> = self.connections.iter()
+            .filter(
+                |edge| edge.to == self.output
+                    && edge.constraints.iter().any(|c| c == "Commands")
+            )
+            .map(|edge| edge.from.clone())
+            .collect()
+        ;
+
+        self.lobes.push(
+            LobeSpec {
+                name: "command_log".to_string(),
+                kind: "CommandLogLobe".to_string(),
+                constructor: None,
+                params: params,
+            }
+        );
+        self.connections.push(
+            EdgeSpec {
+                from: self.input.clone(),
+                to: "command_log".to_string(),
+                constraints: vec![ "FrameData".to_string() ],
+            }
+        );
+
+        // every source gets its own tap so the log knows who sent what
+        for source in sources {
+            let tap = format!("{}_command_tap", source);
+
+            self.lobes.push(
+                LobeSpec {
+                    name: tap.clone(),
+                    kind: "CommandTapLobe".to_string(),
+                    constructor: None,
+                    params: Default::default(),
+                }
+            );
+            self.connections.push(
+                EdgeSpec {
+                    from: source,
+                    to: tap.clone(),
+                    constraints: vec![ "Commands".to_string() ],
+                }
+            );
+            self.connections.push(
+                EdgeSpec {
+                    from: tap,
+                    to: "command_log".to_string(),
+                    constraints: vec![ "LobeCommands".to_string() ],
+                }
+            );
+        }
+    }
+
+    /// report on every ledger in the cortex
+    ///
+    /// ledgers are found by looking up which lobes produce ledger reports in
+    /// the registry. each one watches the report lobe as an observer, so it
+    /// isn't counted as one of the ledger's spenders. if `summary` is set,
+    /// the spending of every ledger is printed when the game ends, and if a
+    /// path is given, the history of every step is written to it as CSV.
+    pub fn report_ledgers(
+        &mut self,
+        registry: &LobeRegistry,
+        csv: Option<&Path>,
+        summary: bool
+    )
+        -> Result<()>
+    {
+        let mut params = toml::value::Table::new();
+
+        params.insert("summary".to_string(), toml::Value::Boolean(summary));
+
+        if let Some(path) = csv {
+            params.insert(
+                "csv".to_string(),
+                toml::Value::String(path.to_string_lossy().into_owned())
+            );
+        }
+
+        let mut ledgers = vec![ ];
+
+        for spec in &mut self.lobes {
+            let signature = registry.signature(&spec.registry_name())?;
+
+            if !signature.outputs.contains(&KeliConstraint::LedgerReport) {
+                continue
+            }
+
+            let observers = spec.params.entry("observers".to_string())
+                .or_insert_with(|| toml::Value::Array(vec![ ]))
+            ;
+
+            match *observers {
+                toml::Value::Array(ref mut observers) => observers.push(
+                    toml::Value::String("ledger_report".to_string())
+                ),
+                _ => bail!(
+                    ErrorKind::InvalidParameter(
+                        "observers".to_string(),
+                        "expected an array".to_string()
+                    )
+                )
+            }
+
+            ledgers.push(spec.name.clone());
+        }
+
+        self.lobes.push(
+            LobeSpec {
+                name: "ledger_report".to_string(),
+                kind: "LedgerReportLobe".to_string(),
+                constructor: None,
+                params: params,
+            }
+        );
+        self.connections.push(
+            EdgeSpec {
+                from: self.input.clone(),
+                to: "ledger_report".to_string(),
+                constraints: vec![ "FrameData".to_string() ],
+            }
+        );
+
+        for ledger in ledgers {
+            self.connections.push(
+                EdgeSpec {
+                    from: ledger,
+                    to: "ledger_report".to_string(),
+                    constraints: vec![ "LedgerReport".to_string() ],
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    /// construct every lobe and wire them together into a keli cortex
+    pub fn build(&self) -> Result<KeliCortex> {
+        self.build_with(&LobeRegistry::new())
+    }
+
+    /// build the cortex using lobes from the given registry
+    pub fn build_with(&self, registry: &LobeRegistry) -> Result<KeliCortex> {
+        let mut builder = CortexBuilder::new();
+        let mut nodes = HashMap::new();
+        let names = LobeNames::default();
+        let debug = DebugToggle::default();
+        // each lobe gets its own seed drawn in declaration order
+        let mut seeds = self.seed.map(seeded_rng);
+
+        for spec in &self.lobes {
+            if nodes.contains_key(&spec.name) {
+                bail!(
+                    ErrorKind::InvalidTopology(
+                        format!("lobe {:?} declared more than once", spec.name)
+                    )
+                )
+            }
+
+            let name = spec.registry_name();
+            let signature = registry.signature(&name)?.clone();
+            let mut params = LobeParams::from_table(spec.params.clone())
+                .with_names(Rc::clone(&names))
+                .with_debug_toggle(Arc::clone(&debug))
+            ;
+
+            if let Some(ref mut rng) = seeds {
+                params = params.with_seed(rng.gen());
+            }
+
+            let node = registry.create(&name, &params)?;
+            let hdl = builder.add_node(node);
+
+            names.borrow_mut().insert(hdl, spec.name.clone());
+            nodes.insert(spec.name.clone(), (hdl, signature));
+        }
+
+        for edge in &self.connections {
+            let (from, to, constraints) = resolve_edge(&nodes, edge, false)?;
+
+            builder.connect(from, to, constraints)?;
+        }
+        for edge in &self.feedback {
+            let (from, to, constraints) = resolve_edge(&nodes, edge, true)?;
+
+            builder.feedback(from, to, constraints)?;
+        }
+
+        builder.set_input(lookup(&nodes, &self.input)?.0);
+        builder.set_output(lookup(&nodes, &self.output)?.0);
+
+        Ok(KeliCortex(builder.build()?))
+    }
+}
+
+fn lookup<'a>(
+    nodes: &'a HashMap<String, (cortical::NodeHdl, LobeSignature)>,
+    name: &str
+)
+    -> Result<&'a (cortical::NodeHdl, LobeSignature)>
+{
+    match nodes.get(name) {
+        Some(node) => Ok(node),
+        None => bail!(
+            ErrorKind::InvalidTopology(format!("no lobe named {:?}", name))
+        )
+    }
+}
+
+fn resolve_edge(
+    nodes: &HashMap<String, (cortical::NodeHdl, LobeSignature)>,
+    edge: &EdgeSpec,
+    is_feedback: bool
+)
+    -> Result<(cortical::NodeHdl, cortical::NodeHdl, Vec<KeliConstraint>)>
+{
+    let &(from, ref from_sig) = lookup(nodes, &edge.from)?;
+    let &(to, ref to_sig) = lookup(nodes, &edge.to)?;
+
+    let (produced, consumed) = if is_feedback {
+        (&from_sig.feedback_outputs, &to_sig.feedback_inputs)
+    }
+    else {
+        (&from_sig.outputs, &to_sig.inputs)
+    };
+
+    let mut constraints = vec![ ];
+
+    for name in &edge.constraints {
+        let constraint = parse_constraint(name)?;
+
+        if !produced.contains(&constraint) || !consumed.contains(&constraint) {
+            bail!(
+                ErrorKind::ConstraintMismatch(
+                    edge.from.clone(), edge.to.clone(), name.clone()
+                )
+            )
+        }
+
+        constraints.push(constraint);
+    }
+
+    Ok((from, to, constraints))
+}
+
+/// parse the name of a keli constraint
+pub fn parse_constraint(name: &str) -> Result<KeliConstraint> {
+    match name {
+        "FrameData" => Ok(KeliConstraint::FrameData),
+        "Resources" => Ok(KeliConstraint::Resources),
+        "PotentialBaseLocations" => Ok(
+            KeliConstraint::PotentialBaseLocations
+        ),
+        "BaseLocationChanges" => Ok(KeliConstraint::BaseLocationChanges),
+        "BaseSites" => Ok(KeliConstraint::BaseSites),
+        "Budget" => Ok(KeliConstraint::Budget),
+        "BudgetRequest" => Ok(KeliConstraint::BudgetRequest),
+        "IncomeRate" => Ok(KeliConstraint::IncomeRate),
+        "Reservations" => Ok(KeliConstraint::Reservations),
+        "LedgerReport" => Ok(KeliConstraint::LedgerReport),
+        "BuildOrderProgress" => Ok(KeliConstraint::BuildOrderProgress),
+        "OccupancyGrid" => Ok(KeliConstraint::OccupancyGrid),
+        "LobeCommands" => Ok(KeliConstraint::LobeCommands),
+        "Commands" => Ok(KeliConstraint::Commands),
+
+        _ => bail!(ErrorKind::UnknownConstraint(name.to_string()))
+    }
+}
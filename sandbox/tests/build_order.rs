@@ -0,0 +1,417 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    BuildBlocker,
+    BuildOrder,
+    BuildOrderProbeLobe,
+    BuildOrderProbeLog,
+    BuildOrderProgress,
+    BuildStep,
+    BuildTrigger,
+    FrameReplay,
+    KeliConstraint,
+    LobeRegistry,
+    LobeSignature,
+    RecordedFrame,
+    RecordedOrder,
+    RecordedState,
+    RecordedUnit,
+    ReplayStep,
+    Topology,
+};
+use sc2::data::{ UnitType };
+
+use common::{
+    edge,
+    filled,
+    image,
+    lobe,
+    register_budget_probe,
+    unit,
+    unit_type,
+};
+
+const DRONE_ABILITY: u32 = 1342;
+const OVERLORD_ABILITY: u32 = 1344;
+const POOL_ABILITY: u32 = 1155;
+const HATCHERY_ABILITY: u32 = 1152;
+
+const HATCHERY: u64 = 1;
+const DRONE: u64 = 2;
+const LARVA: [u64; 3] = [ 3, 4, 5 ];
+
+/// a hatchery, a drone and three larva, plus any extra units, on a buildable
+/// map covered in creep with plenty of minerals in the bank
+fn frame(step: u32, food_used: u32, extra: Vec<RecordedUnit>)
+    -> RecordedFrame
+{
+    let mut units = vec![
+        unit(HATCHERY, UnitType::ZergHatchery, 50.0, 50.0),
+        unit(DRONE, UnitType::ZergDrone, 45.0, 45.0),
+    ];
+
+    units.extend(
+        LARVA.iter().map(|&tag| unit(tag, UnitType::ZergLarva, 49.0, 48.0))
+    );
+    units.extend(extra);
+
+    RecordedFrame {
+        state: RecordedState {
+            current_step: step,
+            minerals: 1000,
+            vespene: 0,
+            food_used: food_used,
+            food_cap: 30,
+            larva_count: LARVA.len() as u32,
+        },
+        units: units,
+        creep: filled(),
+        visibility: image(),
+    }
+}
+
+fn frames(frames: Vec<RecordedFrame>) -> Vec<Rc<sc2::FrameData>> {
+    common::frames(
+        vec![
+            unit_type(UnitType::ZergDrone, "Drone", 50, 0, 1.0, DRONE_ABILITY),
+            unit_type(
+                UnitType::ZergOverlord,
+                "Overlord",
+                100,
+                0,
+                0.0,
+                OVERLORD_ABILITY
+            ),
+            unit_type(
+                UnitType::ZergSpawningPool,
+                "SpawningPool",
+                200,
+                0,
+                0.0,
+                POOL_ABILITY
+            ),
+            unit_type(
+                UnitType::ZergHatchery,
+                "Hatchery",
+                300,
+                0,
+                0.0,
+                HATCHERY_ABILITY
+            ),
+        ],
+        frames
+    )
+}
+
+/// whole budget -> budget probe -> build order -> progress probe
+fn topology(order: &str) -> Topology {
+    let mut params = toml::value::Table::new();
+    params.insert("order".to_string(), toml::Value::String(order.to_string()));
+
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("budget", "BudgetProbe", Default::default()),
+            lobe("build_order", "BuildOrderLobe", params),
+            lobe("progress", "ProgressProbe", Default::default()),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "build_order", &[ "FrameData" ]),
+            edge("whole_budget", "budget", &[ "Budget" ]),
+            edge("budget", "build_order", &[ "Budget" ]),
+            edge("build_order", "progress", &[ "BuildOrderProgress" ]),
+            edge("build_order", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![
+            edge("build_order", "budget", &[ "Budget", "BudgetRequest" ]),
+        ],
+    }
+}
+
+/// replay the frames through the build order and return what it reported
+fn run(order: &str, recorded: Vec<RecordedFrame>)
+    -> (Vec<ReplayStep>, Vec<BuildOrderProgress>)
+{
+    let mut registry = LobeRegistry::new();
+
+    register_budget_probe(&mut registry, "BudgetProbe");
+
+    let progress = BuildOrderProbeLog::default();
+    let shared_progress = Rc::clone(&progress);
+
+    registry.register(
+        "ProgressProbe",
+        LobeSignature {
+            inputs: vec![ KeliConstraint::BuildOrderProgress ],
+            ..LobeSignature::default()
+        },
+        move |_| Ok(
+            Box::new(BuildOrderProbeLobe::with_log(Rc::clone(&shared_progress)))
+        )
+    );
+
+    let cortex = topology(order).build_with(&registry).unwrap();
+
+    let mut replay = FrameReplay::from_cortex(cortex, frames(recorded))
+        .unwrap();
+    let steps = replay.run().unwrap();
+
+    let progress = progress.borrow().clone();
+
+    (steps, progress)
+}
+
+/// the ability and units of the only command issued during a step
+fn only_command(step: &ReplayStep) -> (u32, Vec<u64>) {
+    assert_eq!(step.commands.len(), 1, "step {}", step.step);
+
+    match step.commands[0] {
+        sc2::Command::Action { ref units, ability, .. } => (
+            ability as u32, units.iter().map(|u| u.tag).collect()
+        ),
+        ref command => panic!("unexpected command {:?}", command),
+    }
+}
+
+#[test]
+fn parses_supply_and_time_triggers() {
+    let order = BuildOrder::parse(
+        "13 overlord, 16 hatch, 18 gas, 17 pool, 2:30 queen, 3:05 ZergRoach"
+    ).unwrap();
+
+    let expected = vec![
+        (BuildTrigger::Supply(13), UnitType::ZergOverlord),
+        (BuildTrigger::Supply(16), UnitType::ZergHatchery),
+        (BuildTrigger::Supply(18), UnitType::ZergExtractor),
+        (BuildTrigger::Supply(17), UnitType::ZergSpawningPool),
+        (BuildTrigger::Time(150), UnitType::ZergQueen),
+        (BuildTrigger::Time(185), UnitType::ZergRoach),
+    ];
+
+    let steps: Vec<BuildStep> = expected.into_iter().map(
+        |(trigger, unit_type)| BuildStep {
+            trigger: trigger, unit_type: unit_type
+        }
+    ).collect();
+
+    assert_eq!(order.steps(), &steps);
+}
+
+#[test]
+fn parse_ignores_spacing_and_empty_items() {
+    let order = BuildOrder::parse(" 13   Spawning   Pool ,, 0:45 drone, ")
+        .unwrap();
+
+    assert_eq!(
+        order.steps(),
+        &vec![
+            BuildStep {
+                trigger: BuildTrigger::Supply(13),
+                unit_type: UnitType::ZergSpawningPool,
+            },
+            BuildStep {
+                trigger: BuildTrigger::Time(45),
+                unit_type: UnitType::ZergDrone,
+            },
+        ]
+    );
+}
+
+#[test]
+fn parse_rejects_invalid_items() {
+    for order in &[
+        "", " , ", "13", "pool", "13 warpgate", "1:60 pool", "-1 pool",
+        "x:30 pool", "13 pool, gas",
+    ] {
+        assert!(BuildOrder::parse(order).is_err(), "{:?}", order);
+    }
+}
+
+#[test]
+fn builds_drones_until_supply_is_reached() {
+    let (steps, progress) = run(
+        "12 pool", (0..4).map(|step| frame(step, 10, vec![ ])).collect()
+    );
+
+    for step in &steps {
+        let (ability, units) = only_command(step);
+
+        assert_eq!(ability, DRONE_ABILITY, "step {}", step.step);
+        assert!(LARVA.contains(&units[0]), "step {}", step.step);
+    }
+
+    assert_eq!(progress.len(), steps.len());
+
+    for p in &progress {
+        assert_eq!(p.step, 0);
+        assert_eq!(p.total, 1);
+        assert_eq!(
+            p.blocked, Some(BuildBlocker::Supply { current: 10, required: 12 })
+        );
+    }
+}
+
+#[test]
+fn waits_for_time_trigger() {
+    let (steps, progress) = run(
+        "0:10 pool",
+        vec![
+            frame(0, 10, vec![ ]),
+            frame(120, 10, vec![ ]),
+            frame(230, 10, vec![ ]),
+        ]
+    );
+
+    assert!(steps[0].commands.is_empty());
+    assert!(steps[1].commands.is_empty());
+    assert_eq!(only_command(&steps[2]), (POOL_ABILITY, vec![ DRONE ]));
+
+    assert_eq!(
+        progress[0].blocked,
+        Some(BuildBlocker::Time { current: 0, required: 10 })
+    );
+    assert_eq!(
+        progress[1].blocked,
+        Some(BuildBlocker::Time { current: 5, required: 10 })
+    );
+    assert_eq!(progress[2].blocked, Some(BuildBlocker::Pending));
+    assert_eq!(progress[2].step, 0);
+}
+
+#[test]
+fn waits_for_ordered_structure_and_orders_it_again_after_timeout() {
+    let (steps, progress) = run(
+        "12 pool",
+        vec![
+            frame(0, 12, vec![ ]),
+            frame(1, 12, vec![ ]),
+            frame(2, 12, vec![ ]),
+            frame(1000, 12, vec![ ]),
+        ]
+    );
+
+    assert_eq!(only_command(&steps[0]), (POOL_ABILITY, vec![ DRONE ]));
+    assert!(steps[1].commands.is_empty());
+    assert!(steps[2].commands.is_empty());
+    assert_eq!(only_command(&steps[3]), (POOL_ABILITY, vec![ DRONE ]));
+
+    for p in &progress {
+        assert_eq!(p.step, 0);
+        assert_eq!(p.blocked, Some(BuildBlocker::Pending));
+        assert_eq!(
+            p.current,
+            Some(
+                BuildStep {
+                    trigger: BuildTrigger::Supply(12),
+                    unit_type: UnitType::ZergSpawningPool,
+                }
+            )
+        );
+    }
+}
+
+#[test]
+fn moves_on_once_items_show_up() {
+    let pool = unit(10, UnitType::ZergSpawningPool, 40.0, 40.0);
+
+    let mut egg = unit(11, UnitType::ZergEgg, 49.0, 48.0);
+    egg.orders.push(
+        RecordedOrder { ability: OVERLORD_ABILITY, target: None, progress: 0.0 }
+    );
+
+    let (steps, progress) = run(
+        "12 pool, 12 overlord",
+        vec![
+            frame(0, 12, vec![ ]),
+            frame(1, 12, vec![ pool.clone() ]),
+            frame(2, 12, vec![ pool.clone(), egg ]),
+        ]
+    );
+
+    assert_eq!(only_command(&steps[0]), (POOL_ABILITY, vec![ DRONE ]));
+    assert_eq!(progress[0].step, 0);
+    assert_eq!(progress[0].blocked, Some(BuildBlocker::Pending));
+
+    let (ability, units) = only_command(&steps[1]);
+
+    assert_eq!(ability, OVERLORD_ABILITY);
+    assert!(LARVA.contains(&units[0]));
+    assert_eq!(progress[1].step, 1);
+    assert_eq!(progress[1].blocked, Some(BuildBlocker::Pending));
+
+    assert!(steps[2].commands.is_empty());
+    assert_eq!(progress[2].step, 2);
+    assert_eq!(progress[2].total, 2);
+    assert_eq!(progress[2].current, None);
+    assert_eq!(progress[2].blocked, None);
+}
+
+#[test]
+fn builds_an_overlord_when_supply_blocked() {
+    let mut blocked = frame(0, 10, vec![ ]);
+    blocked.state.food_cap = 10;
+
+    let mut egg = unit(11, UnitType::ZergEgg, 49.0, 48.0);
+    egg.orders.push(
+        RecordedOrder { ability: OVERLORD_ABILITY, target: None, progress: 0.0 }
+    );
+
+    let mut morphing = frame(1, 10, vec![ egg ]);
+    morphing.state.food_cap = 10;
+
+    let (steps, progress) = run("12 pool", vec![ blocked, morphing ]);
+
+    let (ability, units) = only_command(&steps[0]);
+
+    assert_eq!(ability, OVERLORD_ABILITY);
+    assert!(LARVA.contains(&units[0]));
+
+    // the overlord is on its way, so there is nothing to build until it pops
+    assert!(steps[1].commands.is_empty());
+
+    for p in &progress {
+        assert_eq!(
+            p.blocked, Some(BuildBlocker::Supply { current: 10, required: 12 })
+        );
+    }
+}
+
+#[test]
+fn does_not_order_again_while_the_drone_carries_out_the_order() {
+    let mut building = frame(1000, 12, vec![ ]);
+    building.units[1].orders.push(
+        RecordedOrder { ability: POOL_ABILITY, target: None, progress: 0.0 }
+    );
+
+    let (steps, progress) = run(
+        "12 pool", vec![ frame(0, 12, vec![ ]), building ]
+    );
+
+    assert_eq!(only_command(&steps[0]), (POOL_ABILITY, vec![ DRONE ]));
+
+    // the timeout has passed, but the drone is still on its way
+    assert!(steps[1].commands.is_empty());
+    assert_eq!(progress[1].blocked, Some(BuildBlocker::Pending));
+}
+
+#[test]
+fn hatcheries_need_a_base_location() {
+    let (steps, progress) = run("12 hatch", vec![ frame(0, 12, vec![ ]) ]);
+
+    // without potential base locations, there is nowhere to expand to
+    assert!(steps[0].commands.is_empty());
+    assert_eq!(progress[0].blocked, Some(BuildBlocker::NoBaseLocation));
+}
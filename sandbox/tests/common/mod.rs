@@ -0,0 +1,155 @@
+//! helpers shared by the replay tests
+//!
+//! every test binary only uses some of these, so the rest would warn.
+#![allow(dead_code)]
+
+use std::rc::Rc;
+
+use sandbox::{
+    BudgetProbeLobe,
+    BudgetProbeLog,
+    EdgeSpec,
+    KeliConstraint,
+    LobeRegistry,
+    LobeSignature,
+    LobeSpec,
+    RecordedAlliance,
+    RecordedDisplayType,
+    RecordedFrame,
+    RecordedImage,
+    RecordedTerrain,
+    RecordedUnit,
+    RecordedUnitType,
+    Recording,
+};
+use sc2;
+use sc2::data::{ UnitType };
+use toml;
+
+pub const MAP_SIZE: i32 = 100;
+
+/// an empty image
+pub fn image() -> RecordedImage {
+    RecordedImage { width: 0, height: 0, bits_per_pixel: 8, data: vec![ ] }
+}
+
+/// an image covering the whole map with every pixel set
+pub fn filled() -> RecordedImage {
+    RecordedImage {
+        width: MAP_SIZE,
+        height: MAP_SIZE,
+        bits_per_pixel: 8,
+        data: vec![ 1; (MAP_SIZE * MAP_SIZE) as usize ],
+    }
+}
+
+/// a finished, visible unit of our own
+pub fn unit(tag: u64, unit_type: UnitType, x: f32, y: f32) -> RecordedUnit {
+    RecordedUnit {
+        tag: tag,
+        unit_type: unit_type as u32,
+        display_type: RecordedDisplayType::Visible,
+        alliance: RecordedAlliance::Domestic,
+        owner: 1,
+        pos: (x, y, 0.0),
+        facing: 0.0,
+        radius: 0.5,
+        build_progress: 1.0,
+        health: (40.0, 40.0),
+        shield: (0.0, 0.0),
+        energy: (0.0, 0.0),
+        is_flying: false,
+        is_burrowed: false,
+        orders: vec![ ],
+        mineral_contents: 0,
+        vespene_contents: 0,
+    }
+}
+
+/// the cost, supply and morph ability of a unit type
+pub fn unit_type(
+    unit_type: UnitType,
+    name: &str,
+    minerals: u32,
+    vespene: u32,
+    food: f32,
+    ability: u32
+)
+    -> RecordedUnitType
+{
+    RecordedUnitType {
+        unit_type: unit_type as u32,
+        name: name.to_string(),
+        mineral_cost: minerals,
+        vespene_cost: vespene,
+        food_required: food,
+        food_provided: 0.0,
+        build_time: 0.0,
+        ability: ability,
+    }
+}
+
+/// turn recorded frames on a buildable map into frame data
+pub fn frames(unit_types: Vec<RecordedUnitType>, frames: Vec<RecordedFrame>)
+    -> Vec<Rc<sc2::FrameData>>
+{
+    let recording = Recording {
+        terrain: RecordedTerrain {
+            width: MAP_SIZE,
+            height: MAP_SIZE,
+            pathing_grid: image(),
+            placement_grid: filled(),
+            terrain_height: image(),
+            playable_min: (0.0, 0.0),
+            playable_max: (MAP_SIZE as f32, MAP_SIZE as f32),
+            enemy_start_locations: vec![ ],
+        },
+        unit_types: unit_types,
+        frames: frames,
+    };
+
+    recording.frame_data().unwrap()
+}
+
+pub fn lobe(name: &str, kind: &str, params: toml::value::Table) -> LobeSpec {
+    LobeSpec {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        constructor: None,
+        params: params,
+    }
+}
+
+pub fn edge(from: &str, to: &str, constraints: &[&str]) -> EdgeSpec {
+    EdgeSpec {
+        from: from.to_string(),
+        to: to.to_string(),
+        constraints: constraints.iter().map(|c| c.to_string()).collect(),
+    }
+}
+
+/// register a budget probe under its own lobe type so its log can be
+/// inspected
+pub fn register_budget_probe(registry: &mut LobeRegistry, kind: &str)
+    -> BudgetProbeLog
+{
+    let log = BudgetProbeLog::default();
+    let shared = Rc::clone(&log);
+
+    registry.register(
+        kind,
+        LobeSignature {
+            inputs: vec![ KeliConstraint::Budget ],
+            outputs: vec![ KeliConstraint::Budget ],
+            feedback_inputs: vec![
+                KeliConstraint::Budget, KeliConstraint::BudgetRequest
+            ],
+            feedback_outputs: vec![
+                KeliConstraint::Budget, KeliConstraint::BudgetRequest
+            ],
+        },
+        move |_| Ok(Box::new(BudgetProbeLobe::with_log(Rc::clone(&shared))))
+    );
+
+    log
+}
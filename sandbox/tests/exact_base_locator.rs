@@ -0,0 +1,92 @@
+extern crate sandbox;
+extern crate sc2;
+
+use sandbox::{ BaseResource, ResourceKind, solve_base_location };
+use sc2::data::{ ImageData, Point2 };
+
+const MAP_SIZE: i32 = 64;
+
+/// a placement grid with every tile placeable
+fn open_grid() -> ImageData {
+    ImageData {
+        width: MAP_SIZE,
+        height: MAP_SIZE,
+        bits_per_pixel: 8,
+        data: vec![ 1; (MAP_SIZE * MAP_SIZE) as usize ],
+    }
+}
+
+/// mark every tile in the given columns as unplaceable
+fn block_columns(grid: &mut ImageData, x0: i32, x1: i32) {
+    for y in 0..grid.height {
+        for x in x0..x1 {
+            grid.data[(x + y * grid.width) as usize] = 0;
+        }
+    }
+}
+
+/// a vertical mineral line at x = 20 with a geyser below it
+fn cluster() -> Vec<BaseResource> {
+    let mut resources: Vec<BaseResource> = (26..34).map(
+        |y| BaseResource {
+            pos: Point2::new(20.0, y as f32 + 0.5),
+            kind: ResourceKind::Mineral,
+        }
+    ).collect();
+
+    resources.push(
+        BaseResource {
+            pos: Point2::new(23.5, 21.5),
+            kind: ResourceKind::Geyser,
+        }
+    );
+
+    resources
+}
+
+#[test]
+fn location_is_right_against_the_gap() {
+    let location = solve_base_location(&open_grid(), &cluster());
+
+    // three tiles clear of the mineral line on the open side, level with
+    // the middle of it
+    assert_eq!(location, Some(Point2::new(26.5, 29.5)));
+}
+
+#[test]
+fn location_keeps_the_gap_to_a_geyser() {
+    let geyser = BaseResource {
+        pos: Point2::new(30.5, 30.5),
+        kind: ResourceKind::Geyser,
+    };
+
+    // every side is as close, so the first one searched wins
+    assert_eq!(
+        solve_base_location(&open_grid(), &[ geyser ]),
+        Some(Point2::new(30.5, 23.5))
+    );
+}
+
+#[test]
+fn location_avoids_unplaceable_tiles() {
+    let mut grid = open_grid();
+    block_columns(&mut grid, 24, MAP_SIZE);
+
+    // the open side is blocked, so it goes against the other side instead
+    assert_eq!(
+        solve_base_location(&grid, &cluster()), Some(Point2::new(13.5, 29.5))
+    );
+}
+
+#[test]
+fn no_location_without_placeable_tiles() {
+    let mut grid = open_grid();
+    block_columns(&mut grid, 0, MAP_SIZE);
+
+    assert_eq!(solve_base_location(&grid, &cluster()), None);
+}
+
+#[test]
+fn no_location_without_resources() {
+    assert_eq!(solve_base_location(&open_grid(), &[ ]), None);
+}
@@ -0,0 +1,156 @@
+extern crate sandbox;
+extern crate sc2;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    BaseStatus,
+    ExpansionSites,
+    RecordedAlliance,
+    RecordedFrame,
+    RecordedOrder,
+    RecordedState,
+    RecordedTerrain,
+    RecordedUnit,
+    Recording,
+
+    closest_drone,
+};
+use sc2::data::{ Point2, UnitType };
+
+use common::{ MAP_SIZE, filled, image, unit, unit_type };
+
+const POOL_ABILITY: u32 = 1155;
+
+/// a single frame on a map that can be walked everywhere
+fn frame(units: Vec<RecordedUnit>) -> Rc<sc2::FrameData> {
+    let recording = Recording {
+        terrain: RecordedTerrain {
+            width: MAP_SIZE,
+            height: MAP_SIZE,
+            pathing_grid: filled(),
+            placement_grid: filled(),
+            terrain_height: image(),
+            playable_min: (0.0, 0.0),
+            playable_max: (MAP_SIZE as f32, MAP_SIZE as f32),
+            enemy_start_locations: vec![ ],
+        },
+        unit_types: vec![
+            unit_type(
+                UnitType::ZergSpawningPool,
+                "SpawningPool",
+                200,
+                0,
+                0.0,
+                POOL_ABILITY
+            ),
+        ],
+        frames: vec![
+            RecordedFrame {
+                state: RecordedState {
+                    current_step: 0,
+                    minerals: 0,
+                    vespene: 0,
+                    food_used: 12,
+                    food_cap: 14,
+                    larva_count: 0,
+                },
+                units: units,
+                creep: filled(),
+                visibility: image(),
+            },
+        ],
+    };
+
+    Rc::clone(&recording.frame_data().unwrap()[0])
+}
+
+fn enemy_hatchery(tag: u64, x: f32, y: f32) -> RecordedUnit {
+    let mut u = unit(tag, UnitType::ZergHatchery, x, y);
+    u.alliance = RecordedAlliance::Enemy;
+
+    u
+}
+
+/// our main at the middle of the map, a close free location, a closer one
+/// held by the enemy and a far one
+fn sites(claimed: Option<Point2>) -> ExpansionSites {
+    let frame = frame(
+        vec![
+            unit(1, UnitType::ZergHatchery, 50.5, 50.5),
+            enemy_hatchery(2, 40.5, 50.5),
+        ]
+    );
+
+    let mut sites = ExpansionSites::new();
+    sites.update(
+        &frame,
+        &[
+            Point2::new(90.5, 50.5),
+            Point2::new(65.5, 50.5),
+            Point2::new(40.5, 50.5),
+            Point2::new(50.5, 50.5),
+        ],
+        claimed
+    );
+
+    sites
+}
+
+#[test]
+fn sites_know_who_holds_each_location() {
+    let statuses: Vec<BaseStatus> = sites(None).statuses().iter()
+        .map(|&(_, status)| status)
+        .collect()
+    ;
+
+    assert_eq!(
+        statuses,
+        vec![
+            BaseStatus::Free,
+            BaseStatus::Free,
+            BaseStatus::Enemy,
+            BaseStatus::Ours,
+        ]
+    );
+}
+
+#[test]
+fn closest_free_site_is_the_closest_one_nobody_holds() {
+    assert_eq!(sites(None).closest_free(), Some(Point2::new(65.5, 50.5)));
+}
+
+#[test]
+fn claimed_sites_are_not_free() {
+    let sites = sites(Some(Point2::new(65.5, 50.5)));
+
+    assert_eq!(sites.statuses()[1].1, BaseStatus::Claimed);
+    assert_eq!(sites.closest_free(), Some(Point2::new(90.5, 50.5)));
+}
+
+#[test]
+fn claims_survive_locations_being_nudged() {
+    let sites = sites(Some(Point2::new(66.0, 51.0)));
+
+    assert_eq!(sites.statuses()[1].1, BaseStatus::Claimed);
+    assert!(!sites.is_taken(Point2::new(66.0, 51.0)));
+    assert!(sites.is_taken(Point2::new(41.0, 50.0)));
+}
+
+#[test]
+fn drones_with_build_orders_are_not_sent_to_expand() {
+    let mut busy = unit(3, UnitType::ZergDrone, 64.5, 50.5);
+    busy.orders.push(
+        RecordedOrder { ability: POOL_ABILITY, target: None, progress: 0.0 }
+    );
+
+    let frame = frame(
+        vec![ busy, unit(4, UnitType::ZergDrone, 75.5, 50.5) ]
+    );
+
+    let drone = closest_drone(&frame, Point2::new(65.5, 50.5)).unwrap();
+
+    assert_eq!(drone.tag, 4);
+}
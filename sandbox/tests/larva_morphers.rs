@@ -0,0 +1,165 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    FrameReplay,
+    RecordedFrame,
+    RecordedState,
+    RecordedUnit,
+    ReplayStep,
+    Topology,
+};
+use sc2::data::{ UnitType };
+
+use common::{ edge, filled, image, lobe, unit, unit_type };
+
+const DRONE_ABILITY: u32 = 1342;
+const ZERGLING_ABILITY: u32 = 1343;
+
+/// larva next to the lair, halfway across the map and in the far corner
+const CLOSE: u64 = 3;
+const MIDDLE: u64 = 4;
+const FAR: u64 = 5;
+
+fn units(producer: UnitType) -> Vec<RecordedUnit> {
+    vec![
+        unit(1, producer, 20.0, 20.0),
+        unit(FAR, UnitType::ZergLarva, 90.0, 90.0),
+        unit(CLOSE, UnitType::ZergLarva, 21.0, 20.0),
+        unit(MIDDLE, UnitType::ZergLarva, 50.0, 50.0),
+    ]
+}
+
+/// a single frame with the given bank and supply left
+fn frames(producer: UnitType, minerals: u32, food: u32)
+    -> Vec<Rc<sc2::FrameData>>
+{
+    common::frames(
+        vec![
+            unit_type(UnitType::ZergDrone, "Drone", 50, 0, 1.0, DRONE_ABILITY),
+            unit_type(
+                UnitType::ZergZergling,
+                "Zergling",
+                25,
+                0,
+                0.5,
+                ZERGLING_ABILITY
+            ),
+        ],
+        vec![
+            RecordedFrame {
+                state: RecordedState {
+                    current_step: 0,
+                    minerals: minerals,
+                    vespene: 0,
+                    food_used: 10,
+                    food_cap: 10 + food,
+                    larva_count: 3,
+                },
+                units: units(producer),
+                creep: filled(),
+                visibility: image(),
+            },
+        ]
+    )
+}
+
+/// whole budget -> larva morpher
+fn topology(unit_type: &str) -> Topology {
+    let mut params = toml::value::Table::new();
+    params.insert(
+        "unit_type".to_string(), toml::Value::String(unit_type.to_string())
+    );
+
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("morpher", "LarvaMorpherLobe", params),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "morpher", &[ "FrameData" ]),
+            edge("whole_budget", "morpher", &[ "Budget" ]),
+            edge("morpher", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![ ],
+    }
+}
+
+/// the larva ordered to morph on the only step
+fn morphed(unit_type: &str, frames: Vec<Rc<sc2::FrameData>>) -> Vec<u64> {
+    let cortex = topology(unit_type).build().unwrap();
+
+    let steps: Vec<ReplayStep> = FrameReplay::from_cortex(cortex, frames)
+        .unwrap()
+        .run()
+        .unwrap()
+    ;
+
+    match steps[0].commands.first() {
+        Some(&sc2::Command::Action { ref units, .. }) => units.iter()
+            .map(|u| u.tag)
+            .collect(),
+        Some(command) => panic!("unexpected command {:?}", command),
+        None => vec![ ],
+    }
+}
+
+#[test]
+fn larva_closest_to_a_hatchery_are_morphed_first() {
+    assert_eq!(
+        morphed("ZergDrone", frames(UnitType::ZergHatchery, 100, 10)),
+        vec![ CLOSE, MIDDLE ]
+    );
+}
+
+#[test]
+fn larva_are_picked_around_lairs_and_hives() {
+    for &producer in &[ UnitType::ZergLair, UnitType::ZergHive ] {
+        assert_eq!(
+            morphed("ZergDrone", frames(producer, 50, 10)),
+            vec![ CLOSE ],
+            "{:?}",
+            producer
+        );
+    }
+}
+
+#[test]
+fn every_larva_is_charged_against_the_budget() {
+    // the bank covers all three, but there is only supply for two
+    assert_eq!(
+        morphed("ZergDrone", frames(UnitType::ZergHatchery, 1000, 2)),
+        vec![ CLOSE, MIDDLE ]
+    );
+
+    // a larva makes a pair of zerglings, so it takes 50 minerals and a
+    // whole supply
+    assert_eq!(
+        morphed("ZergZergling", frames(UnitType::ZergHatchery, 149, 10)),
+        vec![ CLOSE, MIDDLE ]
+    );
+    assert_eq!(
+        morphed("ZergZergling", frames(UnitType::ZergHatchery, 1000, 1)),
+        vec![ CLOSE ]
+    );
+}
+
+#[test]
+fn nothing_is_morphed_when_no_larva_is_covered() {
+    assert!(
+        morphed("ZergDrone", frames(UnitType::ZergHatchery, 49, 10))
+            .is_empty()
+    );
+}
@@ -0,0 +1,262 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    Budget,
+    BudgetProbeLog,
+    FrameReplay,
+    LobeRegistry,
+    LobeSpec,
+    RecordedFrame,
+    RecordedState,
+    RecordedUnit,
+    Topology,
+};
+use sc2::data::{ UnitType };
+
+use common::{
+    edge,
+    filled,
+    image,
+    lobe,
+    register_budget_probe,
+    unit,
+    unit_type,
+};
+
+const MINERALS: u32 = 1000;
+
+const POOL_ABILITY: u32 = 1155;
+const POOL_COST: u32 = 200;
+const EVO_ABILITY: u32 = 1156;
+const EVO_COST: u32 = 75;
+const DRONE_ABILITY: u32 = 1342;
+const DRONE_COST: u32 = 50;
+
+/// a hatchery and two drones
+fn units() -> Vec<RecordedUnit> {
+    vec![
+        unit(1, UnitType::ZergHatchery, 50.0, 50.0),
+        unit(2, UnitType::ZergDrone, 45.0, 45.0),
+        unit(3, UnitType::ZergDrone, 46.0, 45.0),
+    ]
+}
+
+/// the units on a buildable map covered in creep, with the same bank on
+/// every step
+fn frames(minerals: u32, units: Vec<RecordedUnit>, steps: u32)
+    -> Vec<Rc<sc2::FrameData>>
+{
+    common::frames(
+        vec![
+            unit_type(
+                UnitType::ZergSpawningPool,
+                "SpawningPool",
+                POOL_COST,
+                0,
+                0.0,
+                POOL_ABILITY
+            ),
+            unit_type(
+                UnitType::ZergEvolutionChamber,
+                "EvolutionChamber",
+                EVO_COST,
+                0,
+                0.0,
+                EVO_ABILITY
+            ),
+            unit_type(
+                UnitType::ZergDrone,
+                "Drone",
+                DRONE_COST,
+                0,
+                1.0,
+                DRONE_ABILITY
+            ),
+        ],
+        (0..steps).map(
+            |step| RecordedFrame {
+                state: RecordedState {
+                    current_step: step,
+                    minerals: minerals,
+                    vespene: 0,
+                    food_used: 10,
+                    food_cap: 14,
+                    larva_count: 3,
+                },
+                units: units.clone(),
+                creep: filled(),
+                visibility: image(),
+            }
+        ).collect()
+    )
+}
+
+fn morpher(unit_type: &str) -> toml::value::Table {
+    let mut params = toml::value::Table::new();
+    params.insert(
+        "unit_type".to_string(), toml::Value::String(unit_type.to_string())
+    );
+
+    params
+}
+
+/// whole budget -> even split ledger -> probes -> pool and evo morphers
+fn topology() -> Topology {
+    topology_with(
+        lobe("evo", "RandomDroneMorpherLobe", morpher("ZergEvolutionChamber"))
+    )
+}
+
+/// whole budget -> even split ledger -> probes -> pool morpher and the given
+/// spender, which must be named "evo"
+fn topology_with(evo: LobeSpec) -> Topology {
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("ledger", "EvenSplitLedgerLobe", Default::default()),
+            lobe("pool_probe", "PoolProbe", Default::default()),
+            lobe("evo_probe", "EvoProbe", Default::default()),
+            lobe(
+                "pool",
+                "RandomDroneMorpherLobe",
+                morpher("ZergSpawningPool")
+            ),
+            evo,
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "pool", &[ "FrameData" ]),
+            edge("frame_forwarder", "evo", &[ "FrameData" ]),
+            edge("whole_budget", "ledger", &[ "Budget" ]),
+            edge("ledger", "pool_probe", &[ "Budget" ]),
+            edge("ledger", "evo_probe", &[ "Budget" ]),
+            edge("pool_probe", "pool", &[ "Budget" ]),
+            edge("evo_probe", "evo", &[ "Budget" ]),
+            edge("pool", "command_merger", &[ "Commands" ]),
+            edge("evo", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![
+            edge("pool", "pool_probe", &[ "Budget", "BudgetRequest" ]),
+            edge("evo", "evo_probe", &[ "Budget", "BudgetRequest" ]),
+            edge("pool_probe", "ledger", &[ "Budget", "BudgetRequest" ]),
+            edge("evo_probe", "ledger", &[ "Budget", "BudgetRequest" ]),
+        ],
+    }
+}
+
+/// replay the frames and return what the pool and evo probes saw
+///
+/// if `report` is set, a report lobe watches every ledger.
+fn run(
+    mut topology: Topology,
+    frames: Vec<Rc<sc2::FrameData>>,
+    report: bool
+)
+    -> (BudgetProbeLog, BudgetProbeLog)
+{
+    let mut registry = LobeRegistry::new();
+    let pool = register_budget_probe(&mut registry, "PoolProbe");
+    let evo = register_budget_probe(&mut registry, "EvoProbe");
+
+    if report {
+        topology.report_ledgers(&registry, None, false).unwrap();
+    }
+
+    let cortex = topology.build_with(&registry).unwrap();
+
+    FrameReplay::from_cortex(cortex, frames).unwrap().run().unwrap();
+
+    (pool, evo)
+}
+
+fn minerals(minerals: u32) -> Budget {
+    Budget { minerals: minerals, ..Budget::default() }
+}
+
+#[test]
+fn requests_cap_the_split_at_their_cost() {
+    let (pool, evo) = run(topology(), frames(MINERALS, units(), 2), false);
+
+    let pool = pool.borrow();
+    let evo = evo.borrow();
+
+    // nothing has been requested yet, so the bank is split evenly
+    assert_eq!(pool[0].allotted.minerals, MINERALS / 2);
+    assert_eq!(evo[0].allotted.minerals, MINERALS / 2);
+
+    // once the requests are in, each morpher only gets what it asked for
+    assert_eq!(pool[1].allotted, minerals(POOL_COST));
+    assert_eq!(evo[1].allotted, minerals(EVO_COST));
+}
+
+#[test]
+fn reports_do_not_take_a_share_of_the_budget() {
+    let (pool, evo) = run(topology(), frames(MINERALS, units(), 2), true);
+
+    let pool = pool.borrow();
+    let evo = evo.borrow();
+
+    // the report lobe is an output of the ledger, but only watches it
+    assert_eq!(pool[0].allotted.minerals, MINERALS / 2);
+    assert_eq!(evo[0].allotted.minerals, MINERALS / 2);
+
+    assert_eq!(pool[1].allotted, minerals(POOL_COST));
+    assert_eq!(evo[1].allotted, minerals(EVO_COST));
+}
+
+#[test]
+fn idle_spenders_give_up_their_share() {
+    // there is no larva, so the drone morpher has nothing to spend on
+    let (pool, drones) = run(
+        topology_with(
+            lobe("evo", "LarvaMorpherLobe", morpher("ZergDrone"))
+        ),
+        frames(300, units(), 2),
+        false
+    );
+
+    let pool = pool.borrow();
+    let drones = drones.borrow();
+
+    // nothing has been heard from the spenders yet, so half is not enough
+    assert_eq!(pool[0].allotted, minerals(150));
+    assert_eq!(drones[0].allotted, minerals(150));
+
+    assert_eq!(pool[1].allotted, minerals(POOL_COST));
+    assert_eq!(drones[1].allotted, Budget::default());
+}
+
+#[test]
+fn finished_spenders_give_up_their_share() {
+    let mut evo = lobe(
+        "evo", "RandomDroneMorpherLobe", morpher("ZergEvolutionChamber")
+    );
+    evo.constructor = Some("one_and_done".to_string());
+
+    // the evolution chamber has already been built
+    let mut units = units();
+    units.push(unit(4, UnitType::ZergEvolutionChamber, 55.0, 55.0));
+
+    let (pool, evo) = run(topology_with(evo), frames(300, units, 2), false);
+
+    let pool = pool.borrow();
+    let evo = evo.borrow();
+
+    assert_eq!(pool[0].allotted, minerals(150));
+    assert_eq!(evo[0].allotted, minerals(150));
+
+    assert_eq!(pool[1].allotted, minerals(POOL_COST));
+    assert_eq!(evo[1].allotted, Budget::default());
+}
@@ -0,0 +1,335 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    Budget,
+    BudgetProbeLog,
+    BudgetProbeRecord,
+    FrameReplay,
+    LobeRegistry,
+    RecordedAlliance,
+    RecordedFrame,
+    RecordedState,
+    ReplayStep,
+    Topology,
+};
+use sc2::data::{ ActionTarget, UnitType };
+
+use common::{
+    edge,
+    filled,
+    image,
+    lobe,
+    register_budget_probe,
+    unit,
+    unit_type,
+};
+
+const MINERALS: u32 = 400;
+const VESPENE: u32 = 100;
+const STEPS: u32 = 40;
+
+const EXTRACTOR_ABILITY: u32 = 1154;
+const GEYSER: u64 = 10;
+
+/// (category, [ (unit type name, unit type, build ability id, minerals, gas) ])
+const CATEGORIES: &'static [(&'static str, &'static [Morph])] = &[
+    (
+        "economy",
+        &[
+            ("ZergHatchery", UnitType::ZergHatchery, 1152, 300, 0),
+            (
+                "ZergExtractor",
+                UnitType::ZergExtractor,
+                EXTRACTOR_ABILITY,
+                25,
+                0
+            ),
+        ]
+    ),
+    (
+        "army",
+        &[
+            ("ZergSpawningPool", UnitType::ZergSpawningPool, 1155, 200, 0),
+            ("ZergRoachWarren", UnitType::ZergRoachWarren, 1165, 150, 0),
+        ]
+    ),
+    (
+        "tech",
+        &[
+            (
+                "ZergEvolutionChamber",
+                UnitType::ZergEvolutionChamber,
+                1156,
+                75,
+                0
+            ),
+            ("ZergSpire", UnitType::ZergSpire, 1158, 200, 200),
+        ]
+    ),
+];
+
+type Morph = (&'static str, UnitType, u32, u32, u32);
+
+/// a hatchery with a few drones and a geyser on a buildable map covered in
+/// creep, and a bank that never changes
+fn frames() -> Vec<Rc<sc2::FrameData>> {
+    let mut unit_types = vec![
+        unit_type(UnitType::ZergDrone, "Drone", 50, 0, 1.0, 1342),
+    ];
+    unit_types[0].build_time = 272.0;
+
+    for &(_, morphs) in CATEGORIES {
+        for &(name, kind, ability, minerals, vespene) in morphs {
+            unit_types.push(
+                unit_type(kind, name, minerals, vespene, 0.0, ability)
+            );
+        }
+    }
+
+    let mut units = vec![ unit(1, UnitType::ZergHatchery, 50.0, 50.0) ];
+
+    for tag in 2..6 {
+        units.push(unit(tag, UnitType::ZergDrone, 45.0 + tag as f32, 45.0));
+    }
+
+    let mut geyser = unit(GEYSER, UnitType::NeutralVespeneGeyser, 57.5, 50.5);
+    geyser.alliance = RecordedAlliance::Neutral;
+    geyser.radius = 1.5;
+    geyser.vespene_contents = 2250;
+
+    units.push(geyser);
+
+    common::frames(
+        unit_types,
+        (0..STEPS).map(
+            |step| RecordedFrame {
+                state: RecordedState {
+                    current_step: step,
+                    minerals: MINERALS,
+                    vespene: VESPENE,
+                    food_used: 10,
+                    food_cap: 14,
+                    larva_count: 3,
+                },
+                units: units.clone(),
+                creep: filled(),
+                visibility: image(),
+            }
+        ).collect()
+    )
+}
+
+/// whole budget -> root ledger -> category ledgers -> drone morphers, with a
+/// probe above the root ledger and above every category ledger
+fn topology() -> Topology {
+    let mut topology = Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("root_probe", "RootProbe", Default::default()),
+            lobe("root_ledger", "EvenSplitLedgerLobe", Default::default()),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("whole_budget", "root_probe", &[ "Budget" ]),
+            edge("root_probe", "root_ledger", &[ "Budget" ]),
+        ],
+        feedback: vec![
+            edge("root_ledger", "root_probe", &[ "Budget", "BudgetRequest" ]),
+        ],
+    };
+
+    for &(category, morphs) in CATEGORIES {
+        let probe = format!("{}_probe", category);
+        let ledger = format!("{}_ledger", category);
+
+        topology.lobes.push(
+            lobe(&probe, &probe_kind(category), Default::default())
+        );
+        topology.lobes.push(
+            lobe(&ledger, "EvenSplitLedgerLobe", Default::default())
+        );
+
+        topology.connections.push(edge("root_ledger", &probe, &[ "Budget" ]));
+        topology.connections.push(edge(&probe, &ledger, &[ "Budget" ]));
+
+        topology.feedback.push(
+            edge(&probe, "root_ledger", &[ "Budget", "BudgetRequest" ])
+        );
+        topology.feedback.push(
+            edge(&ledger, &probe, &[ "Budget", "BudgetRequest" ])
+        );
+
+        for &(unit_type, _, _, _, _) in morphs {
+            let morpher = format!("{}_morpher", unit_type);
+
+            let mut params = toml::value::Table::new();
+            params.insert(
+                "unit_type".to_string(),
+                toml::Value::String(unit_type.to_string())
+            );
+
+            topology.lobes.push(
+                lobe(&morpher, "RandomDroneMorpherLobe", params)
+            );
+
+            topology.connections.push(
+                edge("frame_forwarder", &morpher, &[ "FrameData" ])
+            );
+            topology.connections.push(edge(&ledger, &morpher, &[ "Budget" ]));
+            topology.connections.push(
+                edge(&morpher, "command_merger", &[ "Commands" ])
+            );
+
+            topology.feedback.push(
+                edge(&morpher, &ledger, &[ "Budget", "BudgetRequest" ])
+            );
+        }
+    }
+
+    topology
+}
+
+fn probe_kind(category: &str) -> String {
+    format!("{}Probe", category)
+}
+
+#[test]
+fn nested_ledgers_propagate_spending() {
+    let mut registry = LobeRegistry::new();
+
+    let root = register_budget_probe(&mut registry, "RootProbe");
+    let categories: Vec<BudgetProbeLog> = CATEGORIES.iter()
+        .map(|&(category, _)| {
+            register_budget_probe(&mut registry, &probe_kind(category))
+        })
+        .collect()
+    ;
+
+    let cortex = topology().build_with(&registry).unwrap();
+    let mut replay = FrameReplay::from_cortex(cortex, frames()).unwrap();
+
+    let steps = replay.run().unwrap();
+
+    assert_eq!(steps.len(), STEPS as usize);
+
+    let root = root.borrow();
+    let bank = Budget {
+        minerals: MINERALS,
+        vespene: VESPENE,
+        food: 4,
+        larva: 3,
+    };
+
+    assert_eq!(root.len(), STEPS as usize);
+
+    for (step, record) in root.iter().enumerate() {
+        // the root ledger never hands out more than is in the bank
+        assert!(record.allotted.fits_within(&bank), "step {}", step);
+
+        let mut allotted = Budget::default();
+        let mut spent = Budget::default();
+
+        for category in &categories {
+            let category = category.borrow()[step];
+
+            // no category spends more than it was given
+            assert!(
+                category.spent.fits_within(&category.allotted),
+                "step {}",
+                step
+            );
+
+            allotted += category.allotted;
+            spent += category.spent;
+        }
+
+        // categories split what the root ledger was given and their spending
+        // is reported all the way up
+        assert!(allotted.fits_within(&record.allotted), "step {}", step);
+        assert_eq!(spent, record.spent, "step {}", step);
+    }
+
+    // every category gets to spend something over the game
+    for category in &categories {
+        let spent = category.borrow().iter().fold(
+            Budget::default(), |acc, record| acc + record.spent
+        );
+
+        assert!(spent.minerals > 0);
+    }
+}
+
+/// replay the frames and return the commands and the budget seen by every
+/// probe, root first
+fn run() -> (Vec<ReplayStep>, Vec<Vec<BudgetProbeRecord>>) {
+    let mut registry = LobeRegistry::new();
+
+    let mut logs = vec![ register_budget_probe(&mut registry, "RootProbe") ];
+
+    for &(category, _) in CATEGORIES {
+        logs.push(register_budget_probe(&mut registry, &probe_kind(category)));
+    }
+
+    let cortex = topology().build_with(&registry).unwrap();
+    let mut replay = FrameReplay::from_cortex(cortex, frames()).unwrap();
+
+    let steps = replay.run().unwrap();
+
+    (steps, logs.iter().map(|log| log.borrow().clone()).collect())
+}
+
+#[test]
+fn same_seed_gives_same_commands_and_splits() {
+    let (first_steps, first_logs) = run();
+    let (second_steps, second_logs) = run();
+
+    // compare the debug output so commands needn't implement PartialEq
+    assert_eq!(
+        format!("{:?}", first_steps), format!("{:?}", second_steps)
+    );
+    assert_eq!(first_logs, second_logs);
+
+    // make sure there was something to compare
+    assert!(first_steps.iter().any(|step| !step.commands.is_empty()));
+}
+
+#[test]
+fn extractors_are_built_on_the_geyser() {
+    let (steps, _) = run();
+
+    let targets: Vec<Option<ActionTarget>> = steps.iter()
+        .flat_map(|step| step.commands.iter())
+        .filter_map(
+            |command| match *command {
+                sc2::Command::Action { ability, ref target, .. }
+                    if ability as u32 == EXTRACTOR_ABILITY =>
+                {
+                    Some(target.clone())
+                },
+                _ => None
+            }
+        )
+        .collect()
+    ;
+
+    assert!(!targets.is_empty());
+
+    for target in targets {
+        match target {
+            Some(ActionTarget::UnitTag(tag)) => assert_eq!(tag, GEYSER),
+            target => panic!("unexpected target {:?}", target),
+        }
+    }
+}
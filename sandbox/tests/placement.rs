@@ -0,0 +1,139 @@
+extern crate sandbox;
+extern crate sc2;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    OccupancyGrid,
+    PlacementStrategy,
+    RecordedFrame,
+    RecordedState,
+    RecordedUnit,
+
+    find_placement,
+    seeded_rng,
+};
+use sc2::data::{ Point2, UnitType };
+
+use common::{ filled, image, unit };
+
+const ANCHOR: (f32, f32) = (50.5, 50.5);
+
+/// a hatchery at the anchor plus the given units, on a buildable map covered
+/// in creep
+fn frame(extra: Vec<RecordedUnit>) -> Rc<sc2::FrameData> {
+    let mut units = vec![
+        unit(1, UnitType::ZergHatchery, ANCHOR.0, ANCHOR.1),
+    ];
+    units.extend(extra);
+
+    let frames = common::frames(
+        vec![ ],
+        vec![
+            RecordedFrame {
+                state: RecordedState {
+                    current_step: 0,
+                    minerals: 0,
+                    vespene: 0,
+                    food_used: 12,
+                    food_cap: 14,
+                    larva_count: 0,
+                },
+                units: units,
+                creep: filled(),
+                visibility: image(),
+            },
+        ]
+    );
+
+    Rc::clone(&frames[0])
+}
+
+fn place(
+    frame: &sc2::FrameData,
+    occupancy: Option<&Rc<OccupancyGrid>>,
+    strategy: PlacementStrategy
+)
+    -> Point2
+{
+    find_placement(
+        frame,
+        occupancy,
+        UnitType::ZergSpawningPool,
+        Point2::new(ANCHOR.0, ANCHOR.1),
+        strategy,
+        &mut seeded_rng(7)
+    ).unwrap()
+}
+
+/// true if two 3x3 footprints share a tile
+fn overlaps(a: Point2, b: Point2) -> bool {
+    (a.x - b.x).abs() < 3.0 && (a.y - b.y).abs() < 3.0
+}
+
+#[test]
+fn flying_and_ground_units_do_not_block_placement() {
+    let open = place(&frame(vec![ ]), None, PlacementStrategy::Compact);
+
+    let mut overlord = unit(2, UnitType::ZergOverlord, open.x, open.y);
+    overlord.is_flying = true;
+    overlord.radius = 1.0;
+
+    let drone = unit(3, UnitType::ZergDrone, open.x, open.y);
+
+    let location = place(
+        &frame(vec![ overlord, drone ]), None, PlacementStrategy::Compact
+    );
+
+    assert_eq!(location, open);
+}
+
+#[test]
+fn structures_block_placement() {
+    let open = place(&frame(vec![ ]), None, PlacementStrategy::Compact);
+
+    let evo = unit(2, UnitType::ZergEvolutionChamber, open.x, open.y);
+
+    let location = place(&frame(vec![ evo ]), None, PlacementStrategy::Compact);
+
+    assert!(!overlaps(location, open), "{:?}", location);
+}
+
+#[test]
+fn placement_uses_the_shared_occupancy_grid() {
+    let empty = frame(vec![ ]);
+    let open = place(&empty, None, PlacementStrategy::Compact);
+
+    // the shared grid knows about a structure the frame does not show
+    let occupancy = Rc::new(
+        OccupancyGrid::from_frame(
+            &frame(
+                vec![ unit(2, UnitType::ZergEvolutionChamber, open.x, open.y) ]
+            )
+        )
+    );
+
+    let location = place(&empty, Some(&occupancy), PlacementStrategy::Compact);
+
+    assert!(!overlaps(location, open), "{:?}", location);
+}
+
+#[test]
+fn away_from_minerals_faces_away_from_the_mineral_line() {
+    // a mineral line to the west of the hatchery
+    let minerals = (0..8).map(
+        |i| unit(
+            10 + i as u64, UnitType::NeutralMineralField, 43.0, 47.5 + i as f32
+        )
+    ).collect();
+
+    let location = place(
+        &frame(minerals), None, PlacementStrategy::AwayFromMinerals
+    );
+
+    // east of the hatchery and level with it
+    assert!(location.x > ANCHOR.0 + 2.5, "{:?}", location);
+    assert!((location.y - ANCHOR.1).abs() <= 1.0, "{:?}", location);
+}
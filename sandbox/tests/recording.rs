@@ -0,0 +1,233 @@
+extern crate sandbox;
+extern crate sc2;
+
+use std::env;
+use std::fs;
+use std::path::{ PathBuf };
+use std::process;
+
+use sandbox::{
+    RecordedAlliance,
+    RecordedDisplayType,
+    RecordedFrame,
+    RecordedImage,
+    RecordedOrder,
+    RecordedState,
+    RecordedTarget,
+    RecordedTerrain,
+    RecordedUnit,
+    RecordedUnitType,
+    Recording,
+};
+use sc2::data::{ UnitType };
+
+const MAP_SIZE: i32 = 8;
+
+/// an image with a different value in every pixel
+fn image(seed: u8) -> RecordedImage {
+    RecordedImage {
+        width: MAP_SIZE,
+        height: MAP_SIZE,
+        bits_per_pixel: 8,
+        data: (0..(MAP_SIZE * MAP_SIZE) as u8)
+            .map(|i| i.wrapping_mul(seed))
+            .collect(),
+    }
+}
+
+/// a recording with every recorded field set to something other than its
+/// default
+fn recording() -> Recording {
+    let drone = RecordedUnit {
+        tag: 2,
+        unit_type: UnitType::ZergDrone as u32,
+        display_type: RecordedDisplayType::Visible,
+        alliance: RecordedAlliance::Domestic,
+        owner: 1,
+        pos: (3.5, 4.5, 11.0),
+        facing: 1.5,
+        radius: 0.375,
+        build_progress: 1.0,
+        health: (30.0, 40.0),
+        shield: (0.0, 0.0),
+        energy: (0.0, 0.0),
+        is_flying: false,
+        is_burrowed: true,
+        orders: vec![
+            RecordedOrder {
+                ability: 1155,
+                target: Some(RecordedTarget::Location(6.5, 2.5)),
+                progress: 0.25,
+            },
+            RecordedOrder {
+                ability: 1155,
+                target: Some(RecordedTarget::Unit(3)),
+                progress: 0.0,
+            },
+            RecordedOrder { ability: 1342, target: None, progress: 0.5 },
+        ],
+        mineral_contents: 0,
+        vespene_contents: 0,
+    };
+
+    let overlord = RecordedUnit {
+        tag: 3,
+        unit_type: UnitType::ZergOverlord as u32,
+        display_type: RecordedDisplayType::Snapshot,
+        alliance: RecordedAlliance::Enemy,
+        owner: 2,
+        pos: (1.0, 2.0, 12.5),
+        facing: 3.0,
+        radius: 1.0,
+        build_progress: 0.5,
+        health: (200.0, 200.0),
+        shield: (10.0, 20.0),
+        energy: (50.0, 200.0),
+        is_flying: true,
+        is_burrowed: false,
+        orders: vec![ ],
+        mineral_contents: 0,
+        vespene_contents: 0,
+    };
+
+    let geyser = RecordedUnit {
+        tag: 4,
+        unit_type: UnitType::NeutralVespeneGeyser as u32,
+        display_type: RecordedDisplayType::Hidden,
+        alliance: RecordedAlliance::Neutral,
+        owner: 16,
+        pos: (6.5, 6.5, 11.0),
+        facing: 0.0,
+        radius: 1.5,
+        build_progress: 1.0,
+        health: (0.0, 0.0),
+        shield: (0.0, 0.0),
+        energy: (0.0, 0.0),
+        is_flying: false,
+        is_burrowed: false,
+        orders: vec![ ],
+        mineral_contents: 0,
+        vespene_contents: 2250,
+    };
+
+    Recording {
+        terrain: RecordedTerrain {
+            width: MAP_SIZE,
+            height: MAP_SIZE,
+            pathing_grid: image(3),
+            placement_grid: image(5),
+            terrain_height: image(7),
+            playable_min: (1.0, 2.0),
+            playable_max: (7.0, 6.0),
+            enemy_start_locations: vec![ (5.5, 5.5), (2.5, 6.5) ],
+        },
+        unit_types: vec![
+            RecordedUnitType {
+                unit_type: UnitType::ZergDrone as u32,
+                name: "Drone".to_string(),
+                mineral_cost: 50,
+                vespene_cost: 0,
+                food_required: 1.0,
+                food_provided: 0.0,
+                build_time: 272.0,
+                ability: 1342,
+            },
+        ],
+        frames: (0..3).map(
+            |step| RecordedFrame {
+                state: RecordedState {
+                    current_step: step * 4,
+                    minerals: 50 + step,
+                    vespene: 10 + step,
+                    food_used: 12,
+                    food_cap: 14,
+                    larva_count: step,
+                },
+                units: vec![ drone.clone(), overlord.clone(), geyser.clone() ],
+                creep: image(11 + step as u8),
+                visibility: image(13 + step as u8),
+            }
+        ).collect(),
+    }
+}
+
+/// a temporary file only used by the named test
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(
+        format!("sandbox-recording-{}-{}.bin", name, process::id())
+    )
+}
+
+/// save and load a recording, returning what was loaded and the file size
+fn save_and_load(recording: &Recording, name: &str) -> (Recording, u64) {
+    let path = temp_path(name);
+
+    recording.save(&path).unwrap();
+    let size = fs::metadata(&path).map(|m| m.len());
+    let loaded = Recording::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    (loaded.unwrap(), size.unwrap())
+}
+
+#[test]
+fn recording_survives_save_and_load() {
+    let recording = recording();
+    let (loaded, _) = save_and_load(&recording, "roundtrip");
+
+    assert_eq!(loaded, recording);
+}
+
+#[test]
+fn unchanged_layers_are_only_saved_once() {
+    let changing = recording();
+    let mut unchanged = changing.clone();
+
+    for frame in &mut unchanged.frames {
+        frame.creep = image(11);
+        frame.visibility = image(13);
+    }
+
+    let (loaded, unchanged_size) = save_and_load(&unchanged, "unchanged");
+    let (_, changing_size) = save_and_load(&changing, "changing");
+
+    assert_eq!(loaded, unchanged);
+
+    // every frame after the first leaves out two layers
+    let pixels = (MAP_SIZE * MAP_SIZE) as u64;
+    let skipped = (unchanged.frames.len() as u64 - 1) * 2 * pixels;
+
+    assert!(
+        unchanged_size + skipped <= changing_size,
+        "{} + {} > {}",
+        unchanged_size,
+        skipped,
+        changing_size
+    );
+}
+
+#[test]
+fn recording_survives_frame_data() {
+    let recording = recording();
+    let frames = recording.frame_data().unwrap();
+
+    assert_eq!(frames.len(), recording.frames.len());
+
+    for (frame, recorded) in frames.iter().zip(recording.frames.iter()) {
+        assert_eq!(&RecordedFrame::from_frame_data(frame), recorded);
+    }
+
+    let data = &frames[0].data;
+
+    assert_eq!(
+        RecordedTerrain::from_terrain_info(&data.terrain_info),
+        recording.terrain
+    );
+
+    let unit_types: Vec<RecordedUnitType> = data.unit_type_data.values()
+        .map(|t| RecordedUnitType::from_unit_type_data(t))
+        .collect()
+    ;
+
+    assert_eq!(unit_types, recording.unit_types);
+}
@@ -0,0 +1,121 @@
+extern crate sandbox;
+
+use std::collections::{ HashMap };
+use std::fs::{ self, File };
+use std::io::Read;
+use std::path::Path;
+
+use sandbox::{ KeliConstraint, LobeRegistry, LobeSignature, parse_constraint };
+
+/// read the constraints of every `constrain_lobe!` invocation in the crate
+fn declared_signatures() -> HashMap<String, LobeSignature> {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut signatures = HashMap::new();
+
+    for entry in fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.extension().map_or(true, |ext| ext != "rs") {
+            continue
+        }
+
+        let mut s = String::new();
+        File::open(&path).unwrap().read_to_string(&mut s).unwrap();
+
+        let mut lines = s.lines();
+
+        while let Some(line) = lines.next() {
+            if line.trim() != "constrain_lobe! {" {
+                continue
+            }
+
+            let mut lobe = None;
+            let mut signature = LobeSignature::default();
+
+            for line in lines.by_ref().take_while(|line| *line != "}") {
+                let line = line.trim().trim_right_matches(',');
+
+                if line.starts_with("lobe:") {
+                    lobe = Some(line["lobe:".len()..].trim().to_string());
+                    continue
+                }
+
+                let words: Vec<&str> = line.split_whitespace().collect();
+
+                let (feedback, kind, constraint) = match words.len() {
+                    4 if words[0] == "fbk" => (true, words[1], words[3]),
+                    3 => (false, words[0], words[2]),
+                    _ => continue
+                };
+
+                let constraint = parse_constraint(constraint).unwrap();
+
+                let constraints = match (feedback, kind) {
+                    (false, "out") => &mut signature.outputs,
+                    (false, _) => &mut signature.inputs,
+                    (true, "out") => &mut signature.feedback_outputs,
+                    (true, _) => &mut signature.feedback_inputs,
+                };
+
+                if !constraints.contains(&constraint) {
+                    constraints.push(constraint);
+                }
+            }
+
+            signatures.insert(lobe.unwrap(), signature);
+        }
+    }
+
+    signatures
+}
+
+/// check that two sets of constraints hold the same constraints
+fn same(a: &[KeliConstraint], b: &[KeliConstraint]) -> bool {
+    a.iter().all(|c| b.contains(c)) && b.iter().all(|c| a.contains(c))
+}
+
+#[test]
+fn registered_signatures_match_lobe_constraints() {
+    let registry = LobeRegistry::new();
+    let declared = declared_signatures();
+
+    for name in registry.names() {
+        // every constructor of a lobe shares the lobe's constraints
+        let lobe = name.split("::").next().unwrap();
+
+        let registered = registry.signature(name).unwrap();
+        let declared = match declared.get(lobe) {
+            Some(signature) => signature,
+            None => panic!("no constrain_lobe! for {:?}", lobe),
+        };
+
+        assert!(
+            same(&registered.inputs, &declared.inputs),
+            "inputs of {}: {:?} != {:?}",
+            name,
+            registered.inputs,
+            declared.inputs
+        );
+        assert!(
+            same(&registered.outputs, &declared.outputs),
+            "outputs of {}: {:?} != {:?}",
+            name,
+            registered.outputs,
+            declared.outputs
+        );
+        assert!(
+            same(&registered.feedback_inputs, &declared.feedback_inputs),
+            "feedback inputs of {}: {:?} != {:?}",
+            name,
+            registered.feedback_inputs,
+            declared.feedback_inputs
+        );
+        assert!(
+            same(&registered.feedback_outputs, &declared.feedback_outputs),
+            "feedback outputs of {}: {:?} != {:?}",
+            name,
+            registered.feedback_outputs,
+            declared.feedback_outputs
+        );
+    }
+}
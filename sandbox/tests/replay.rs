@@ -0,0 +1,176 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    Budget,
+    BudgetProbeLog,
+    FrameReplay,
+    LobeRegistry,
+    RecordedFrame,
+    RecordedState,
+    ReplayStep,
+    Topology,
+};
+use sc2::data::{ ActionTarget, UnitType };
+
+use common::{
+    edge,
+    filled,
+    image,
+    lobe,
+    register_budget_probe,
+    unit,
+    unit_type,
+};
+
+const STEPS: u32 = 10;
+
+const POOL_ABILITY: u32 = 1155;
+const POOL_COST: u32 = 200;
+
+/// a hatchery and a drone on a buildable map covered in creep, with the same
+/// amount of minerals in the bank on every step
+fn frames(minerals: u32) -> Vec<Rc<sc2::FrameData>> {
+    let mut pool = unit_type(
+        UnitType::ZergSpawningPool,
+        "SpawningPool",
+        POOL_COST,
+        0,
+        0.0,
+        POOL_ABILITY
+    );
+    pool.build_time = 1040.0;
+
+    common::frames(
+        vec![ pool ],
+        (0..STEPS).map(
+            |step| RecordedFrame {
+                state: RecordedState {
+                    current_step: step,
+                    minerals: minerals,
+                    vespene: 0,
+                    food_used: 10,
+                    food_cap: 14,
+                    larva_count: 3,
+                },
+                units: vec![
+                    unit(1, UnitType::ZergHatchery, 50.0, 50.0),
+                    unit(2, UnitType::ZergDrone, 45.0, 45.0),
+                ],
+                creep: filled(),
+                visibility: image(),
+            }
+        ).collect()
+    )
+}
+
+/// whole budget -> probe -> spawning pool morpher
+fn topology() -> Topology {
+    let mut params = toml::value::Table::new();
+    params.insert(
+        "unit_type".to_string(),
+        toml::Value::String("ZergSpawningPool".to_string())
+    );
+
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("probe", "Probe", Default::default()),
+            lobe("morpher", "RandomDroneMorpherLobe", params),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "morpher", &[ "FrameData" ]),
+            edge("whole_budget", "probe", &[ "Budget" ]),
+            edge("probe", "morpher", &[ "Budget" ]),
+            edge("morpher", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![
+            edge("morpher", "probe", &[ "Budget", "BudgetRequest" ]),
+        ],
+    }
+}
+
+/// replay the frames through the topology and return what the probe saw
+fn run(minerals: u32) -> (Vec<ReplayStep>, BudgetProbeLog) {
+    let mut registry = LobeRegistry::new();
+    let log = register_budget_probe(&mut registry, "Probe");
+
+    let cortex = topology().build_with(&registry).unwrap();
+
+    let mut replay = FrameReplay::from_cortex(cortex, frames(minerals))
+        .unwrap();
+
+    (replay.run().unwrap(), log)
+}
+
+#[test]
+fn replay_emits_morph_commands() {
+    let (steps, _) = run(250);
+
+    assert_eq!(steps.len(), STEPS as usize);
+
+    for (i, step) in steps.iter().enumerate() {
+        assert_eq!(step.step, i as u32);
+        assert_eq!(step.commands.len(), 1, "step {}", i);
+
+        match step.commands[0] {
+            sc2::Command::Action { ref units, ability, ref target } => {
+                assert_eq!(units.len(), 1);
+                assert_eq!(units[0].tag, 2);
+                assert_eq!(ability as u32, POOL_ABILITY);
+
+                match *target {
+                    Some(ActionTarget::Location(_)) => (),
+                    ref target => panic!("unexpected target {:?}", target),
+                }
+            },
+            ref command => panic!("unexpected command {:?}", command),
+        }
+    }
+}
+
+#[test]
+fn probe_records_budget_and_spending() {
+    let (_, log) = run(250);
+
+    let log = log.borrow();
+    let bank = Budget { minerals: 250, vespene: 0, food: 4, larva: 3 };
+    let cost = Budget { minerals: POOL_COST, ..Budget::default() };
+
+    assert_eq!(log.len(), STEPS as usize);
+
+    for (step, record) in log.iter().enumerate() {
+        assert_eq!(record.allotted, bank, "step {}", step);
+        assert_eq!(record.spent, cost, "step {}", step);
+    }
+}
+
+#[test]
+fn nothing_is_spent_without_enough_budget() {
+    let (steps, log) = run(POOL_COST - 1);
+
+    for step in &steps {
+        assert!(step.commands.is_empty(), "step {}", step.step);
+    }
+
+    let log = log.borrow();
+
+    assert_eq!(log.len(), STEPS as usize);
+
+    for (step, record) in log.iter().enumerate() {
+        assert_eq!(record.allotted.minerals, POOL_COST - 1, "step {}", step);
+        assert_eq!(record.spent, Budget::default(), "step {}", step);
+    }
+}
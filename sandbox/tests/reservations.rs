@@ -0,0 +1,281 @@
+extern crate sandbox;
+extern crate sc2;
+extern crate toml;
+
+mod common;
+
+use std::rc::Rc;
+
+use sandbox::{
+    FrameReplay,
+    LobeRegistry,
+    RecordedFrame,
+    RecordedOrder,
+    RecordedState,
+    RecordedUnit,
+    ReplayStep,
+    Topology,
+};
+use sc2::data::{ UnitType };
+
+use common::{
+    edge,
+    filled,
+    image,
+    lobe,
+    register_budget_probe,
+    unit,
+    unit_type,
+};
+
+const POOL_ABILITY: u32 = 1155;
+const POOL_COST: u32 = 200;
+const EVO_ABILITY: u32 = 1156;
+const EVO_COST: u32 = 1000;
+
+const DRONE: u64 = 2;
+
+/// what happens to the drone carrying out the order on a step
+#[derive(Copy, Clone)]
+enum Drone {
+    /// the drone is there without an order
+    Idle,
+    /// the drone is on its way to build a spawning pool
+    Building,
+    /// the drone turned into the structure
+    Gone,
+}
+
+/// a hatchery and a drone on a buildable map covered in creep, with the same
+/// bank on every step
+fn frames(minerals: u32, drone: &[Drone]) -> Vec<Rc<sc2::FrameData>> {
+    common::frames(
+        vec![
+            unit_type(
+                UnitType::ZergSpawningPool,
+                "SpawningPool",
+                POOL_COST,
+                0,
+                0.0,
+                POOL_ABILITY
+            ),
+            unit_type(
+                UnitType::ZergEvolutionChamber,
+                "EvolutionChamber",
+                EVO_COST,
+                0,
+                0.0,
+                EVO_ABILITY
+            ),
+        ],
+        drone.iter().enumerate().map(
+            |(step, drone)| RecordedFrame {
+                state: RecordedState {
+                    current_step: step as u32,
+                    minerals: minerals,
+                    vespene: 0,
+                    food_used: 10,
+                    food_cap: 14,
+                    larva_count: 3,
+                },
+                units: units(*drone),
+                creep: filled(),
+                visibility: image(),
+            }
+        ).collect()
+    )
+}
+
+fn units(drone: Drone) -> Vec<RecordedUnit> {
+    let mut units = vec![ unit(1, UnitType::ZergHatchery, 50.0, 50.0) ];
+
+    match drone {
+        Drone::Idle => {
+            units.push(unit(DRONE, UnitType::ZergDrone, 45.0, 45.0));
+        },
+        Drone::Building => {
+            let mut u = unit(DRONE, UnitType::ZergDrone, 45.0, 45.0);
+            u.orders.push(
+                RecordedOrder {
+                    ability: POOL_ABILITY, target: None, progress: 0.0
+                }
+            );
+
+            units.push(u);
+        },
+        Drone::Gone => (),
+    }
+
+    units
+}
+
+fn morpher(unit_type: &str) -> toml::value::Table {
+    let mut params = toml::value::Table::new();
+    params.insert(
+        "unit_type".to_string(), toml::Value::String(unit_type.to_string())
+    );
+
+    params
+}
+
+/// whole budget -> reservation ledger -> probe -> pool morpher
+fn reserving_topology(timeout: u32) -> Topology {
+    let mut params = toml::value::Table::new();
+    params.insert("timeout".to_string(), toml::Value::Integer(timeout as i64));
+
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("reservations", "ReservationLedgerLobe", params),
+            lobe("probe", "Probe", Default::default()),
+            lobe(
+                "pool",
+                "RandomDroneMorpherLobe",
+                morpher("ZergSpawningPool")
+            ),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "reservations", &[ "FrameData" ]),
+            edge("frame_forwarder", "pool", &[ "FrameData" ]),
+            edge("whole_budget", "reservations", &[ "Budget" ]),
+            edge("reservations", "probe", &[ "Budget" ]),
+            edge("probe", "pool", &[ "Budget" ]),
+            edge("pool", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![
+            edge("pool", "probe", &[ "Budget", "BudgetRequest" ]),
+            edge("pool", "reservations", &[ "Reservations" ]),
+        ],
+    }
+}
+
+/// replay the frames and return the minerals the probe was given each step
+fn reserved(timeout: u32, drone: &[Drone]) -> Vec<u32> {
+    let mut registry = LobeRegistry::new();
+    let log = register_budget_probe(&mut registry, "Probe");
+
+    let cortex = reserving_topology(timeout).build_with(&registry).unwrap();
+
+    FrameReplay::from_cortex(cortex, frames(300, drone))
+        .unwrap()
+        .run()
+        .unwrap()
+    ;
+
+    let records = log.borrow();
+
+    records.iter().map(|r| r.allotted.minerals).collect()
+}
+
+#[test]
+fn reservations_are_held_while_the_order_is_pending() {
+    let minerals = reserved(
+        100, &[ Drone::Idle, Drone::Building, Drone::Building ]
+    );
+
+    assert_eq!(minerals, vec![ 300, 100, 100 ]);
+}
+
+#[test]
+fn reservations_are_released_when_the_drone_is_gone() {
+    let minerals = reserved(
+        100, &[ Drone::Idle, Drone::Building, Drone::Gone ]
+    );
+
+    assert_eq!(minerals, vec![ 300, 100, 300 ]);
+}
+
+#[test]
+fn reservations_are_released_when_the_order_is_dropped() {
+    let minerals = reserved(
+        100, &[ Drone::Idle, Drone::Building, Drone::Idle, Drone::Idle ]
+    );
+
+    assert_eq!(&minerals[..3], &[ 300, 100, 300 ]);
+}
+
+#[test]
+fn reservations_are_released_when_they_time_out() {
+    // the drone never picks up the order, so only the timeout releases it
+    let minerals = reserved(
+        2, &[ Drone::Idle, Drone::Idle, Drone::Idle, Drone::Idle ]
+    );
+
+    assert_eq!(minerals, vec![ 300, 100, 100, 300 ]);
+}
+
+/// whole budget -> reservation ledger -> even split ledger -> pool and evo
+/// morphers
+fn refunding_topology() -> Topology {
+    Topology {
+        input: "frame_forwarder".to_string(),
+        output: "command_merger".to_string(),
+        seed: Some(7),
+
+        lobes: vec![
+            lobe("frame_forwarder", "FrameForwarderLobe", Default::default()),
+            lobe("whole_budget", "WholeBudgetLobe", Default::default()),
+            lobe("reservations", "ReservationLedgerLobe", Default::default()),
+            lobe("ledger", "EvenSplitLedgerLobe", Default::default()),
+            lobe(
+                "pool",
+                "RandomDroneMorpherLobe",
+                morpher("ZergSpawningPool")
+            ),
+            lobe(
+                "evo",
+                "RandomDroneMorpherLobe",
+                morpher("ZergEvolutionChamber")
+            ),
+            lobe("command_merger", "CommandMergerLobe", Default::default()),
+        ],
+        connections: vec![
+            edge("frame_forwarder", "whole_budget", &[ "FrameData" ]),
+            edge("frame_forwarder", "reservations", &[ "FrameData" ]),
+            edge("frame_forwarder", "pool", &[ "FrameData" ]),
+            edge("frame_forwarder", "evo", &[ "FrameData" ]),
+            edge("whole_budget", "reservations", &[ "Budget" ]),
+            edge("reservations", "ledger", &[ "Budget", "Reservations" ]),
+            edge("ledger", "pool", &[ "Budget" ]),
+            edge("ledger", "evo", &[ "Budget" ]),
+            edge("pool", "command_merger", &[ "Commands" ]),
+            edge("evo", "command_merger", &[ "Commands" ]),
+        ],
+        feedback: vec![
+            edge("pool", "ledger", &[ "Budget", "BudgetRequest" ]),
+            edge("evo", "ledger", &[ "Budget", "BudgetRequest" ]),
+            edge("pool", "reservations", &[ "Reservations" ]),
+            edge("evo", "reservations", &[ "Reservations" ]),
+        ],
+    }
+}
+
+#[test]
+fn dropped_orders_are_refunded_to_their_spender() {
+    let cortex = refunding_topology().build().unwrap();
+
+    let steps: Vec<ReplayStep> = FrameReplay::from_cortex(
+        cortex, frames(400, &[ Drone::Idle, Drone::Building, Drone::Idle ])
+    )
+        .unwrap()
+        .run()
+        .unwrap()
+    ;
+
+    // the evolution chamber is never affordable, so only the pool orders
+    assert_eq!(steps[0].commands.len(), 1);
+
+    // the pool spent its half of the bank on the order
+    assert_eq!(steps[1].commands.len(), 0);
+
+    // once the order is dropped, the pool gets its half back instead of
+    // being charged for a structure that never started
+    assert_eq!(steps[2].commands.len(), 1);
+}
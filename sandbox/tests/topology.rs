@@ -0,0 +1,154 @@
+extern crate sandbox;
+
+use sandbox::{ Error, ErrorKind, Topology };
+
+/// build a topology and return the error it fails with
+fn build_error(s: &str) -> Error {
+    match Topology::from_str(s).unwrap().build() {
+        Ok(_) => panic!("topology should not build:\n{}", s),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn unknown_lobe_types_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "frame_forwarder"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "NoSuchLobe"
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::UnknownLobe(ref name) => assert_eq!(name, "NoSuchLobe"),
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn unknown_constructors_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "frame_forwarder"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+        constructor = "no_such_constructor"
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::UnknownLobe(ref name) => assert_eq!(
+            name, "FrameForwarderLobe::no_such_constructor"
+        ),
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn unsupported_constraints_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "command_merger"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+
+        [[lobes]]
+        name = "command_merger"
+        type = "CommandMergerLobe"
+
+        [[connections]]
+        from = "frame_forwarder"
+        to = "command_merger"
+        constraints = [ "Commands" ]
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::ConstraintMismatch(ref from, ref to, ref constraint) => {
+            assert_eq!(from, "frame_forwarder");
+            assert_eq!(to, "command_merger");
+            assert_eq!(constraint, "Commands");
+        },
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn duplicate_lobe_names_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "frame_forwarder"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::InvalidTopology(_) => (),
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn edges_to_undeclared_lobes_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "frame_forwarder"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+
+        [[connections]]
+        from = "frame_forwarder"
+        to = "nowhere"
+        constraints = [ "FrameData" ]
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::InvalidTopology(ref msg) => assert!(
+            msg.contains("nowhere"), "{}", msg
+        ),
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn undeclared_outputs_are_rejected() {
+    let err = build_error(
+        r#"
+        input = "frame_forwarder"
+        output = "command_merger"
+
+        [[lobes]]
+        name = "frame_forwarder"
+        type = "FrameForwarderLobe"
+        "#
+    );
+
+    match *err.kind() {
+        ErrorKind::InvalidTopology(ref msg) => assert!(
+            msg.contains("command_merger"), "{}", msg
+        ),
+        ref kind => panic!("unexpected error {:?}", kind),
+    }
+}
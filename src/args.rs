@@ -23,6 +23,13 @@ Options:
   -m <path> --map=<path>            Path to the StarCraft II map.
   -r --realtime                     Run StarCraft II in real time
   -s <count> --step-size=<count>    How many steps to take per call.
+  -c <path> --cortex=<path>         Path to a cortex topology file.
+  --seed=<seed>                     Seed lobes that make random decisions.
+  --record=<path>                   Record every frame to a file for replay.
+  --log-commands=<path>             Log every command as JSON Lines.
+  --report-ledgers                  Summarize ledger spending after the game.
+  --ledger-csv=<path>               Write ledger spending history as CSV.
+  --list-lobes                      List the lobes available to the cortex.
 ";
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
@@ -35,6 +42,13 @@ pub struct Args {
     pub flag_map:           Option<PathBuf>,
     pub flag_realtime:      bool,
     pub flag_step_size:     Option<usize>,
+    pub flag_cortex:        Option<PathBuf>,
+    pub flag_list_lobes:    bool,
+    pub flag_seed:          Option<u64>,
+    pub flag_record:        Option<PathBuf>,
+    pub flag_log_commands:  Option<PathBuf>,
+    pub flag_report_ledgers: bool,
+    pub flag_ledger_csv:    Option<PathBuf>,
 }
 
 pub fn get_coordinator_settings(args: &Args) -> Result<CoordinatorSettings> {
@@ -1,12 +1,11 @@
 
+extern crate cortical;
 extern crate ctrlc;
 extern crate docopt;
 extern crate sandbox;
 extern crate sc2;
 extern crate tantrum;
 
-#[macro_use]
-extern crate cortical;
 #[macro_use]
 extern crate error_chain;
 #[macro_use]
@@ -18,32 +17,19 @@ mod errors;
 use std::sync::Arc;
 use std::sync::atomic::{ AtomicBool, Ordering };
 
-use cortical::{ CortexBuilder };
 use docopt::Docopt;
 use sc2::{ Coordinator, User };
-use sc2::data::{ UnitType, PlayerSetup, Difficulty, Race };
-use tantrum::{
-    ResourceLobe,
-    CommandMergerLobe,
-    FrameForwarderLobe,
-};
+use sc2::data::{ PlayerSetup, Difficulty, Race };
 
-use sandbox::{
-    create_keli_bot,
-    KeliCortex,
-    KeliConstraint,
-    NudgeBaseLocatorLobe,
-    WholeBudgetLobe,
-    EvenSplitLedgerLobe,
-    RandomDroneMorpherLobe,
-    DebugWindowLobe,
-};
+use sandbox::{ create_keli_bot, LobeRegistry, Topology };
 
 use args::{
     USAGE, VERSION, get_coordinator_settings, get_game_settings, Args
 };
 use errors::{ Result };
 
+const DEFAULT_CORTEX: &'static str = include_str!("../cortex/default.toml");
+
 quick_main!(|| -> Result<()> {
     let args: Args = Docopt::new(USAGE)
         .and_then(|d| d.deserialize())
@@ -55,6 +41,13 @@ quick_main!(|| -> Result<()> {
         return Ok(())
     }
 
+    if args.flag_list_lobes {
+        for name in LobeRegistry::new().names() {
+            println!("{}", name);
+        }
+        return Ok(())
+    }
+
     let coordinator_settings = get_coordinator_settings(&args)?;
     let game_settings = get_game_settings(&args)?;
 
@@ -65,131 +58,29 @@ quick_main!(|| -> Result<()> {
         race: Race::Terran, difficulty: Difficulty::VeryEasy
     };
 
-    let mut keli_builder = CortexBuilder::new();
-
-    let frame_forwarder_lobe = keli_builder.add_node(
-        Box::new(FrameForwarderLobe::new())
-    );
-
-    let debug_window_lobe = keli_builder.add_node(
-        Box::new(DebugWindowLobe::new())
-    );
-    let whole_budget_lobe = keli_builder.add_node(
-        Box::new(WholeBudgetLobe::new())
-    );
-    let even_split_ledger_lobe = keli_builder.add_node(
-        Box::new(EvenSplitLedgerLobe::new())
-    );
-    let resource_lobe = keli_builder.add_node(
-        Box::new(ResourceLobe::with_debug())
-    );
-    let base_locator_lobe = keli_builder.add_node(
-        Box::new(NudgeBaseLocatorLobe::with_debug())
-    );
-
-    let spawning_pool_morpher_lobe = keli_builder.add_node(
-        Box::new(
-            RandomDroneMorpherLobe::one_and_done(UnitType::ZergSpawningPool)
-        )
-    );
-    let evolution_chamber_morpher_lobe = keli_builder.add_node(
-        Box::new(
-            RandomDroneMorpherLobe::one_and_done(
-                UnitType::ZergEvolutionChamber
-            )
-        )
-    );
-
-    let command_merger_lobe = keli_builder.add_node(
-        Box::new(CommandMergerLobe::new())
-    );
-
-
-    keli_builder.connect(
-        frame_forwarder_lobe,
-        debug_window_lobe,
-        vec![ KeliConstraint::FrameData ]
-    )?;
-    keli_builder.connect(
-        frame_forwarder_lobe,
-        whole_budget_lobe,
-        vec![ KeliConstraint::FrameData ]
-    )?;
-    keli_builder.connect(
-        frame_forwarder_lobe,
-        spawning_pool_morpher_lobe,
-        vec![ KeliConstraint::FrameData ]
-    )?;
-    keli_builder.connect(
-        frame_forwarder_lobe,
-        evolution_chamber_morpher_lobe,
-        vec![ KeliConstraint::FrameData ]
-    )?;
-    keli_builder.connect(
-        frame_forwarder_lobe,
-        resource_lobe,
-        vec![ KeliConstraint::FrameData ]
-    )?;
-
-    keli_builder.connect(
-        whole_budget_lobe,
-        even_split_ledger_lobe,
-        vec![ KeliConstraint::Budget ]
-    )?;
-
-    keli_builder.connect(
-        even_split_ledger_lobe,
-        spawning_pool_morpher_lobe,
-        vec![ KeliConstraint::Budget ]
-    )?;
-    keli_builder.feedback(
-        spawning_pool_morpher_lobe,
-        even_split_ledger_lobe,
-        vec![ KeliConstraint::Budget ]
-    )?;
-
-    keli_builder.connect(
-        even_split_ledger_lobe,
-        evolution_chamber_morpher_lobe,
-        vec![ KeliConstraint::Budget ]
-    )?;
-    keli_builder.feedback(
-        evolution_chamber_morpher_lobe,
-        even_split_ledger_lobe,
-        vec![ KeliConstraint::Budget ]
-    )?;
-
-    keli_builder.connect(
-        resource_lobe,
-        base_locator_lobe,
-        vec![ KeliConstraint::Resources ]
-    )?;
-
-    keli_builder.connect(
-        spawning_pool_morpher_lobe,
-        command_merger_lobe,
-        vec![ KeliConstraint::Commands ]
-    )?;
-    keli_builder.connect(
-        evolution_chamber_morpher_lobe,
-        command_merger_lobe,
-        vec![ KeliConstraint::Commands ]
-    )?;
-    keli_builder.connect(
-        resource_lobe,
-        command_merger_lobe,
-        vec![ KeliConstraint::Commands ]
-    )?;
-    keli_builder.connect(
-        base_locator_lobe,
-        command_merger_lobe,
-        vec![ KeliConstraint::Commands ]
-    )?;
+    let mut topology = match args.flag_cortex {
+        Some(ref path) => Topology::from_file(path)?,
+        None => Topology::from_str(DEFAULT_CORTEX)?
+    };
 
-    keli_builder.set_input(frame_forwarder_lobe);
-    keli_builder.set_output(command_merger_lobe);
+    if let Some(seed) = args.flag_seed {
+        topology.seed = Some(seed);
+    }
+    if let Some(ref path) = args.flag_record {
+        topology.record_frames(path);
+    }
+    if let Some(ref path) = args.flag_log_commands {
+        topology.log_commands(path);
+    }
+    if args.flag_report_ledgers || args.flag_ledger_csv.is_some() {
+        topology.report_ledgers(
+            &LobeRegistry::new(),
+            args.flag_ledger_csv.as_ref().map(|path| path.as_path()),
+            args.flag_report_ledgers
+        )?;
+    }
 
-    let bot = create_keli_bot(KeliCortex(keli_builder.build()?))?;
+    let bot = create_keli_bot(topology.build()?)?;
 
     coordinator.launch_starcraft(
         vec![